@@ -0,0 +1,21 @@
+[
+  (block)
+  (declaration_list)
+  (field_declaration_list)
+  (enum_variant_list)
+  (field_initializer_list)
+  (match_block)
+  (use_list)
+  (arguments)
+  (parameters)
+  (array_expression)
+  (tuple_expression)
+  (token_tree)
+  (where_clause)
+] @indent
+
+[
+  "}"
+  ")"
+  "]"
+] @outdent
@@ -38,6 +38,10 @@ pub struct Colors {
     pub text: Text,
     #[serde(default)]
     pub buttons: Buttons,
+    #[serde(default)]
+    pub editor: Editor,
+    #[serde(default)]
+    pub syntax: Syntax,
 }
 
 impl Colors {
@@ -107,6 +111,59 @@ pub struct Text {
     pub error: Color,
 }
 
+/// The colors of the text editor itself, around and behind the text.
+#[derive(Debug, Clone, Copy, Default, Deserialize, Serialize)]
+pub struct Editor {
+    #[serde(default = "default_transparent", with = "color_serde")]
+    pub cursor: Color,
+    #[serde(default = "default_transparent", with = "color_serde")]
+    pub selection: Color,
+    #[serde(default = "default_transparent", with = "color_serde")]
+    pub current_line: Color,
+    #[serde(default = "default_transparent", with = "color_serde")]
+    pub gutter: Color,
+    #[serde(default = "default_transparent", with = "color_serde")]
+    pub line_number: Color,
+    #[serde(default = "default_transparent", with = "color_serde")]
+    pub line_number_active: Color,
+    #[serde(default = "default_transparent", with = "color_serde")]
+    pub diff_added: Color,
+    #[serde(default = "default_transparent", with = "color_serde")]
+    pub diff_modified: Color,
+    #[serde(default = "default_transparent", with = "color_serde")]
+    pub diff_removed: Color,
+}
+
+/// The colors of highlighted code, a transparent color leaves the text in
+/// the primary text color.
+#[derive(Debug, Clone, Copy, Default, Deserialize, Serialize)]
+pub struct Syntax {
+    #[serde(default = "default_transparent", with = "color_serde")]
+    pub comment: Color,
+    #[serde(default = "default_transparent", with = "color_serde")]
+    pub keyword: Color,
+    #[serde(default = "default_transparent", with = "color_serde")]
+    pub string: Color,
+    #[serde(default = "default_transparent", with = "color_serde")]
+    pub number: Color,
+    #[serde(default = "default_transparent", with = "color_serde")]
+    pub constant: Color,
+    #[serde(default = "default_transparent", with = "color_serde")]
+    pub function: Color,
+    #[serde(default = "default_transparent", with = "color_serde")]
+    pub r#type: Color,
+    #[serde(default = "default_transparent", with = "color_serde")]
+    pub property: Color,
+    #[serde(default = "default_transparent", with = "color_serde")]
+    pub attribute: Color,
+    #[serde(default = "default_transparent", with = "color_serde")]
+    pub tag: Color,
+    #[serde(default = "default_transparent", with = "color_serde")]
+    pub operator: Color,
+    #[serde(default = "default_transparent", with = "color_serde")]
+    pub punctuation: Color,
+}
+
 fn default_transparent() -> Color {
     Color::TRANSPARENT
 }
@@ -0,0 +1,668 @@
+use std::borrow::Cow;
+use std::ops::Range;
+use std::path::Path;
+use std::sync::Arc;
+use std::{fmt, io};
+
+use ropey::{Rope, RopeSlice};
+use serde::{Deserialize, Serialize};
+use tokio::fs;
+use tokio::io::AsyncWriteExt;
+
+use crate::encoding::{self, Encoding};
+use crate::line_ending::{self, LineEnding};
+
+/// A line / column location inside of a [`Buffer`].
+///
+/// The column is measured in bytes from the start of the line, matching the
+/// cursor reported by the editor widget.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Position {
+    pub line: usize,
+    pub column: usize,
+}
+
+impl Position {
+    #[must_use]
+    pub fn new(line: usize, column: usize) -> Self {
+        Self { line, column }
+    }
+}
+
+impl From<(usize, usize)> for Position {
+    fn from((line, column): (usize, usize)) -> Self {
+        Self { line, column }
+    }
+}
+
+/// A single replacement of a char range with new text.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Edit {
+    pub range: Range<usize>,
+    pub text: String,
+}
+
+impl Edit {
+    #[must_use]
+    pub fn insert(at: usize, text: impl Into<String>) -> Self {
+        Self {
+            range: at..at,
+            text: text.into(),
+        }
+    }
+
+    #[must_use]
+    pub fn remove(range: Range<usize>) -> Self {
+        Self {
+            range,
+            text: String::new(),
+        }
+    }
+
+    #[must_use]
+    pub fn replace(range: Range<usize>, text: impl Into<String>) -> Self {
+        Self {
+            range,
+            text: text.into(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.range.is_empty() && self.text.is_empty()
+    }
+}
+
+/// A text document backed by a rope.
+///
+/// Every edit bumps the revision, which can be used to tell snapshots of the
+/// same buffer apart without comparing their contents.
+#[derive(Clone, Default)]
+pub struct Buffer {
+    rope: Rope,
+    revision: u64,
+}
+
+impl fmt::Debug for Buffer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Buffer")
+            .field("len_chars", &self.rope.len_chars())
+            .field("len_lines", &self.rope.len_lines())
+            .field("revision", &self.revision)
+            .finish()
+    }
+}
+
+impl Buffer {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    #[must_use]
+    pub fn with_text(text: &str) -> Self {
+        Self {
+            rope: Rope::from_str(text),
+            revision: 0,
+        }
+    }
+
+    /// Reads the file at `path`, decoding it with `encoding` or the encoding
+    /// detected from its bytes, which is returned along with the buffer.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Error` if the file cannot be read or is not valid in the
+    /// encoding.
+    pub async fn load(
+        path: impl AsRef<Path>,
+        encoding: Option<Encoding>,
+    ) -> Result<(Self, Encoding), Error> {
+        let bytes = fs::read(path).await?;
+        let encoding = encoding.unwrap_or_else(|| Encoding::detect(&bytes));
+        let text = encoding.decode(&bytes)?;
+
+        Ok((Self::with_text(&text), encoding))
+    }
+
+    pub fn revision(&self) -> u64 {
+        self.revision
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rope.len_chars() == 0
+    }
+
+    pub fn len_chars(&self) -> usize {
+        self.rope.len_chars()
+    }
+
+    pub fn len_bytes(&self) -> usize {
+        self.rope.len_bytes()
+    }
+
+    pub fn len_lines(&self) -> usize {
+        self.rope.len_lines()
+    }
+
+    /// Returns the whole buffer as a `String`.
+    ///
+    /// This copies the entire rope, prefer [`Buffer::snapshot`] or
+    /// [`Buffer::chunks`] when the text is only streamed somewhere else.
+    pub fn text(&self) -> String {
+        self.rope.to_string()
+    }
+
+    pub fn chunks(&self) -> impl Iterator<Item = &str> {
+        self.rope.chunks()
+    }
+
+    /// Returns the chunks of the rope holding the bytes in `range`.
+    pub fn chunks_in(&self, range: Range<usize>) -> impl Iterator<Item = &str> {
+        let end = range.end.min(self.rope.len_bytes());
+
+        self.rope.byte_slice(range.start.min(end)..end).chunks()
+    }
+
+    /// Returns the text from `byte_index` to the end of the chunk of the rope
+    /// it falls in, or an empty string past the end of the buffer.
+    pub fn chunk_at_byte(&self, byte_index: usize) -> &str {
+        if byte_index >= self.rope.len_bytes() {
+            return "";
+        }
+
+        let (chunk, start, _, _) = self.rope.chunk_at_byte(byte_index);
+
+        &chunk[byte_index - start..]
+    }
+
+    pub fn slice(&self, range: Range<usize>) -> Cow<'_, str> {
+        let range = self.clamp(range);
+
+        self.rope.slice(range).into()
+    }
+
+    /// Returns the line at `index`, including its line ending.
+    pub fn line(&self, index: usize) -> Option<Cow<'_, str>> {
+        self.rope.get_line(index).map(Cow::from)
+    }
+
+    /// Returns the line at `index`, without its line ending.
+    pub fn line_text(&self, index: usize) -> Option<Cow<'_, str>> {
+        self.rope
+            .get_line(index)
+            .map(|line| trim_line_ending(line).into())
+    }
+
+    /// Returns the length in bytes of the line at `index`, without its line
+    /// ending.
+    pub fn line_len(&self, index: usize) -> Option<usize> {
+        self.rope
+            .get_line(index)
+            .map(|line| trim_line_ending(line).len_bytes())
+    }
+
+    /// How many lines end with each line ending.
+    pub fn line_endings(&self) -> line_ending::Counts {
+        let mut counts = line_ending::Counts::default();
+
+        for line_ending in self.rope.lines().filter_map(LineEnding::of) {
+            counts.add(line_ending);
+        }
+
+        counts
+    }
+
+    /// The line ending most lines end with, the one new lines get.
+    pub fn line_ending(&self) -> LineEnding {
+        self.line_endings().dominant().unwrap_or_default()
+    }
+
+    /// The edits turning every other line ending into `line_ending`, in the
+    /// order of the text. Applied last first, each one leaves the ranges of
+    /// the ones before it as they were.
+    pub fn convert_line_endings(&self, line_ending: LineEnding) -> Vec<Edit> {
+        let mut edits = vec![];
+        let mut end = 0;
+
+        for line in self.rope.lines() {
+            end += line.len_chars();
+
+            if let Some(found) = LineEnding::of(line)
+                && found != line_ending
+            {
+                edits.push(Edit::replace(
+                    end - found.as_str().len()..end,
+                    line_ending.as_str(),
+                ));
+            }
+        }
+
+        edits
+    }
+
+    pub fn char_to_byte(&self, char_index: usize) -> usize {
+        self.rope.char_to_byte(char_index.min(self.rope.len_chars()))
+    }
+
+    pub fn byte_to_char(&self, byte_index: usize) -> usize {
+        self.rope.byte_to_char(byte_index.min(self.rope.len_bytes()))
+    }
+
+    pub fn char_to_line(&self, char_index: usize) -> usize {
+        self.rope.char_to_line(char_index.min(self.rope.len_chars()))
+    }
+
+    pub fn line_to_char(&self, line: usize) -> usize {
+        self.rope.line_to_char(line.min(self.rope.len_lines()))
+    }
+
+    pub fn line_to_byte(&self, line: usize) -> usize {
+        self.rope.line_to_byte(line.min(self.rope.len_lines()))
+    }
+
+    /// Converts a [`Position`] into a char index, clamping it to the end of its
+    /// line.
+    pub fn position_to_char(&self, position: Position) -> usize {
+        let Some(line) = self.rope.get_line(position.line) else {
+            return self.rope.len_chars();
+        };
+
+        let line = trim_line_ending(line);
+        let column = floor_char_boundary(line, position.column.min(line.len_bytes()));
+
+        self.rope.line_to_char(position.line) + line.byte_to_char(column)
+    }
+
+    pub fn char_to_position(&self, char_index: usize) -> Position {
+        let char_index = char_index.min(self.rope.len_chars());
+        let line = self.rope.char_to_line(char_index);
+        let column = self.rope.char_to_byte(char_index) - self.rope.line_to_byte(line);
+
+        Position { line, column }
+    }
+
+    /// Converts a [`Position`] into a byte offset, clamping it like
+    /// [`Buffer::position_to_char`].
+    pub fn position_to_byte(&self, position: Position) -> usize {
+        self.char_to_byte(self.position_to_char(position))
+    }
+
+    pub fn byte_to_position(&self, byte_index: usize) -> Position {
+        self.char_to_position(self.byte_to_char(byte_index))
+    }
+
+    pub fn insert(&mut self, at: usize, text: &str) -> Edit {
+        self.apply(Edit::insert(at, text))
+    }
+
+    pub fn remove(&mut self, range: Range<usize>) -> Edit {
+        self.apply(Edit::remove(range))
+    }
+
+    pub fn replace(&mut self, range: Range<usize>, text: &str) -> Edit {
+        self.apply(Edit::replace(range, text))
+    }
+
+    /// Applies the [`Edit`] and returns the inverse edit that undoes it.
+    pub fn apply(&mut self, edit: Edit) -> Edit {
+        let range = self.clamp(edit.range);
+        let removed = self.rope.slice(range.clone()).to_string();
+
+        if !range.is_empty() {
+            self.rope.remove(range.clone());
+        }
+
+        if !edit.text.is_empty() {
+            self.rope.insert(range.start, &edit.text);
+        }
+
+        self.revision += 1;
+
+        Edit {
+            range: range.start..range.start + edit.text.chars().count(),
+            text: removed,
+        }
+    }
+
+    /// Replaces the whole buffer with `text`.
+    pub fn set_text(&mut self, text: &str) {
+        self.rope = Rope::from_str(text);
+        self.revision += 1;
+    }
+
+    /// The [`Edit`] that turns the buffer into `text`, replacing what lies
+    /// between the prefix and suffix they have in common.
+    pub fn diff(&self, text: &str) -> Edit {
+        let old = self.text();
+        let old_len = self.rope.len_chars();
+        let new_len = text.chars().count();
+
+        let prefix = old
+            .chars()
+            .zip(text.chars())
+            .take_while(|(a, b)| a == b)
+            .count();
+        let suffix = old
+            .chars()
+            .rev()
+            .zip(text.chars().rev())
+            .take(old_len.min(new_len) - prefix)
+            .take_while(|(a, b)| a == b)
+            .count();
+
+        Edit::replace(
+            prefix..old_len - suffix,
+            text.chars()
+                .skip(prefix)
+                .take(new_len - prefix - suffix)
+                .collect::<String>(),
+        )
+    }
+
+    /// A hash of the text, the same across runs, to tell whether a file
+    /// still holds the text something was remembered for.
+    pub fn hash(&self) -> u64 {
+        self.chunks()
+            .fold(FNV_OFFSET, |hash, chunk| fnv(hash, chunk.as_bytes()))
+    }
+
+    /// Remembers the current text, to tell later whether the buffer still
+    /// holds it.
+    pub fn checkpoint(&self) -> Checkpoint {
+        Checkpoint {
+            revision: self.revision,
+            len_bytes: self.len_bytes(),
+            hash: self.hash(),
+        }
+    }
+
+    /// Takes a cheap, immutable copy of the buffer.
+    pub fn snapshot(&self) -> Snapshot {
+        Snapshot {
+            rope: Arc::new(self.rope.clone()),
+            revision: self.revision,
+        }
+    }
+
+    fn clamp(&self, range: Range<usize>) -> Range<usize> {
+        let len = self.rope.len_chars();
+        let end = range.end.min(len);
+
+        range.start.min(end)..end
+    }
+}
+
+/// The text of a [`Buffer`] at some point, like when it was last saved.
+///
+/// Edits that end up where the text was, like undoing back to it, still
+/// match the checkpoint.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Checkpoint {
+    revision: u64,
+    len_bytes: usize,
+    hash: u64,
+}
+
+impl Checkpoint {
+    /// Whether `buffer` holds the text it held at the checkpoint.
+    ///
+    /// The text is only hashed again when its length is the same.
+    pub fn matches(&self, buffer: &Buffer) -> bool {
+        self.revision == buffer.revision
+            || (self.len_bytes == buffer.len_bytes() && self.hash == buffer.hash())
+    }
+
+    /// The hash of the text at the checkpoint, see [`Buffer::hash`].
+    pub fn hash(&self) -> u64 {
+        self.hash
+    }
+}
+
+const FNV_OFFSET: u64 = 0xcbf2_9ce4_8422_2325;
+const FNV_PRIME: u64 = 0x0100_0000_01b3;
+
+/// Hashes `bytes` into `hash` with FNV-1a, which unlike the hasher of the
+/// standard library is stable across runs and Rust versions.
+pub(crate) fn fnv(hash: u64, bytes: &[u8]) -> u64 {
+    bytes.iter().fold(hash, |hash, byte| {
+        (hash ^ u64::from(*byte)).wrapping_mul(FNV_PRIME)
+    })
+}
+
+/// Hashes `bytes` on their own, see [`fnv`].
+pub(crate) fn fnv_hash(bytes: &[u8]) -> u64 {
+    fnv(FNV_OFFSET, bytes)
+}
+
+impl From<&str> for Buffer {
+    fn from(text: &str) -> Self {
+        Self::with_text(text)
+    }
+}
+
+/// An immutable view of a [`Buffer`] at a given revision.
+#[derive(Clone)]
+pub struct Snapshot {
+    rope: Arc<Rope>,
+    revision: u64,
+}
+
+impl fmt::Debug for Snapshot {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Snapshot")
+            .field("len_chars", &self.rope.len_chars())
+            .field("revision", &self.revision)
+            .finish()
+    }
+}
+
+impl Snapshot {
+    pub fn revision(&self) -> u64 {
+        self.revision
+    }
+
+    pub fn len_chars(&self) -> usize {
+        self.rope.len_chars()
+    }
+
+    pub fn chunks(&self) -> impl Iterator<Item = &str> {
+        self.rope.chunks()
+    }
+
+    pub fn text(&self) -> String {
+        self.rope.to_string()
+    }
+
+    pub fn ends_with(&self, suffix: &str) -> bool {
+        let len = self.rope.len_bytes();
+
+        len >= suffix.len()
+            && self
+                .rope
+                .byte_slice(len - suffix.len()..)
+                .chars()
+                .eq(suffix.chars())
+    }
+
+    /// Writes the snapshot to `path` in `encoding`, appending `line_ending`
+    /// if the text does not already end with it.
+    ///
+    /// The whole text is encoded before the file is created, so a char the
+    /// encoding can't represent leaves the file as it was.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Error` if the text cannot be encoded, or if the file
+    /// cannot be created or written to.
+    pub async fn save(
+        &self,
+        path: impl AsRef<Path>,
+        line_ending: Option<&str>,
+        encoding: Encoding,
+    ) -> Result<(), Error> {
+        let mut bytes = Vec::with_capacity(self.rope.len_bytes());
+        bytes.extend_from_slice(encoding.bom());
+
+        let mut encoder = encoding.encoder();
+
+        for chunk in self.rope.chunks() {
+            encoder.encode(chunk, &mut bytes)?;
+        }
+
+        if let Some(line_ending) = line_ending
+            && !self.ends_with(line_ending)
+        {
+            encoder.encode(line_ending, &mut bytes)?;
+        }
+
+        encoder.finish(&mut bytes)?;
+
+        let mut file = fs::File::create(path).await?;
+
+        file.write_all(&bytes).await?;
+        file.flush().await?;
+
+        Ok(())
+    }
+}
+
+fn trim_line_ending(line: RopeSlice<'_>) -> RopeSlice<'_> {
+    let ending = LineEnding::of(line).map_or(0, |ending| ending.as_str().len());
+
+    line.slice(..line.len_chars() - ending)
+}
+
+fn floor_char_boundary(line: RopeSlice<'_>, byte: usize) -> usize {
+    let char_index = line.byte_to_char(byte);
+
+    line.char_to_byte(char_index)
+}
+
+#[derive(Debug, Clone, thiserror::Error)]
+pub enum Error {
+    #[error(transparent)]
+    Io(Arc<io::Error>),
+    #[error(transparent)]
+    Encoding(#[from] encoding::Error),
+}
+
+impl Error {
+    pub fn kind(&self) -> io::ErrorKind {
+        match self {
+            Error::Io(error) => error.kind(),
+            Error::Encoding(_) => io::ErrorKind::InvalidData,
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(error: io::Error) -> Self {
+        Self::Io(Arc::new(error))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn applying_the_inverse_undoes_an_edit() {
+        let mut buffer = Buffer::with_text("héllo wörld");
+
+        let inverse = buffer.apply(Edit::replace(1..5, "ey"));
+        assert_eq!(buffer.text(), "hey wörld");
+
+        let redo = buffer.apply(inverse);
+        assert_eq!(buffer.text(), "héllo wörld");
+
+        buffer.apply(redo);
+        assert_eq!(buffer.text(), "hey wörld");
+    }
+
+    #[test]
+    fn edits_past_the_end_are_clamped() {
+        let mut buffer = Buffer::with_text("abc");
+
+        let inverse = buffer.apply(Edit::remove(2..10));
+        assert_eq!(buffer.text(), "ab");
+        assert_eq!(inverse, Edit::insert(2, "c"));
+    }
+
+    #[test]
+    fn positions_skip_crlf_line_endings() {
+        let buffer = Buffer::with_text("ab\r\ncd\r\n");
+
+        assert_eq!(buffer.len_lines(), 3);
+        assert_eq!(buffer.char_to_position(4), Position::new(1, 0));
+        assert_eq!(buffer.position_to_char(Position::new(1, 1)), 5);
+        // Columns past the end of a line stop before its line ending.
+        assert_eq!(buffer.position_to_char(Position::new(0, 10)), 2);
+        assert_eq!(buffer.position_to_char(Position::new(5, 0)), 8);
+    }
+
+    #[test]
+    fn positions_count_columns_in_bytes() {
+        let buffer = Buffer::with_text("héllo\nwörld");
+
+        assert_eq!(buffer.char_to_position(2), Position::new(0, 3));
+        assert_eq!(buffer.char_to_position(9), Position::new(1, 4));
+        assert_eq!(buffer.position_to_char(Position::new(0, 3)), 2);
+        // A column inside of a char is moved back to its start.
+        assert_eq!(buffer.position_to_char(Position::new(1, 2)), 7);
+
+        for index in 0..=buffer.len_chars() {
+            assert_eq!(
+                buffer.position_to_char(buffer.char_to_position(index)),
+                index
+            );
+        }
+    }
+
+    #[test]
+    fn checkpoint_matches_the_same_text() {
+        let mut buffer = Buffer::with_text("abc");
+        let checkpoint = buffer.checkpoint();
+
+        assert!(checkpoint.matches(&buffer));
+
+        let inverse = buffer.insert(3, "d");
+        assert!(!checkpoint.matches(&buffer));
+
+        buffer.apply(inverse);
+        assert!(checkpoint.matches(&buffer));
+
+        buffer.replace(0..3, "xyz");
+        assert!(!checkpoint.matches(&buffer));
+    }
+
+    #[test]
+    fn convert_line_endings_replaces_the_others() {
+        let mut buffer = Buffer::with_text("a\r\nb\nc\rd\n");
+
+        let edits = buffer.convert_line_endings(LineEnding::Lf);
+        assert_eq!(edits.len(), 2);
+
+        for edit in edits.into_iter().rev() {
+            buffer.apply(edit);
+        }
+
+        assert_eq!(buffer.text(), "a\nb\nc\nd\n");
+        assert!(buffer.convert_line_endings(LineEnding::Lf).is_empty());
+    }
+
+    #[test]
+    fn convert_line_endings_to_crlf() {
+        let mut buffer = Buffer::with_text("a\nb\r\nc");
+
+        for edit in buffer
+            .convert_line_endings(LineEnding::Crlf)
+            .into_iter()
+            .rev()
+        {
+            buffer.apply(edit);
+        }
+
+        assert_eq!(buffer.text(), "a\r\nb\r\nc");
+        assert_eq!(buffer.line_ending(), LineEnding::Crlf);
+    }
+}
@@ -0,0 +1,693 @@
+//! Cursors besides the one of the editor widget.
+//!
+//! The widget only knows of a single cursor, the others are kept as
+//! [`Caret`]s in the document and moved and edited along with it.
+
+use std::ops::Range;
+
+pub use iced_core::text::editor::Motion;
+
+use crate::buffer::{Buffer, Edit, Position};
+
+/// How many lines paging moves a caret by, the editor widget pages by the
+/// height of its viewport instead.
+const PAGE_LINES: usize = 20;
+
+/// A cursor with the other end of its selection.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Caret {
+    /// Where the selection started, the same as `head` without one.
+    pub anchor: Position,
+    /// Where the cursor is.
+    pub head: Position,
+}
+
+impl Caret {
+    pub fn new(position: Position) -> Self {
+        Self {
+            anchor: position,
+            head: position,
+        }
+    }
+
+    /// A caret selecting `range`, with the cursor at its end.
+    pub fn select(range: Range<Position>) -> Self {
+        Self {
+            anchor: range.start,
+            head: range.end,
+        }
+    }
+
+    /// The selected range, from the first of its ends to the last.
+    pub fn range(&self) -> Range<Position> {
+        self.anchor.min(self.head)..self.anchor.max(self.head)
+    }
+
+    pub fn is_selection(&self) -> bool {
+        self.anchor != self.head
+    }
+
+    /// Whether the caret and `other` share text, or sit at the same spot.
+    pub fn overlaps(&self, other: &Caret) -> bool {
+        let (a, b) = (self.range(), other.range());
+
+        a == b || (a.start < b.end && b.start < a.end)
+    }
+
+    /// Moves the cursor with `motion`, extending the selection when `select`
+    /// is set or dropping it otherwise.
+    ///
+    /// Moving left or right out of a selection stops at its edge.
+    #[must_use]
+    pub fn moved(self, buffer: &Buffer, motion: Motion, select: bool) -> Self {
+        if select {
+            return Self {
+                anchor: self.anchor,
+                head: moved(buffer, self.head, motion),
+            };
+        }
+
+        match motion {
+            Motion::Left if self.is_selection() => Self::new(self.range().start),
+            Motion::Right if self.is_selection() => Self::new(self.range().end),
+            _ => Self::new(moved(buffer, self.head, motion)),
+        }
+    }
+
+    /// Keeps both ends of the caret inside of `buffer`, after it shrunk.
+    #[must_use]
+    pub fn clamp(self, buffer: &Buffer) -> Self {
+        let clamp = |position| buffer.char_to_position(buffer.position_to_char(position));
+
+        Self {
+            anchor: clamp(self.anchor),
+            head: clamp(self.head),
+        }
+    }
+}
+
+/// A line and a column of a [`Buffer`], the column counted in chars rather
+/// than bytes so it lines up with the same column of other lines.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct Cell {
+    pub line: usize,
+    pub column: usize,
+}
+
+impl Cell {
+    pub fn at(buffer: &Buffer, position: Position) -> Self {
+        let line = buffer.line_text(position.line).unwrap_or_default();
+        let column = floor_char_boundary(&line, position.column);
+
+        Self {
+            line: position.line,
+            column: line[..column].chars().count(),
+        }
+    }
+
+    /// The position of the cell, at the end of its line if the line is
+    /// shorter than the column.
+    pub fn position(&self, buffer: &Buffer) -> Position {
+        let line = buffer.line_text(self.line).unwrap_or_default();
+        let column = line
+            .char_indices()
+            .nth(self.column)
+            .map_or(line.len(), |(index, _)| index);
+
+        Position::new(self.line, column)
+    }
+}
+
+/// A rectangular selection, from the corner it started at to the one the
+/// cursor is at.
+///
+/// The columns may reach past the end of short lines, which are then
+/// selected up to their end.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Block {
+    pub anchor: Cell,
+    pub head: Cell,
+}
+
+impl Block {
+    pub fn new(cell: Cell) -> Self {
+        Self {
+            anchor: cell,
+            head: cell,
+        }
+    }
+
+    /// Moves the corner of the cursor by a line or a column with `motion`,
+    /// other motions leave the block as it is.
+    #[must_use]
+    pub fn moved(self, buffer: &Buffer, motion: Motion) -> Self {
+        let Cell { line, column } = self.head;
+        let last_line = buffer.len_lines().saturating_sub(1);
+
+        let head = match motion {
+            Motion::Up => Cell {
+                line: line.saturating_sub(1),
+                column,
+            },
+            Motion::Down => Cell {
+                line: (line + 1).min(last_line),
+                column,
+            },
+            Motion::Left => Cell {
+                line,
+                column: column.saturating_sub(1),
+            },
+            Motion::Right => Cell {
+                line,
+                column: column + 1,
+            },
+            _ => self.head,
+        };
+
+        Self { head, ..self }
+    }
+
+    /// A caret per line of the block selecting its columns, the one on the
+    /// line of the cursor first.
+    pub fn carets(&self, buffer: &Buffer) -> (Caret, Vec<Caret>) {
+        let caret = |line| Caret {
+            anchor: Cell {
+                line,
+                column: self.anchor.column,
+            }
+            .position(buffer),
+            head: Cell {
+                line,
+                column: self.head.column,
+            }
+            .position(buffer),
+        };
+
+        let lines = self.anchor.line.min(self.head.line)..=self.anchor.line.max(self.head.line);
+
+        (
+            caret(self.head.line),
+            lines
+                .filter(|line| *line != self.head.line)
+                .map(caret)
+                .collect(),
+        )
+    }
+}
+
+/// Sorts `carets` in document order, merging the ones that overlap.
+pub fn merge(mut carets: Vec<Caret>) -> Vec<Caret> {
+    carets.sort_by_key(|caret| caret.range().start);
+
+    let mut merged: Vec<Caret> = Vec::with_capacity(carets.len());
+
+    for caret in carets {
+        match merged.last_mut() {
+            Some(last) if last.overlaps(&caret) => {
+                let end = last.range().end.max(caret.range().end);
+
+                *last = Caret::select(last.range().start..end);
+            }
+            _ => merged.push(caret),
+        }
+    }
+
+    merged
+}
+
+/// What is typed at a caret.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Input {
+    /// Text replacing the selection.
+    Insert(String),
+    /// Deletes the selection, or the char before the cursor.
+    Backspace,
+    /// Deletes the selection, or the char after the cursor.
+    Delete,
+}
+
+/// The edits typing `inputs` at the merged `carets` does, one input per
+/// caret.
+///
+/// The edits are returned in document order and have to be applied from the
+/// last one up, along with the char index each caret ends up at once all of
+/// them are.
+pub fn edits(buffer: &Buffer, carets: &[Caret], inputs: &[Input]) -> (Vec<Edit>, Vec<usize>) {
+    let mut edits = Vec::with_capacity(carets.len());
+    let mut heads = Vec::with_capacity(carets.len());
+    let mut shift = 0isize;
+    let mut previous_end = 0;
+
+    for (caret, input) in carets.iter().zip(inputs) {
+        let range = caret.range();
+        let mut start = buffer.position_to_char(range.start);
+        let mut end = buffer.position_to_char(range.end);
+
+        let text = match input {
+            Input::Insert(text) => text.clone(),
+            Input::Backspace => {
+                if start == end {
+                    start = previous_char(buffer, start);
+                }
+
+                String::new()
+            }
+            Input::Delete => {
+                if start == end {
+                    end = next_char(buffer, end);
+                }
+
+                String::new()
+            }
+        };
+
+        let start = start.max(previous_end);
+        let end = end.max(start);
+        let inserted = text.chars().count();
+
+        heads.push((start as isize + shift) as usize + inserted);
+        shift += inserted as isize - (end - start) as isize;
+        previous_end = end;
+
+        edits.push(Edit::replace(start..end, text));
+    }
+
+    (edits, heads)
+}
+
+/// The range of the word `position` is in or right after.
+pub fn word_at(buffer: &Buffer, position: Position) -> Option<Range<Position>> {
+    let line = buffer.line_text(position.line)?;
+    let column = floor_char_boundary(&line, position.column);
+
+    let start = line[..column]
+        .char_indices()
+        .rev()
+        .take_while(|(_, c)| class(*c) == Class::Word)
+        .last()
+        .map_or(column, |(index, _)| index);
+    let end = column
+        + line[column..]
+            .chars()
+            .take_while(|c| class(*c) == Class::Word)
+            .map(char::len_utf8)
+            .sum::<usize>();
+
+    (end > start).then(|| Position::new(position.line, start)..Position::new(position.line, end))
+}
+
+/// The ranges `needle` occurs at in `buffer`, in document order.
+///
+/// The chunks of the buffer are searched one after the other, with the end
+/// of the previous one kept around for the occurrences spanning both.
+pub fn occurrences(buffer: &Buffer, needle: &str) -> Vec<Range<Position>> {
+    if needle.is_empty() {
+        return Vec::new();
+    }
+
+    let mut occurrences = Vec::new();
+    let mut window = String::new();
+    // The byte index of the window in the buffer, and the one past the last
+    // occurrence found.
+    let mut offset = 0;
+    let mut next = 0usize;
+
+    for chunk in buffer.chunks() {
+        window.push_str(chunk);
+
+        let from = next.saturating_sub(offset);
+
+        for (index, _) in window[from..].match_indices(needle) {
+            let start = offset + from + index;
+            next = start + needle.len();
+
+            occurrences.push(buffer.byte_to_position(start)..buffer.byte_to_position(next));
+        }
+
+        let dropped = floor_char_boundary(&window, window.len().saturating_sub(needle.len() - 1));
+
+        window.drain(..dropped);
+        offset += dropped;
+    }
+
+    occurrences
+}
+
+fn moved(buffer: &Buffer, position: Position, motion: Motion) -> Position {
+    let line = buffer.line_text(position.line).unwrap_or_default();
+    let column = floor_char_boundary(&line, position.column);
+    let last_line = buffer.len_lines().saturating_sub(1);
+
+    match motion {
+        Motion::Left => match line[..column].char_indices().next_back() {
+            Some((index, _)) => Position::new(position.line, index),
+            None if position.line > 0 => line_end(buffer, position.line - 1),
+            None => position,
+        },
+        Motion::Right => match line[column..].chars().next() {
+            Some(c) => Position::new(position.line, column + c.len_utf8()),
+            None if position.line < last_line => Position::new(position.line + 1, 0),
+            None => position,
+        },
+        Motion::Up => vertical(
+            buffer,
+            &line,
+            column,
+            position.line.checked_sub(1),
+            Position::default(),
+        ),
+        Motion::Down => vertical(
+            buffer,
+            &line,
+            column,
+            (position.line < last_line).then_some(position.line + 1),
+            line_end(buffer, last_line),
+        ),
+        Motion::PageUp => vertical(
+            buffer,
+            &line,
+            column,
+            Some(position.line.saturating_sub(PAGE_LINES)),
+            Position::default(),
+        ),
+        Motion::PageDown => vertical(
+            buffer,
+            &line,
+            column,
+            Some((position.line + PAGE_LINES).min(last_line)),
+            line_end(buffer, last_line),
+        ),
+        Motion::WordLeft => {
+            let prefix = &line[..column];
+            let trimmed = prefix.trim_end();
+
+            match trimmed.chars().next_back() {
+                Some(c) => {
+                    let kind = class(c);
+                    let start = trimmed
+                        .char_indices()
+                        .rev()
+                        .take_while(|(_, c)| class(*c) == kind)
+                        .last()
+                        .map_or(0, |(index, _)| index);
+
+                    Position::new(position.line, start)
+                }
+                None if column > 0 => Position::new(position.line, 0),
+                None => moved(buffer, position, Motion::Left),
+            }
+        }
+        Motion::WordRight => {
+            let suffix = &line[column..];
+            let trimmed = suffix.trim_start();
+            let skipped = suffix.len() - trimmed.len();
+
+            match trimmed.chars().next() {
+                Some(c) => {
+                    let kind = class(c);
+                    let len = trimmed
+                        .chars()
+                        .take_while(|c| class(*c) == kind)
+                        .map(char::len_utf8)
+                        .sum::<usize>();
+
+                    Position::new(position.line, column + skipped + len)
+                }
+                None if skipped > 0 => Position::new(position.line, line.len()),
+                None => moved(buffer, position, Motion::Right),
+            }
+        }
+        Motion::Home => Position::new(position.line, 0),
+        Motion::End => Position::new(position.line, line.len()),
+        Motion::DocumentStart => Position::default(),
+        Motion::DocumentEnd => line_end(buffer, last_line),
+    }
+}
+
+/// The position on `target` at the same char count as `column` on `line`,
+/// or `past` when there is no line to move to.
+fn vertical(
+    buffer: &Buffer,
+    line: &str,
+    column: usize,
+    target: Option<usize>,
+    past: Position,
+) -> Position {
+    let Some(target) = target else {
+        return past;
+    };
+
+    let chars = line[..column].chars().count();
+    let text = buffer.line_text(target).unwrap_or_default();
+    let column = text
+        .char_indices()
+        .nth(chars)
+        .map_or(text.len(), |(index, _)| index);
+
+    Position::new(target, column)
+}
+
+fn line_end(buffer: &Buffer, line: usize) -> Position {
+    Position::new(line, buffer.line_len(line).unwrap_or_default())
+}
+
+fn previous_char(buffer: &Buffer, index: usize) -> usize {
+    if index >= 2 && buffer.slice(index - 2..index) == "\r\n" {
+        index - 2
+    } else {
+        index.saturating_sub(1)
+    }
+}
+
+fn next_char(buffer: &Buffer, index: usize) -> usize {
+    if buffer.slice(index..index + 2) == "\r\n" {
+        index + 2
+    } else {
+        (index + 1).min(buffer.len_chars())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Class {
+    Word,
+    Whitespace,
+    Punctuation,
+}
+
+fn class(c: char) -> Class {
+    if c.is_alphanumeric() || c == '_' {
+        Class::Word
+    } else if c.is_whitespace() {
+        Class::Whitespace
+    } else {
+        Class::Punctuation
+    }
+}
+
+fn floor_char_boundary(text: &str, index: usize) -> usize {
+    let mut index = index.min(text.len());
+
+    while !text.is_char_boundary(index) {
+        index -= 1;
+    }
+
+    index
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(line: usize, column: usize) -> Position {
+        Position::new(line, column)
+    }
+
+    /// Types `inputs` at `carets`, returning the text and where the carets
+    /// end up.
+    fn typed(text: &str, carets: &[Caret], inputs: &[Input]) -> (String, Vec<Position>) {
+        let mut buffer = Buffer::with_text(text);
+        let (edits, heads) = edits(&buffer, carets, inputs);
+
+        for edit in edits.into_iter().rev() {
+            buffer.apply(edit);
+        }
+
+        let heads = heads
+            .into_iter()
+            .map(|head| buffer.char_to_position(head))
+            .collect();
+
+        (buffer.text(), heads)
+    }
+
+    #[test]
+    fn typing_shifts_the_carets_after_each_edit() {
+        let carets = [at(0, 1), at(1, 1), at(2, 1)].map(Caret::new);
+        let insert = vec![Input::Insert(String::from("X")); 3];
+
+        assert_eq!(
+            typed("ab\ncd\nef", &carets, &insert),
+            (
+                String::from("aXb\ncXd\neXf"),
+                vec![at(0, 2), at(1, 2), at(2, 2)]
+            )
+        );
+    }
+
+    #[test]
+    fn inserted_lines_shift_the_carets_below() {
+        let carets = [at(0, 1), at(1, 1)].map(Caret::new);
+        let enter = vec![Input::Insert(String::from("\n")); 2];
+
+        assert_eq!(
+            typed("ab\ncd", &carets, &enter),
+            (String::from("a\nb\nc\nd"), vec![at(1, 0), at(3, 0)])
+        );
+    }
+
+    #[test]
+    fn deleting_at_neighbouring_carets_removes_each_char_once() {
+        let carets = [at(0, 1), at(0, 2)].map(Caret::new);
+
+        assert_eq!(
+            typed("abc", &carets, &[Input::Backspace, Input::Backspace]),
+            (String::from("c"), vec![at(0, 0), at(0, 0)])
+        );
+
+        let carets = [at(0, 0), at(0, 1)].map(Caret::new);
+
+        assert_eq!(
+            typed("abc", &carets, &[Input::Delete, Input::Delete]),
+            (String::from("c"), vec![at(0, 0), at(0, 0)])
+        );
+    }
+
+    #[test]
+    fn backspace_stops_at_the_edit_before_it() {
+        let carets = [Caret::select(at(0, 0)..at(0, 2)), Caret::new(at(0, 2))];
+
+        assert_eq!(
+            typed("abcd", &carets, &[Input::Backspace, Input::Backspace]),
+            (String::from("cd"), vec![at(0, 0), at(0, 0)])
+        );
+    }
+
+    #[test]
+    fn backspace_removes_a_whole_crlf() {
+        let carets = [at(1, 0), at(2, 1)].map(Caret::new);
+
+        assert_eq!(
+            typed(
+                "a\r\nb\r\ncd",
+                &carets,
+                &[Input::Backspace, Input::Backspace]
+            ),
+            (String::from("ab\r\nd"), vec![at(0, 1), at(1, 0)])
+        );
+    }
+
+    #[test]
+    fn merge_sorts_and_joins_overlapping_carets() {
+        let carets = vec![
+            Caret::select(at(0, 2)..at(0, 5)),
+            Caret::new(at(1, 0)),
+            Caret::new(at(0, 0)),
+            Caret::select(at(0, 7)..at(0, 4)),
+            Caret::new(at(1, 0)),
+        ];
+
+        assert_eq!(
+            merge(carets),
+            [
+                Caret::new(at(0, 0)),
+                Caret::select(at(0, 2)..at(0, 7)),
+                Caret::new(at(1, 0)),
+            ]
+        );
+    }
+
+    #[test]
+    fn carets_move_by_chars_words_and_lines() {
+        let buffer = Buffer::with_text("héllo wörld\nab");
+        let caret = Caret::new(at(0, 0));
+
+        let right = caret.moved(&buffer, Motion::Right, false);
+        assert_eq!(right.head, at(0, 1));
+        assert_eq!(right.moved(&buffer, Motion::Right, false).head, at(0, 3));
+        assert_eq!(
+            caret.moved(&buffer, Motion::WordRight, false).head,
+            at(0, 6)
+        );
+
+        // Moving down keeps the count of chars before the cursor.
+        let down = Caret::new(at(0, 3)).moved(&buffer, Motion::Down, false);
+        assert_eq!(down.head, at(1, 2));
+        assert_eq!(down.moved(&buffer, Motion::Right, false), down);
+        assert_eq!(
+            Caret::new(at(1, 0))
+                .moved(&buffer, Motion::Left, false)
+                .head,
+            at(0, 13)
+        );
+
+        assert_eq!(
+            caret.moved(&buffer, Motion::End, true),
+            Caret::select(at(0, 0)..at(0, 13))
+        );
+    }
+
+    #[test]
+    fn moving_out_of_a_selection_stops_at_its_edge() {
+        let buffer = Buffer::with_text("héllo wörld");
+        let selection = Caret::select(at(0, 1)..at(0, 6));
+
+        assert_eq!(
+            selection.moved(&buffer, Motion::Left, false),
+            Caret::new(at(0, 1))
+        );
+        assert_eq!(
+            selection.moved(&buffer, Motion::Right, false),
+            Caret::new(at(0, 6))
+        );
+    }
+
+    #[test]
+    fn word_at_finds_the_word_around_or_before_a_position() {
+        let buffer = Buffer::with_text("foo_bar baz\na + b");
+
+        assert_eq!(word_at(&buffer, at(0, 3)), Some(at(0, 0)..at(0, 7)));
+        assert_eq!(word_at(&buffer, at(0, 7)), Some(at(0, 0)..at(0, 7)));
+        assert_eq!(word_at(&buffer, at(0, 8)), Some(at(0, 8)..at(0, 11)));
+        assert_eq!(word_at(&buffer, at(1, 2)), None);
+    }
+
+    #[test]
+    fn occurrences_are_found_across_chunks() {
+        let text = "héllo\r\nwörld ".repeat(400);
+        let buffer = Buffer::with_text(&text);
+
+        assert!(buffer.chunks().count() > 1);
+
+        for needle in ["wörld", "\r\nw", "o\r", "héllo\r\nwörld héllo"] {
+            let expected = text
+                .match_indices(needle)
+                .map(|(index, _)| {
+                    buffer.byte_to_position(index)..buffer.byte_to_position(index + needle.len())
+                })
+                .collect::<Vec<_>>();
+
+            assert_eq!(occurrences(&buffer, needle), expected);
+        }
+    }
+
+    #[test]
+    fn occurrences_dont_overlap() {
+        let buffer = Buffer::with_text("aaa\naa");
+
+        assert_eq!(
+            occurrences(&buffer, "aa"),
+            [at(0, 0)..at(0, 2), at(1, 0)..at(1, 2)]
+        );
+        assert!(occurrences(&buffer, "").is_empty());
+    }
+}
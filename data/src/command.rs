@@ -0,0 +1,217 @@
+use std::fmt;
+use std::str::FromStr;
+
+use crate::context::Context;
+use crate::shortcut::{KeyBind, KeySequence, Shortcut};
+
+macro_rules! commands {
+    ($($command:ident => $id:literal, $category:ident, $title:literal;)*) => {
+        /// Every action of the editor that can be bound to a key or picked
+        /// from the command palette.
+        #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+        pub enum Command {
+            $($command,)*
+        }
+
+        impl Command {
+            pub const ALL: &'static [Command] = &[$(Command::$command,)*];
+
+            /// The stable identifier of the command, as used in the config.
+            pub fn id(self) -> &'static str {
+                match self {
+                    $(Command::$command => $id,)*
+                }
+            }
+
+            pub fn title(self) -> &'static str {
+                match self {
+                    $(Command::$command => $title,)*
+                }
+            }
+
+            pub fn category(self) -> Category {
+                match self {
+                    $(Command::$command => Category::$category,)*
+                }
+            }
+        }
+    };
+}
+
+commands! {
+    NewFile => "new_file", File, "New File";
+    OpenFile => "open_file", File, "Open File...";
+    SaveFile => "save_file", File, "Save";
+    ReopenWithEncoding => "reopen_with_encoding", File, "Reopen with Encoding...";
+    SaveWithEncoding => "save_with_encoding", File, "Save with Encoding...";
+    Undo => "undo", Edit, "Undo";
+    Redo => "redo", Edit, "Redo";
+    Earlier => "earlier", Edit, "Go to Earlier Text State";
+    Later => "later", Edit, "Go to Later Text State";
+    UndoHistory => "undo_history", Edit, "Undo History...";
+    Copy => "copy", Edit, "Copy";
+    Cut => "cut", Edit, "Cut";
+    Paste => "paste", Edit, "Paste";
+    SelectAll => "select_all", Edit, "Select All";
+    UnfocusEditor => "unfocus_editor", Edit, "Unfocus Editor";
+    ExpandSelection => "expand_selection", Edit, "Expand Selection";
+    ShrinkSelection => "shrink_selection", Edit, "Shrink Selection";
+    AddCursorAbove => "add_cursor_above", Edit, "Add Cursor Above";
+    AddCursorBelow => "add_cursor_below", Edit, "Add Cursor Below";
+    AddNextOccurrence => "add_next_occurrence", Edit, "Add Next Occurrence";
+    SelectAllOccurrences => "select_all_occurrences", Edit, "Select All Occurrences";
+    ClearCursors => "clear_cursors", Edit, "Clear Secondary Cursors";
+    ColumnSelectUp => "column_select_up", Edit, "Column Select Up";
+    ColumnSelectDown => "column_select_down", Edit, "Column Select Down";
+    ColumnSelectLeft => "column_select_left", Edit, "Column Select Left";
+    ColumnSelectRight => "column_select_right", Edit, "Column Select Right";
+    ConvertToLf => "convert_to_lf", Edit, "Convert Line Endings to LF";
+    ConvertToCrlf => "convert_to_crlf", Edit, "Convert Line Endings to CRLF";
+    NextTab => "next_tab", Tab, "Next Tab";
+    PreviousTab => "previous_tab", Tab, "Previous Tab";
+    MoveTabLeft => "move_tab_left", Tab, "Move Tab Left";
+    MoveTabRight => "move_tab_right", Tab, "Move Tab Right";
+    CloseTab => "close_tab", Tab, "Close Tab";
+    ReopenClosedTab => "reopen_closed_tab", Tab, "Reopen Closed Tab";
+    SplitHorizontal => "split_horizontal", Pane, "Split Down";
+    SplitVertical => "split_vertical", Pane, "Split Right";
+    ClosePane => "close_pane", Pane, "Close Pane";
+    ToggleMaximizePane => "toggle_maximize_pane", Pane, "Toggle Maximized Pane";
+    MoveUp => "move_up", Pane, "Focus Pane Above";
+    MoveDown => "move_down", Pane, "Focus Pane Below";
+    MoveLeft => "move_left", Pane, "Focus Pane Left";
+    MoveRight => "move_right", Pane, "Focus Pane Right";
+    CommandPalette => "command_palette", View, "Command Palette";
+    GoToFile => "go_to_file", View, "Go to File...";
+    GoToLine => "go_to_line", View, "Go to Line...";
+    GoToSymbol => "go_to_symbol", View, "Go to Symbol...";
+    ToggleWordWrap => "toggle_word_wrap", View, "Toggle Word Wrap";
+    ToggleFold => "toggle_fold", View, "Toggle Fold";
+    FoldAll => "fold_all", View, "Fold All";
+    UnfoldAll => "unfold_all", View, "Unfold All";
+    ChangeLanguage => "change_language", View, "Change Language Mode...";
+    ToggleSidebar => "toggle_sidebar", View, "Toggle Sidebar";
+    CloseModal => "close_modal", View, "Close Dialog";
+    ThemeEditor => "theme_editor", View, "Theme Editor";
+    ToggleFullscreen => "toggle_fullscreen", Window, "Toggle Fullscreen";
+    QuitApplication => "quit_application", Window, "Quit";
+}
+
+impl fmt::Display for Command {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.category(), self.title())
+    }
+}
+
+impl FromStr for Command {
+    type Err = UnknownCommand;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Command::ALL
+            .iter()
+            .copied()
+            .find(|command| command.id() == s)
+            .ok_or_else(|| UnknownCommand(s.to_string()))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Category {
+    File,
+    Edit,
+    Tab,
+    Pane,
+    View,
+    Window,
+}
+
+impl fmt::Display for Category {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let category = match self {
+            Category::File => "File",
+            Category::Edit => "Edit",
+            Category::Tab => "Tab",
+            Category::Pane => "Pane",
+            Category::View => "View",
+            Category::Window => "Window",
+        };
+
+        write!(f, "{category}")
+    }
+}
+
+/// A command together with the keys currently bound to it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Entry {
+    pub command: Command,
+    pub key_bind: Option<KeySequence>,
+}
+
+/// What the keys pressed so far resolve to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Lookup {
+    /// Nothing is bound to the keys, not even as the start of a chord.
+    None,
+    /// The keys are the start of at least one chord. `exact` is the command
+    /// bound to the keys themselves, run when the chord times out.
+    Pending {
+        exact: Option<Command>,
+    },
+    Command(Command),
+}
+
+/// The lookup from keys to commands, shared by the key handling, the command
+/// palette and anything else listing commands.
+#[derive(Debug, Clone, Default)]
+pub struct Registry {
+    shortcuts: Vec<Shortcut>,
+}
+
+impl Registry {
+    /// Bindings with a more specific `when` clause are checked first.
+    pub fn new(mut shortcuts: Vec<Shortcut>) -> Self {
+        shortcuts.sort_by_key(|shortcut| std::cmp::Reverse(shortcut.when().specificity()));
+
+        Self { shortcuts }
+    }
+
+    /// Resolves the `keys` pressed so far, the last one being the latest,
+    /// considering only the bindings whose `when` clause holds in `context`.
+    ///
+    /// While a modal is open, only the bindings about modals apply, so keys
+    /// typed into it don't save or close files behind it.
+    pub fn lookup(&self, keys: &[KeyBind], context: &Context) -> Lookup {
+        let mut shortcuts = self.shortcuts.iter().filter(|shortcut| {
+            shortcut.when().matches(context) && (!context.modal_open || shortcut.when().is_modal())
+        });
+
+        let exact = shortcuts
+            .clone()
+            .find_map(|shortcut| shortcut.execute(keys));
+
+        if shortcuts.any(|shortcut| shortcut.is_continued_by(keys)) {
+            Lookup::Pending { exact }
+        } else {
+            exact.map_or(Lookup::None, Lookup::Command)
+        }
+    }
+
+    pub fn key_bind(&self, command: Command) -> Option<&KeySequence> {
+        self.shortcuts
+            .iter()
+            .find(|shortcut| shortcut.command() == command)
+            .map(Shortcut::sequence)
+    }
+
+    /// Every command, in declaration order.
+    pub fn entries(&self) -> impl Iterator<Item = Entry> + '_ {
+        Command::ALL.iter().map(|&command| Entry {
+            command,
+            key_bind: self.key_bind(command).cloned(),
+        })
+    }
+}
+
+#[derive(Debug, Clone, thiserror::Error)]
+#[error("unknown command: {0}")]
+pub struct UnknownCommand(pub String);
@@ -11,8 +11,10 @@ pub use self::keys::Keyboard;
 use crate::appearance::theme::Colors;
 use crate::appearance::{self, Appearance};
 use crate::environment::config_dir;
+use crate::title::Title;
 use crate::{Theme, environment};
 
+pub mod check;
 pub mod keys;
 
 const CONFIG_TEMPLATE: &str = include_str!("../../config.toml");
@@ -22,6 +24,8 @@ const DEFAULT_THEME_NAME: &str = "gruvbox";
 pub struct Config {
     pub appearance: Appearance,
     pub font: Font,
+    pub editor: Editor,
+    pub window: Window,
     pub keyboard: Keyboard,
 }
 
@@ -42,6 +46,32 @@ pub struct Font {
     pub bold_weight: Option<font::Weight>,
 }
 
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct Editor {
+    #[serde(default)]
+    pub line_numbers: LineNumbers,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct Window {
+    /// The template the title of the window is filled in from.
+    #[serde(default)]
+    pub title: Title,
+}
+
+/// How the gutter numbers the lines of the editor.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum LineNumbers {
+    #[default]
+    Absolute,
+    /// The distance to the cursor line, which shows its own number.
+    Relative,
+    Off,
+}
+
 fn deserialize_font_weight_from_string<'de, D>(deserializer: D) -> Result<font::Weight, D::Error>
 where
     D: Deserializer<'de>,
@@ -87,6 +117,28 @@ fn default_font_weight() -> font::Weight {
     font::Weight::Normal
 }
 
+#[derive(Deserialize, Debug)]
+#[serde(untagged)]
+enum ThemeKeys {
+    Static(String),
+    Dynamic { light: String, dark: String },
+}
+
+impl Default for ThemeKeys {
+    fn default() -> Self {
+        Self::Static(String::default())
+    }
+}
+
+impl ThemeKeys {
+    fn keys(&self) -> (&str, Option<&str>) {
+        match self {
+            ThemeKeys::Static(manual) => (manual, None),
+            ThemeKeys::Dynamic { light, dark } => (light, Some(dark)),
+        }
+    }
+}
+
 impl Config {
     pub fn config_dir() -> PathBuf {
         let dir = environment::config_dir();
@@ -117,28 +169,6 @@ impl Config {
     pub async fn load() -> Result<Self, Error> {
         use tokio::fs;
 
-        #[derive(Deserialize, Debug)]
-        #[serde(untagged)]
-        pub enum ThemeKeys {
-            Static(String),
-            Dynamic { light: String, dark: String },
-        }
-
-        impl Default for ThemeKeys {
-            fn default() -> Self {
-                Self::Static(String::default())
-            }
-        }
-
-        impl ThemeKeys {
-            pub fn keys(&self) -> (&str, Option<&str>) {
-                match self {
-                    ThemeKeys::Static(manual) => (manual, None),
-                    ThemeKeys::Dynamic { light, dark } => (light, Some(dark)),
-                }
-            }
-        }
-
         #[derive(Deserialize)]
         pub struct Configuration {
             #[serde(default)]
@@ -146,6 +176,10 @@ impl Config {
             #[serde(default)]
             pub font: Font,
             #[serde(default)]
+            pub editor: Editor,
+            #[serde(default)]
+            pub window: Window,
+            #[serde(default)]
             pub keyboard: Keyboard,
         }
 
@@ -162,6 +196,8 @@ impl Config {
         let Configuration {
             theme,
             font,
+            editor,
+            window,
             keyboard,
         } = toml::from_str(content.as_ref()).map_err(|e| Error::Parse(e.to_string()))?;
 
@@ -172,6 +208,8 @@ impl Config {
         Ok(Config {
             appearance,
             font,
+            editor,
+            window,
             keyboard,
         })
     }
@@ -289,6 +327,13 @@ pub enum Error {
     ConfigMissing { has_yaml_config: bool },
 }
 
+impl Error {
+    /// Whether there simply is no config file, which is not worth reporting.
+    pub fn is_missing(&self) -> bool {
+        matches!(self, Error::ConfigMissing { .. })
+    }
+}
+
 impl From<std::io::Error> for Error {
     fn from(error: std::io::Error) -> Self {
         Self::Io(error.to_string())
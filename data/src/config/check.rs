@@ -0,0 +1,403 @@
+use std::collections::BTreeMap;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::{fmt, io};
+
+use serde::Deserialize;
+use tokio::fs;
+use toml::Spanned;
+
+use super::keys::{self, Binding, ConflictKind, KeyBinds, Keyboard};
+use super::{Config, DEFAULT_THEME_NAME, Editor, Font, ThemeKeys};
+use crate::appearance::theme::Colors;
+use crate::command::Command;
+use crate::title::{self, Title};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    Warning,
+    Error,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Severity::Warning => write!(f, "warning"),
+            Severity::Error => write!(f, "error"),
+        }
+    }
+}
+
+/// A problem found in the config file or a theme.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub path: PathBuf,
+    /// The line and column of the problem, both starting at 1.
+    pub location: Option<(usize, usize)>,
+    pub message: String,
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.path.display())?;
+
+        if let Some((line, column)) = self.location {
+            write!(f, ":{line}:{column}")?;
+        }
+
+        write!(f, ": {}: {}", self.severity, self.message)
+    }
+}
+
+/// Everything found wrong with the config file and the themes.
+#[derive(Debug, Clone, Default)]
+pub struct Report {
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+impl Report {
+    pub fn has_errors(&self) -> bool {
+        self.count(Severity::Error) > 0
+    }
+
+    pub fn count(&self, severity: Severity) -> usize {
+        self.diagnostics
+            .iter()
+            .filter(|diagnostic| diagnostic.severity == severity)
+            .count()
+    }
+
+    fn push(
+        &mut self,
+        severity: Severity,
+        path: &Path,
+        content: &str,
+        span: Option<Range<usize>>,
+        message: impl Into<String>,
+    ) {
+        self.diagnostics.push(Diagnostic {
+            severity,
+            path: path.to_path_buf(),
+            location: span.map(|span| location(content, span.start)),
+            message: message.into(),
+        });
+    }
+
+    fn parse_error(&mut self, path: &Path, content: &str, error: &toml::de::Error) {
+        self.push(
+            Severity::Error,
+            path,
+            content,
+            error.span(),
+            error.message().trim(),
+        );
+    }
+}
+
+/// Validates the config file and every theme in the themes directory, the
+/// way they would be loaded on startup.
+pub async fn run() -> Report {
+    let mut report = Report::default();
+
+    let themes = check_themes(&mut report).await;
+    check_config(&mut report, &themes).await;
+
+    report.diagnostics.sort_by(|a, b| {
+        (&a.path, a.location)
+            .cmp(&(&b.path, b.location))
+            .then(b.severity.cmp(&a.severity))
+    });
+
+    report
+}
+
+/// Checks the themes and returns the names of the ones that load.
+async fn check_themes(report: &mut Report) -> Vec<String> {
+    #[derive(Deserialize)]
+    struct V1 {
+        #[serde(rename = "name")]
+        _name: String,
+    }
+
+    let mut themes = vec![];
+
+    let Ok(mut entries) = fs::read_dir(Config::themes_dir()).await else {
+        return themes;
+    };
+
+    while let Ok(Some(entry)) = entries.next_entry().await {
+        let path = entry.path();
+
+        let Some(name) = path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .and_then(|name| name.strip_suffix(".toml"))
+            .map(String::from)
+        else {
+            continue;
+        };
+
+        let content = match fs::read_to_string(&path).await {
+            Ok(content) => content,
+            Err(error) => {
+                report.push(
+                    Severity::Error,
+                    &path,
+                    "",
+                    None,
+                    format!("theme could not be read: {error}"),
+                );
+                continue;
+            }
+        };
+
+        if toml::from_str::<V1>(&content).is_ok() {
+            report.push(
+                Severity::Warning,
+                &path,
+                &content,
+                None,
+                "theme uses the old format and is ignored",
+            );
+            continue;
+        }
+
+        match toml::from_str::<Colors>(&content) {
+            Ok(_) => themes.push(name),
+            Err(error) => report.parse_error(&path, &content, &error),
+        }
+    }
+
+    themes
+}
+
+async fn check_config(report: &mut Report, themes: &[String]) {
+    #[derive(Deserialize)]
+    struct ThemeSection {
+        #[serde(default)]
+        theme: Option<Spanned<ThemeKeys>>,
+    }
+
+    #[derive(Deserialize)]
+    struct FontSection {
+        #[serde(default, rename = "font")]
+        _font: Font,
+    }
+
+    #[derive(Deserialize)]
+    struct EditorSection {
+        #[serde(default, rename = "editor")]
+        _editor: Editor,
+    }
+
+    #[derive(Deserialize)]
+    struct WindowSection {
+        #[serde(default)]
+        window: Option<WindowTable>,
+    }
+
+    #[derive(Deserialize)]
+    struct WindowTable {
+        #[serde(default)]
+        title: Option<Spanned<Title>>,
+    }
+
+    #[derive(Deserialize)]
+    struct KeyboardSection {
+        #[serde(default)]
+        keyboard: BTreeMap<Spanned<String>, Spanned<toml::Value>>,
+    }
+
+    let path = Config::path();
+
+    let content = match fs::read_to_string(&path).await {
+        Ok(content) => content,
+        // Without a config file the defaults are used, there is nothing to
+        // check.
+        Err(error) if error.kind() == io::ErrorKind::NotFound => return,
+        Err(error) => {
+            report.push(
+                Severity::Error,
+                &path,
+                "",
+                None,
+                format!("config could not be read: {error}"),
+            );
+            return;
+        }
+    };
+
+    // Syntax errors hide everything else, the sections are checked on their
+    // own so an error in one still reports the others.
+    if let Err(error) = toml::from_str::<toml::Table>(&content) {
+        report.parse_error(&path, &content, &error);
+        return;
+    }
+
+    match toml::from_str::<ThemeSection>(&content) {
+        Ok(ThemeSection { theme: Some(theme) }) => {
+            let (first, second) = theme.get_ref().keys();
+
+            for name in [Some(first), second].into_iter().flatten() {
+                let is_known = name.is_empty()
+                    || name.eq_ignore_ascii_case(DEFAULT_THEME_NAME)
+                    || themes.iter().any(|theme| theme == name);
+
+                if !is_known {
+                    report.push(
+                        Severity::Warning,
+                        &path,
+                        &content,
+                        Some(theme.span()),
+                        format!("theme `{name}` was not found, the default theme is used"),
+                    );
+                }
+            }
+        }
+        Ok(ThemeSection { theme: None }) => {}
+        Err(error) => report.parse_error(&path, &content, &error),
+    }
+
+    if let Err(error) = toml::from_str::<FontSection>(&content) {
+        report.parse_error(&path, &content, &error);
+    }
+
+    if let Err(error) = toml::from_str::<EditorSection>(&content) {
+        report.parse_error(&path, &content, &error);
+    }
+
+    match toml::from_str::<WindowSection>(&content) {
+        Ok(WindowSection {
+            window: Some(WindowTable { title: Some(title) }),
+        }) => {
+            for placeholder in title.get_ref().unknown_placeholders() {
+                report.push(
+                    Severity::Warning,
+                    &path,
+                    &content,
+                    Some(title.span()),
+                    format!(
+                        "unknown placeholder `{{{placeholder}}}` in the window title, \
+                         expected one of {}",
+                        title::PLACEHOLDERS
+                            .iter()
+                            .map(|placeholder| format!("`{{{placeholder}}}`"))
+                            .collect::<Vec<_>>()
+                            .join(", ")
+                    ),
+                );
+            }
+        }
+        Ok(_) => {}
+        Err(error) => report.parse_error(&path, &content, &error),
+    }
+
+    match toml::from_str::<KeyboardSection>(&content) {
+        Ok(KeyboardSection { keyboard }) => check_keyboard(report, &path, &content, keyboard),
+        Err(error) => report.parse_error(&path, &content, &error),
+    }
+}
+
+fn check_keyboard(
+    report: &mut Report,
+    path: &Path,
+    content: &str,
+    table: BTreeMap<Spanned<String>, Spanned<toml::Value>>,
+) {
+    let mut configured = BTreeMap::<Command, (Vec<Binding>, Range<usize>)>::new();
+
+    for (id, value) in table {
+        let command = match id.get_ref().parse::<Command>() {
+            Ok(command) => command,
+            Err(error) => {
+                report.push(
+                    Severity::Error,
+                    path,
+                    content,
+                    Some(id.span()),
+                    error.to_string(),
+                );
+                continue;
+            }
+        };
+
+        let span = value.span();
+
+        let bindings = value
+            .into_inner()
+            .try_into::<KeyBinds>()
+            .map_err(|_| {
+                String::from(
+                    "expected a key sequence, a list of them or a table with `keys` and `when`",
+                )
+            })
+            .and_then(KeyBinds::into_bindings);
+
+        match bindings {
+            Ok(bindings) => {
+                configured.insert(command, (bindings, span));
+            }
+            Err(error) => report.push(
+                Severity::Error,
+                path,
+                content,
+                Some(span),
+                format!("{}: {error}", command.id()),
+            ),
+        }
+    }
+
+    let defaults = Keyboard::default();
+
+    let bindings = Command::ALL
+        .iter()
+        .flat_map(|&command| match configured.get(&command) {
+            Some((bindings, span)) => bindings
+                .iter()
+                .map(|binding| (command, binding, Some(span.clone())))
+                .collect::<Vec<_>>(),
+            None => defaults
+                .key_binds(command)
+                .iter()
+                .map(|binding| (command, binding, None))
+                .collect(),
+        })
+        .collect::<Vec<_>>();
+
+    let pairs = bindings
+        .iter()
+        .map(|(command, binding, _)| (*command, *binding))
+        .collect::<Vec<_>>();
+
+    for conflict in keys::conflicts(&pairs) {
+        // Conflicts between two default bindings are not the config's fault.
+        let Some(span) = bindings[conflict.second]
+            .2
+            .clone()
+            .or_else(|| bindings[conflict.first].2.clone())
+        else {
+            continue;
+        };
+
+        let severity = match conflict.kind {
+            ConflictKind::Duplicate => Severity::Error,
+            ConflictKind::Shadowed | ConflictKind::Prefix => Severity::Warning,
+        };
+
+        report.push(severity, path, content, Some(span), conflict.to_string());
+    }
+}
+
+/// The line and column of the byte `offset` in `content`, both starting at 1.
+fn location(content: &str, offset: usize) -> (usize, usize) {
+    let before = content.get(..offset).unwrap_or(content);
+    let line = before.matches('\n').count() + 1;
+    let column = before
+        .rsplit('\n')
+        .next()
+        .map_or(0, |line| line.chars().count())
+        + 1;
+
+    (line, column)
+}
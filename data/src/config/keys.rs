@@ -1,60 +1,369 @@
-use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::fmt;
 
-use crate::shortcut::{KeyBind, Shortcut, shortcut};
+use serde::{Deserialize, Deserializer};
 
-#[derive(Debug, Clone, Deserialize)]
+use crate::command::Command;
+use crate::context::When;
+use crate::shortcut::{KeyBind, KeySequence, Shortcut, shortcut};
+
+/// The `[keyboard]` table of the config.
+///
+/// Every key of the table is a command id, bound to either a single key
+/// sequence or a list of them. A sequence is one or more key binds separated
+/// by spaces, pressed one after the other. A binding given as a table can be
+/// restricted to a context with a [`When`] clause:
+///
+/// ```toml
+/// [keyboard]
+/// save_file = "ctrl+s"
+/// command_palette = ["ctrl+shift+p", "f1"]
+/// toggle_word_wrap = "ctrl+k ctrl+w"
+/// go_to_symbol = { keys = "ctrl+r", when = "editor_focused && language == rust" }
+/// theme_editor = []
+/// ```
+///
+/// Commands left out keep their default bindings, an empty list unbinds them.
+/// Invalid entries are skipped and keep the defaults as well, see
+/// [`Keyboard::skipped`].
+#[derive(Debug, Clone)]
 pub struct Keyboard {
-    #[serde(default = "KeyBind::move_up")]
-    pub move_up: KeyBind,
-    #[serde(default = "KeyBind::move_down")]
-    pub move_down: KeyBind,
-    #[serde(default = "KeyBind::move_left")]
-    pub move_left: KeyBind,
-    #[serde(default = "KeyBind::move_right")]
-    pub move_right: KeyBind,
-    #[serde(default = "KeyBind::toggle_sidebar")]
-    pub toggle_sidebar: KeyBind,
-    #[serde(default = "KeyBind::toggle_fullscreen")]
-    pub toggle_fullscreen: KeyBind,
-    #[serde(default = "KeyBind::theme_editor")]
-    pub theme_editor: KeyBind,
-    #[serde(default)]
-    pub quit_application: Option<KeyBind>,
+    bindings: BTreeMap<Command, Vec<Binding>>,
+    skipped: Vec<String>,
+}
+
+/// A key sequence bound to a command, active while `when` holds.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Binding {
+    pub sequence: KeySequence,
+    pub when: When,
+}
+
+/// How two bindings of the keymap get in each other's way.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConflictKind {
+    /// Both are bound to the same keys in the same context, so only the first
+    /// ever runs.
+    Duplicate,
+    /// Both are bound to the same keys and can apply at the same time, the
+    /// first wins then.
+    Shadowed,
+    /// The keys of the first are the start of the second, so the first only
+    /// runs once the chord times out.
+    Prefix,
+}
+
+/// Two conflicting bindings, as indices into the bindings that were checked.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Conflict {
+    pub kind: ConflictKind,
+    pub first: usize,
+    pub second: usize,
+    description: String,
+}
+
+impl fmt::Display for Conflict {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.description)
+    }
+}
+
+/// Finds the conflicts between `bindings`, listed in the order the
+/// [`Registry`](crate::command::Registry) checks them in.
+pub fn conflicts(bindings: &[(Command, &Binding)]) -> Vec<Conflict> {
+    let mut order = (0..bindings.len()).collect::<Vec<_>>();
+    order.sort_by_key(|&index| std::cmp::Reverse(bindings[index].1.when.specificity()));
+
+    let mut conflicts = vec![];
+
+    for (position, &first) in order.iter().enumerate() {
+        let (command, binding) = bindings[first];
+
+        for &second in &order[position + 1..] {
+            let (other_command, other) = bindings[second];
+
+            if binding.sequence != other.sequence || binding.when.excludes(&other.when) {
+                continue;
+            }
+
+            let (kind, description) = if binding.when == other.when {
+                (
+                    ConflictKind::Duplicate,
+                    format!(
+                        "`{}` is bound to both {} and {}, {} never runs",
+                        binding.sequence,
+                        command.id(),
+                        other_command.id(),
+                        other_command.id(),
+                    ),
+                )
+            } else {
+                (
+                    ConflictKind::Shadowed,
+                    format!(
+                        "`{}` is bound to both {} and {}, {} wins when both apply",
+                        binding.sequence,
+                        command.id(),
+                        other_command.id(),
+                        command.id(),
+                    ),
+                )
+            };
+
+            conflicts.push(Conflict {
+                kind,
+                first,
+                second,
+                description,
+            });
+        }
+    }
+
+    for (first, (command, prefix)) in bindings.iter().enumerate() {
+        for (second, (continued_by, binding)) in bindings.iter().enumerate() {
+            if prefix.sequence.is_prefix_of(&binding.sequence)
+                && !prefix.when.excludes(&binding.when)
+            {
+                conflicts.push(Conflict {
+                    kind: ConflictKind::Prefix,
+                    first,
+                    second,
+                    description: format!(
+                        "`{}` ({}) is the start of `{}` ({}) and only runs after the chord times out",
+                        prefix.sequence,
+                        command.id(),
+                        binding.sequence,
+                        continued_by.id(),
+                    ),
+                });
+            }
+        }
+    }
+
+    conflicts
 }
 
 impl Default for Keyboard {
     fn default() -> Self {
         Self {
-            move_up: KeyBind::move_up(),
-            move_down: KeyBind::move_down(),
-            move_left: KeyBind::move_left(),
-            move_right: KeyBind::move_right(),
-            toggle_sidebar: KeyBind::toggle_sidebar(),
-            toggle_fullscreen: KeyBind::toggle_fullscreen(),
-            theme_editor: KeyBind::theme_editor(),
-            quit_application: None,
+            bindings: Command::ALL
+                .iter()
+                .map(|&command| {
+                    let defaults = KeyBind::defaults(command)
+                        .into_iter()
+                        .map(|key_bind| Binding {
+                            sequence: KeySequence::from(key_bind),
+                            when: When::defaults(command),
+                        })
+                        .collect();
+
+                    (command, defaults)
+                })
+                .collect(),
+            skipped: vec![],
         }
     }
 }
 
+/// The value of an entry of the `[keyboard]` table, before its key
+/// sequences and `when` clauses are parsed.
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+pub(super) enum KeyBinds {
+    One(Entry),
+    Many(Vec<Entry>),
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+pub(super) enum Entry {
+    Keys(String),
+    Conditional { keys: String, when: String },
+}
+
+impl KeyBinds {
+    pub(super) fn into_bindings(self) -> Result<Vec<Binding>, String> {
+        let entries = match self {
+            KeyBinds::One(entry) => vec![entry],
+            KeyBinds::Many(entries) => entries,
+        };
+
+        entries
+            .into_iter()
+            .map(|entry| {
+                let (keys, when) = match entry {
+                    Entry::Keys(keys) => (keys, None),
+                    Entry::Conditional { keys, when } => (keys, Some(when)),
+                };
+
+                Ok(Binding {
+                    sequence: keys
+                        .parse::<KeySequence>()
+                        .map_err(|error| error.to_string())?,
+                    when: when
+                        .as_deref()
+                        .map(str::parse::<When>)
+                        .transpose()
+                        .map_err(|error| error.to_string())?
+                        .unwrap_or_default(),
+                })
+            })
+            .collect()
+    }
+}
+
+impl<'de> Deserialize<'de> for Keyboard {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let table = BTreeMap::<String, toml::Value>::deserialize(deserializer)?;
+
+        let mut keyboard = Keyboard::default();
+
+        for (id, value) in table {
+            let key_binds = id
+                .parse::<Command>()
+                .map_err(|error| error.to_string())
+                .and_then(|command| {
+                    value
+                        .try_into::<KeyBinds>()
+                        .map_err(|error| error.to_string())
+                        .and_then(KeyBinds::into_bindings)
+                        .map(|key_binds| (command, key_binds))
+                });
+
+            match key_binds {
+                Ok((command, key_binds)) => {
+                    keyboard.bindings.insert(command, key_binds);
+                }
+                Err(error) => keyboard.skipped.push(format!("{id}: {error}")),
+            }
+        }
+
+        Ok(keyboard)
+    }
+}
+
 impl Keyboard {
+    pub fn key_binds(&self, command: Command) -> &[Binding] {
+        self.bindings.get(&command).map_or(&[], Vec::as_slice)
+    }
+
     pub fn shortcuts(&self) -> Vec<Shortcut> {
-        use crate::shortcut::Command::*;
-
-        let mut shortcuts = vec![
-            shortcut(self.move_up.clone(), MoveUp),
-            shortcut(self.move_down.clone(), MoveDown),
-            shortcut(self.move_left.clone(), MoveLeft),
-            shortcut(self.move_right.clone(), MoveRight),
-            shortcut(self.toggle_sidebar.clone(), ToggleSidebar),
-            shortcut(self.toggle_fullscreen.clone(), ToggleFullscreen),
-            shortcut(self.theme_editor.clone(), ThemeEditor),
-        ];
-
-        if let Some(quit_application) = self.quit_application.clone() {
-            shortcuts.push(shortcut(quit_application, QuitApplication));
+        self.bindings
+            .iter()
+            .flat_map(|(&command, key_binds)| {
+                key_binds.iter().map(move |binding| {
+                    shortcut(binding.sequence.clone(), binding.when.clone(), command)
+                })
+            })
+            .collect()
+    }
+
+    /// The entries that could not be parsed and were left out, one message
+    /// per entry.
+    pub fn skipped(&self) -> &[String] {
+        &self.skipped
+    }
+
+    /// Finds the bindings that get in each other's way, see [`conflicts`].
+    pub fn conflicts(&self) -> Vec<Conflict> {
+        let bindings = self
+            .bindings
+            .iter()
+            .flat_map(|(&command, bindings)| bindings.iter().map(move |binding| (command, binding)))
+            .collect::<Vec<_>>();
+
+        conflicts(&bindings)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn binding(keys: &str, when: &str) -> Binding {
+        Binding {
+            sequence: keys.parse().unwrap(),
+            when: when.parse().unwrap(),
         }
+    }
+
+    fn kinds(bindings: &[(Command, &Binding)]) -> Vec<(ConflictKind, usize, usize)> {
+        conflicts(bindings)
+            .into_iter()
+            .map(|conflict| (conflict.kind, conflict.first, conflict.second))
+            .collect()
+    }
+
+    #[test]
+    fn same_keys_in_the_same_context_are_duplicates() {
+        let save = binding("ctrl+s", "");
+        let copy = binding("ctrl+s", "");
+
+        assert_eq!(
+            kinds(&[(Command::SaveFile, &save), (Command::Copy, &copy)]),
+            [(ConflictKind::Duplicate, 0, 1)]
+        );
+    }
+
+    #[test]
+    fn the_more_specific_binding_shadows_the_other() {
+        let save = binding("ctrl+s", "");
+        let copy = binding("ctrl+s", "editor_focused");
+
+        assert_eq!(
+            kinds(&[(Command::SaveFile, &save), (Command::Copy, &copy)]),
+            [(ConflictKind::Shadowed, 1, 0)]
+        );
+    }
+
+    #[test]
+    fn exclusive_contexts_dont_conflict() {
+        let paste = binding("ctrl+v", "editor_focused");
+        let close = binding("ctrl+v", "modal_open");
+
+        assert!(kinds(&[(Command::Paste, &paste), (Command::CloseModal, &close)]).is_empty());
+    }
+
+    #[test]
+    fn a_sequence_starting_a_chord_is_a_prefix() {
+        let copy = binding("ctrl+k", "");
+        let cut = binding("ctrl+k ctrl+c", "");
+
+        assert_eq!(
+            kinds(&[(Command::Copy, &copy), (Command::Cut, &cut)]),
+            [(ConflictKind::Prefix, 0, 1)]
+        );
+    }
+
+    #[test]
+    fn default_keymap_has_no_conflicts() {
+        assert_eq!(Keyboard::default().conflicts(), []);
+    }
+
+    #[test]
+    fn invalid_entries_are_skipped() {
+        let keyboard: Keyboard = toml::from_str(
+            r#"
+            save_file = "ctrl+shift+s"
+            copy = "ctrl+nope"
+            not_a_command = "ctrl+q"
+            paste = 1
+            "#,
+        )
+        .unwrap();
 
-        shortcuts
+        assert_eq!(
+            keyboard.key_binds(Command::SaveFile),
+            [binding("ctrl+shift+s", "")]
+        );
+        assert_eq!(
+            keyboard.key_binds(Command::Copy),
+            Keyboard::default().key_binds(Command::Copy)
+        );
+        assert_eq!(keyboard.skipped().len(), 3);
+        assert!(keyboard.skipped()[0].starts_with("copy: "));
     }
 }
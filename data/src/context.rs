@@ -0,0 +1,345 @@
+use std::fmt;
+use std::str::FromStr;
+
+use crate::command::Command;
+
+/// The state of the editor that `when` clauses of key bindings are checked
+/// against.
+///
+/// The editor never has focus while a modal is open, and the palette is a
+/// modal.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Context {
+    pub editor_focused: bool,
+    pub modal_open: bool,
+    pub palette_open: bool,
+    pub has_selection: bool,
+    pub language: Option<String>,
+}
+
+/// The condition a key binding is restricted to, written as terms joined by
+/// `&&`:
+///
+/// ```text
+/// editor_focused && !has_selection
+/// language == rust
+/// ```
+///
+/// An empty clause always holds.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct When(Vec<Term>);
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum Term {
+    Flag { flag: Flag, negated: bool },
+    Language { language: String, negated: bool },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum Flag {
+    EditorFocused,
+    ModalOpen,
+    PaletteOpen,
+    HasSelection,
+}
+
+impl When {
+    /// The condition the default bindings of `command` are restricted to.
+    pub fn defaults(command: Command) -> Self {
+        let flag = match command {
+            Command::Undo
+            | Command::Redo
+            | Command::Copy
+            | Command::Cut
+            | Command::Paste
+            | Command::SelectAll
+            | Command::ExpandSelection
+            | Command::ShrinkSelection
+            | Command::AddCursorAbove
+            | Command::AddCursorBelow
+            | Command::AddNextOccurrence
+            | Command::SelectAllOccurrences
+            | Command::ColumnSelectUp
+            | Command::ColumnSelectDown
+            | Command::ColumnSelectLeft
+            | Command::ColumnSelectRight
+            | Command::UnfocusEditor => Flag::EditorFocused,
+            Command::CloseModal => Flag::ModalOpen,
+            _ => return Self::default(),
+        };
+
+        Self(vec![Term::Flag {
+            flag,
+            negated: false,
+        }])
+    }
+
+    pub fn is_always(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// How many terms the clause has, a binding with more terms is more
+    /// specific and wins over the others.
+    pub fn specificity(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn matches(&self, context: &Context) -> bool {
+        self.0.iter().all(|term| term.matches(context))
+    }
+
+    /// Whether the clause is about a modal being open, the only kind of
+    /// binding that applies while one is.
+    pub fn is_modal(&self) -> bool {
+        self.0.iter().any(|term| {
+            matches!(
+                term,
+                Term::Flag {
+                    flag: Flag::ModalOpen | Flag::PaletteOpen,
+                    ..
+                }
+            )
+        })
+    }
+
+    /// Whether `self` and `other` can never hold at the same time.
+    pub fn excludes(&self, other: &When) -> bool {
+        self.0.iter().any(|a| other.0.iter().any(|b| a.excludes(b)))
+    }
+}
+
+impl Term {
+    fn matches(&self, context: &Context) -> bool {
+        match self {
+            Term::Flag { flag, negated } => {
+                let value = match flag {
+                    Flag::EditorFocused => context.editor_focused,
+                    Flag::ModalOpen => context.modal_open,
+                    Flag::PaletteOpen => context.palette_open,
+                    Flag::HasSelection => context.has_selection,
+                };
+
+                value != *negated
+            }
+            Term::Language { language, negated } => {
+                let value = context
+                    .language
+                    .as_deref()
+                    .is_some_and(|current| current.eq_ignore_ascii_case(language));
+
+                value != *negated
+            }
+        }
+    }
+
+    fn excludes(&self, other: &Term) -> bool {
+        match (self, other) {
+            (
+                Term::Flag { flag, negated },
+                Term::Flag {
+                    flag: other,
+                    negated: other_negated,
+                },
+            ) => match (flag, negated, other, other_negated) {
+                (a, _, b, _) if a == b => negated != other_negated,
+                (Flag::EditorFocused, false, Flag::ModalOpen | Flag::PaletteOpen, false)
+                | (Flag::ModalOpen | Flag::PaletteOpen, false, Flag::EditorFocused, false)
+                | (Flag::PaletteOpen, false, Flag::ModalOpen, true)
+                | (Flag::ModalOpen, true, Flag::PaletteOpen, false) => true,
+                _ => false,
+            },
+            (
+                Term::Language { language, negated },
+                Term::Language {
+                    language: other,
+                    negated: other_negated,
+                },
+            ) => {
+                let same = language.eq_ignore_ascii_case(other);
+
+                match (negated, other_negated) {
+                    (false, false) => !same,
+                    (true, true) => false,
+                    _ => same,
+                }
+            }
+            _ => false,
+        }
+    }
+}
+
+impl FromStr for When {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.trim().is_empty() {
+            return Ok(Self::default());
+        }
+
+        s.split("&&")
+            .map(str::parse)
+            .collect::<Result<_, _>>()
+            .map(Self)
+    }
+}
+
+impl FromStr for Term {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let term = s.trim();
+
+        for (operator, negated) in [("!=", true), ("==", false)] {
+            if let Some((key, value)) = term.split_once(operator) {
+                let (key, value) = (key.trim(), value.trim());
+
+                if key != "language" {
+                    return Err(ParseError::UnknownKey(key.to_string()));
+                }
+
+                if value.is_empty() {
+                    return Err(ParseError::MissingValue(key.to_string()));
+                }
+
+                return Ok(Term::Language {
+                    language: value.to_string(),
+                    negated,
+                });
+            }
+        }
+
+        let (name, negated) = match term.strip_prefix('!') {
+            Some(name) => (name.trim(), true),
+            None => (term, false),
+        };
+
+        let flag = match name {
+            "editor_focused" => Flag::EditorFocused,
+            "modal_open" => Flag::ModalOpen,
+            "palette_open" => Flag::PaletteOpen,
+            "has_selection" => Flag::HasSelection,
+            "" => return Err(ParseError::EmptyTerm),
+            _ => return Err(ParseError::UnknownKey(name.to_string())),
+        };
+
+        Ok(Term::Flag { flag, negated })
+    }
+}
+
+impl fmt::Display for When {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let terms = self.0.iter().map(ToString::to_string).collect::<Vec<_>>();
+
+        write!(f, "{}", terms.join(" && "))
+    }
+}
+
+impl fmt::Display for Term {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Term::Flag { flag, negated } => {
+                let name = match flag {
+                    Flag::EditorFocused => "editor_focused",
+                    Flag::ModalOpen => "modal_open",
+                    Flag::PaletteOpen => "palette_open",
+                    Flag::HasSelection => "has_selection",
+                };
+
+                write!(f, "{}{name}", if *negated { "!" } else { "" })
+            }
+            Term::Language { language, negated } => {
+                write!(
+                    f,
+                    "language {} {language}",
+                    if *negated { "!=" } else { "==" }
+                )
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum ParseError {
+    #[error("unknown context key: {0}")]
+    UnknownKey(String),
+    #[error("missing value for {0}")]
+    MissingValue(String),
+    #[error("empty term in when clause")]
+    EmptyTerm,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn when(s: &str) -> When {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn parses_terms_joined_by_and() {
+        let clause = when(" editor_focused && !has_selection&&language == rust ");
+
+        assert_eq!(clause.specificity(), 3);
+        assert_eq!(
+            clause.to_string(),
+            "editor_focused && !has_selection && language == rust"
+        );
+        assert!(when("").is_always());
+    }
+
+    #[test]
+    fn rejects_invalid_terms() {
+        assert_eq!(
+            "focused".parse::<When>(),
+            Err(ParseError::UnknownKey(String::from("focused")))
+        );
+        assert_eq!(
+            "theme == dark".parse::<When>(),
+            Err(ParseError::UnknownKey(String::from("theme")))
+        );
+        assert_eq!(
+            "language != ".parse::<When>(),
+            Err(ParseError::MissingValue(String::from("language")))
+        );
+        assert_eq!(
+            "editor_focused &&".parse::<When>(),
+            Err(ParseError::EmptyTerm)
+        );
+    }
+
+    #[test]
+    fn matches_the_context() {
+        let context = Context {
+            editor_focused: true,
+            language: Some(String::from("Rust")),
+            ..Context::default()
+        };
+
+        assert!(when("editor_focused && language == rust").matches(&context));
+        assert!(!when("editor_focused && has_selection").matches(&context));
+        assert!(when("!modal_open && language != toml").matches(&context));
+    }
+
+    #[test]
+    fn opposite_terms_exclude_each_other() {
+        assert!(when("has_selection").excludes(&when("!has_selection")));
+        assert!(when("language == rust").excludes(&when("language == toml")));
+        assert!(when("language == rust").excludes(&when("language != Rust")));
+        assert!(!when("language != rust").excludes(&when("language != toml")));
+    }
+
+    #[test]
+    fn unrelated_clauses_can_hold_together() {
+        assert!(!when("editor_focused").excludes(&when("has_selection")));
+        assert!(!when("").excludes(&when("editor_focused")));
+    }
+
+    #[test]
+    fn the_editor_has_no_focus_while_a_modal_is_open() {
+        assert!(when("editor_focused").excludes(&when("modal_open")));
+        assert!(when("palette_open").excludes(&when("editor_focused")));
+        assert!(when("palette_open").excludes(&when("!modal_open")));
+        assert!(!when("palette_open").excludes(&when("modal_open")));
+    }
+}
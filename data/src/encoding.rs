@@ -0,0 +1,381 @@
+//! The character encoding of files.
+//!
+//! Files are decoded into the UTF-8 text of a [`Buffer`](crate::Buffer) when
+//! opened, and encoded back the same way when saved, byte order mark
+//! included.
+
+use std::borrow::Cow;
+use std::fmt;
+
+use encoding_rs::{
+    BIG5, EUC_JP, EUC_KR, EncoderResult, GB18030, GBK, IBM866, ISO_2022_JP, ISO_8859_2, ISO_8859_5,
+    ISO_8859_7, ISO_8859_15, KOI8_R, KOI8_U, MACINTOSH, SHIFT_JIS, UTF_8, UTF_16BE, UTF_16LE,
+    WINDOWS_874, WINDOWS_1250, WINDOWS_1251, WINDOWS_1252, WINDOWS_1253, WINDOWS_1254,
+    WINDOWS_1255, WINDOWS_1256, WINDOWS_1257, WINDOWS_1258,
+};
+
+/// How many bytes the heuristics look at.
+const SAMPLE: usize = 64 * 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Encoding {
+    encoding: &'static encoding_rs::Encoding,
+    /// Whether the file starts with a byte order mark.
+    has_bom: bool,
+}
+
+impl Default for Encoding {
+    fn default() -> Self {
+        Self::new(UTF_8, false)
+    }
+}
+
+impl Encoding {
+    fn new(encoding: &'static encoding_rs::Encoding, has_bom: bool) -> Self {
+        Self { encoding, has_bom }
+    }
+
+    /// The encodings files can be reopened or saved with, Unicode first.
+    pub fn all() -> Vec<Self> {
+        let unicode = [
+            (UTF_8, false),
+            (UTF_8, true),
+            (UTF_16LE, true),
+            (UTF_16BE, true),
+        ];
+        let legacy = [
+            WINDOWS_1252,
+            ISO_8859_15,
+            WINDOWS_1250,
+            ISO_8859_2,
+            WINDOWS_1251,
+            ISO_8859_5,
+            KOI8_R,
+            KOI8_U,
+            IBM866,
+            WINDOWS_1253,
+            ISO_8859_7,
+            WINDOWS_1254,
+            WINDOWS_1255,
+            WINDOWS_1256,
+            WINDOWS_1257,
+            WINDOWS_1258,
+            WINDOWS_874,
+            MACINTOSH,
+            SHIFT_JIS,
+            EUC_JP,
+            ISO_2022_JP,
+            GBK,
+            GB18030,
+            BIG5,
+            EUC_KR,
+        ];
+
+        unicode
+            .into_iter()
+            .chain(legacy.into_iter().map(|encoding| (encoding, false)))
+            .map(|(encoding, has_bom)| Self::new(encoding, has_bom))
+            .collect()
+    }
+
+    /// Guesses the encoding of a file from its `bytes`.
+    ///
+    /// A byte order mark settles it, otherwise text full of zero bytes is
+    /// taken as UTF-16, valid UTF-8 as such and anything else is left to the
+    /// statistics of [`chardetng`].
+    pub fn detect(bytes: &[u8]) -> Self {
+        if let Some((encoding, _)) = encoding_rs::Encoding::for_bom(bytes) {
+            return Self::new(encoding, true);
+        }
+
+        let sample = &bytes[..bytes.len().min(SAMPLE)];
+
+        // Zero bytes are valid UTF-8, so UTF-16 has to be ruled out first.
+        if let Some(encoding) = utf_16_without_bom(sample) {
+            return Self::new(encoding, false);
+        }
+
+        if std::str::from_utf8(bytes).is_ok() {
+            return Self::default();
+        }
+
+        let mut detector = chardetng::EncodingDetector::new();
+        detector.feed(sample, sample.len() == bytes.len());
+
+        Self::new(detector.guess(None, true), false)
+    }
+
+    /// Decodes the `bytes` of a file into text, skipping its byte order
+    /// mark.
+    ///
+    /// # Errors
+    ///
+    /// Will return [`Error::Malformed`] if the bytes are not valid in the
+    /// encoding, rather than replacing them and losing the original bytes
+    /// once the file is saved.
+    pub fn decode(self, bytes: &[u8]) -> Result<String, Error> {
+        let bytes = match encoding_rs::Encoding::for_bom(bytes) {
+            Some((encoding, len)) if encoding == self.encoding => &bytes[len..],
+            _ => bytes,
+        };
+
+        self.encoding
+            .decode_without_bom_handling_and_without_replacement(bytes)
+            .map(Cow::into_owned)
+            .ok_or(Error::Malformed(self))
+    }
+
+    /// An [`Encoder`] for text in the encoding, without the byte order
+    /// mark, see [`Encoding::bom`].
+    pub fn encoder(self) -> Encoder {
+        // The encoder of encoding_rs only writes UTF-16 as UTF-8, it is only
+        // ever meant to decode it.
+        let encoder = (self.encoding != UTF_16LE && self.encoding != UTF_16BE)
+            .then(|| self.encoding.new_encoder());
+
+        Encoder {
+            encoding: self,
+            encoder,
+        }
+    }
+
+    /// The byte order mark written at the start of the file.
+    pub fn bom(self) -> &'static [u8] {
+        if !self.has_bom {
+            return &[];
+        }
+
+        if self.encoding == UTF_8 {
+            b"\xef\xbb\xbf"
+        } else if self.encoding == UTF_16LE {
+            b"\xff\xfe"
+        } else if self.encoding == UTF_16BE {
+            b"\xfe\xff"
+        } else {
+            &[]
+        }
+    }
+}
+
+impl fmt::Display for Encoding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.encoding == UTF_16LE {
+            write!(f, "UTF-16 LE")?;
+        } else if self.encoding == UTF_16BE {
+            write!(f, "UTF-16 BE")?;
+        } else {
+            write!(f, "{}", self.encoding.name())?;
+        }
+
+        if self.has_bom && self.encoding == UTF_8 {
+            write!(f, " with BOM")?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Encodes text a piece at a time.
+///
+/// The state of the encodings that have one, like the escape sequences
+/// switching between the character sets of ISO-2022-JP, carries over from
+/// one piece to the next.
+pub struct Encoder {
+    encoding: Encoding,
+    /// `None` for UTF-16, which is encoded by hand.
+    encoder: Option<encoding_rs::Encoder>,
+}
+
+impl Encoder {
+    /// Appends `text` to `bytes`.
+    ///
+    /// # Errors
+    ///
+    /// Will return [`Error::Unmappable`] if `text` has chars the encoding
+    /// cannot represent.
+    pub fn encode(&mut self, text: &str, bytes: &mut Vec<u8>) -> Result<(), Error> {
+        self.write(text, bytes, false)
+    }
+
+    /// Appends what ends the encoded text to `bytes`, like the switch back to
+    /// ASCII of ISO-2022-JP.
+    ///
+    /// # Errors
+    ///
+    /// Will return [`Error::Unmappable`] if the encoder can't finish.
+    pub fn finish(mut self, bytes: &mut Vec<u8>) -> Result<(), Error> {
+        self.write("", bytes, true)
+    }
+
+    fn write(&mut self, mut text: &str, bytes: &mut Vec<u8>, last: bool) -> Result<(), Error> {
+        let Some(encoder) = &mut self.encoder else {
+            if self.encoding.encoding == UTF_16LE {
+                bytes.extend(text.encode_utf16().flat_map(u16::to_le_bytes));
+            } else {
+                bytes.extend(text.encode_utf16().flat_map(u16::to_be_bytes));
+            }
+
+            return Ok(());
+        };
+
+        loop {
+            bytes.reserve(
+                encoder
+                    .max_buffer_length_from_utf8_without_replacement(text.len())
+                    .unwrap_or(text.len()),
+            );
+
+            let (result, read) =
+                encoder.encode_from_utf8_to_vec_without_replacement(text, bytes, last);
+            text = &text[read..];
+
+            match result {
+                EncoderResult::InputEmpty => return Ok(()),
+                EncoderResult::OutputFull => {}
+                EncoderResult::Unmappable(_) => return Err(Error::Unmappable(self.encoding)),
+            }
+        }
+    }
+}
+
+/// UTF-16 without a byte order mark, told apart by the zero bytes that are
+/// the high half of every ASCII char.
+fn utf_16_without_bom(sample: &[u8]) -> Option<&'static encoding_rs::Encoding> {
+    let units = sample.len() / 2;
+
+    if units == 0 {
+        return None;
+    }
+
+    let (even, odd) = sample.chunks_exact(2).fold((0, 0), |(even, odd), unit| {
+        (
+            even + usize::from(unit[0] == 0),
+            odd + usize::from(unit[1] == 0),
+        )
+    });
+
+    // Mostly ASCII text has a zero in nearly every unit, always on the same
+    // side.
+    if odd * 3 > units * 2 && even * 10 < units {
+        Some(UTF_16LE)
+    } else if even * 3 > units * 2 && odd * 10 < units {
+        Some(UTF_16BE)
+    } else {
+        None
+    }
+}
+
+#[derive(Debug, Clone, thiserror::Error)]
+pub enum Error {
+    #[error("the file is not valid {0}")]
+    Malformed(Encoding),
+    #[error("the text has chars that can't be saved as {0}")]
+    Unmappable(Encoding),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use encoding_rs::ISO_2022_JP;
+
+    fn encode(encoding: Encoding, pieces: &[&str]) -> Result<Vec<u8>, Error> {
+        let mut bytes = Vec::new();
+        let mut encoder = encoding.encoder();
+
+        for piece in pieces {
+            encoder.encode(piece, &mut bytes)?;
+        }
+
+        encoder.finish(&mut bytes)?;
+
+        Ok(bytes)
+    }
+
+    fn round_trip(encoding: Encoding, text: &str) {
+        let mut bytes = encoding.bom().to_vec();
+        bytes.extend_from_slice(&encode(encoding, &[text]).unwrap());
+
+        assert_eq!(Encoding::detect(&bytes), encoding);
+        assert_eq!(encoding.decode(&bytes).unwrap(), text);
+    }
+
+    #[test]
+    fn detects_byte_order_marks() {
+        assert_eq!(
+            Encoding::detect(b"\xef\xbb\xbfabc"),
+            Encoding::new(UTF_8, true)
+        );
+        assert_eq!(
+            Encoding::detect(b"\xff\xfea\x00"),
+            Encoding::new(UTF_16LE, true)
+        );
+        assert_eq!(
+            Encoding::detect(b"\xfe\xff\x00a"),
+            Encoding::new(UTF_16BE, true)
+        );
+    }
+
+    #[test]
+    fn detects_utf_16_without_a_bom() {
+        let text = "plain ascii text\n";
+
+        assert_eq!(
+            Encoding::detect(&encode(Encoding::new(UTF_16LE, false), &[text]).unwrap()),
+            Encoding::new(UTF_16LE, false)
+        );
+        assert_eq!(
+            Encoding::detect(&encode(Encoding::new(UTF_16BE, false), &[text]).unwrap()),
+            Encoding::new(UTF_16BE, false)
+        );
+    }
+
+    #[test]
+    fn valid_utf_8_is_utf_8() {
+        assert_eq!(
+            Encoding::detect("naïve café".as_bytes()),
+            Encoding::default()
+        );
+        assert_eq!(Encoding::detect(b""), Encoding::default());
+    }
+
+    #[test]
+    fn round_trips_unicode() {
+        let text = "naïve café, 日本語\r\n";
+
+        round_trip(Encoding::default(), text);
+        round_trip(Encoding::new(UTF_8, true), text);
+        round_trip(Encoding::new(UTF_16LE, true), text);
+        round_trip(Encoding::new(UTF_16BE, true), text);
+    }
+
+    #[test]
+    fn round_trips_legacy_encodings() {
+        let latin = Encoding::new(WINDOWS_1252, false);
+        let bytes = encode(latin, &["café"]).unwrap();
+
+        assert_eq!(&*bytes, b"caf\xe9");
+        assert_eq!(latin.decode(&bytes).unwrap(), "café");
+    }
+
+    #[test]
+    fn keeps_the_encoder_state_across_pieces() {
+        let encoding = Encoding::new(ISO_2022_JP, false);
+        let bytes = encode(encoding, &["日本", "語\n"]).unwrap();
+
+        assert_eq!(bytes, &*ISO_2022_JP.encode("日本語\n").0);
+        assert_eq!(encoding.decode(&bytes).unwrap(), "日本語\n");
+    }
+
+    #[test]
+    fn malformed_and_unmappable_text_are_errors() {
+        assert!(matches!(
+            Encoding::default().decode(b"caf\xe9"),
+            Err(Error::Malformed(_))
+        ));
+        assert!(matches!(
+            encode(Encoding::new(WINDOWS_1252, false), &["日本語"]),
+            Err(Error::Unmappable(_))
+        ));
+    }
+}
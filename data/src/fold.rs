@@ -0,0 +1,322 @@
+//! Folding of the lines of a buffer.
+//!
+//! The regions that can be folded come from the syntax tree when the buffer
+//! has one, and from indentation otherwise. The folded ones are kept in
+//! [`Folds`], which projects the buffer onto the lines left visible.
+
+use std::collections::BTreeSet;
+use std::ops::Range;
+
+use serde::{Deserialize, Serialize};
+
+use crate::buffer::{Buffer, Edit, Position};
+use crate::syntax::Syntax;
+
+/// How many columns a tab counts for when comparing indentation.
+const TAB_WIDTH: usize = 4;
+
+/// A range of lines that can be folded. Its first line stays visible, the
+/// lines after it up to and including `end` are hidden.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub struct Region {
+    pub start: usize,
+    pub end: usize,
+}
+
+impl Region {
+    /// The lines hidden when the region is folded.
+    pub fn hidden(&self) -> Range<usize> {
+        self.start + 1..self.end + 1
+    }
+}
+
+/// The regions of `buffer` that can be folded, one per first line and sorted
+/// by it.
+///
+/// When several regions start on the same line, like a function and its
+/// body, the outermost one is kept.
+pub fn regions(buffer: &Buffer, syntax: Option<&Syntax>) -> Vec<Region> {
+    let mut regions = syntax
+        .map(|syntax| syntax.fold_regions(buffer))
+        .filter(|regions| !regions.is_empty())
+        .unwrap_or_else(|| indentation(buffer));
+
+    regions.sort_by(|a, b| a.start.cmp(&b.start).then(b.end.cmp(&a.end)));
+    regions.dedup_by_key(|region| region.start);
+
+    regions
+}
+
+/// The regions made of the lines indented deeper than the line before them.
+///
+/// Blank lines belong to the region around them, but a region never ends on
+/// one.
+pub fn indentation(buffer: &Buffer) -> Vec<Region> {
+    let mut regions = Vec::new();
+    let mut open: Vec<(usize, usize)> = Vec::new();
+    let mut last = 0;
+
+    for line in 0..buffer.len_lines() {
+        let Some(indent) = buffer.line_text(line).as_deref().and_then(indent_width) else {
+            continue;
+        };
+
+        while let Some(&(start, width)) = open.last()
+            && width >= indent
+        {
+            open.pop();
+
+            if last > start {
+                regions.push(Region { start, end: last });
+            }
+        }
+
+        open.push((line, indent));
+        last = line;
+    }
+
+    regions.extend(
+        open.into_iter()
+            .filter(|&(start, _)| last > start)
+            .map(|(start, _)| Region { start, end: last }),
+    );
+
+    regions
+}
+
+/// The width of the indentation of `line`, or `None` if it is blank.
+fn indent_width(line: &str) -> Option<usize> {
+    let content = line.trim_start();
+
+    if content.is_empty() {
+        return None;
+    }
+
+    Some(
+        line[..line.len() - content.len()]
+            .chars()
+            .map(|c| if c == '\t' { TAB_WIDTH } else { 1 })
+            .sum(),
+    )
+}
+
+/// The folded regions of a buffer.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Folds {
+    folded: BTreeSet<Region>,
+}
+
+impl Folds {
+    /// Folds the `saved` regions that are still among the `available` ones,
+    /// like the folds of a file from an earlier session.
+    pub fn restore(saved: &[Region], available: &[Region]) -> Self {
+        Self {
+            folded: saved
+                .iter()
+                .filter(|region| available.contains(region))
+                .copied()
+                .collect(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.folded.is_empty()
+    }
+
+    pub fn regions(&self) -> impl Iterator<Item = Region> + '_ {
+        self.folded.iter().copied()
+    }
+
+    /// Whether a folded region starts at `line`.
+    pub fn is_folded(&self, line: usize) -> bool {
+        self.folded.iter().any(|region| region.start == line)
+    }
+
+    /// Folds `region`, or unfolds it if it already was.
+    pub fn toggle(&mut self, region: Region) {
+        if !self.folded.remove(&region) {
+            self.folded.insert(region);
+        }
+    }
+
+    pub fn fold(&mut self, regions: impl IntoIterator<Item = Region>) {
+        self.folded.extend(regions);
+    }
+
+    pub fn unfold_all(&mut self) {
+        self.folded.clear();
+    }
+
+    /// Unfolds the regions starting in `lines`, returning whether there were
+    /// any.
+    pub fn unfold(&mut self, lines: Range<usize>) -> bool {
+        let before = self.folded.len();
+
+        self.folded.retain(|region| !lines.contains(&region.start));
+
+        self.folded.len() != before
+    }
+
+    /// Unfolds the regions hiding `line`, returning whether there were any.
+    pub fn reveal(&mut self, line: usize) -> bool {
+        let before = self.folded.len();
+
+        self.folded
+            .retain(|region| !region.hidden().contains(&line));
+
+        self.folded.len() != before
+    }
+
+    pub fn is_hidden(&self, line: usize) -> bool {
+        self.folded
+            .iter()
+            .any(|region| region.hidden().contains(&line))
+    }
+
+    /// The hidden lines, as sorted ranges that don't overlap.
+    fn hidden(&self) -> Vec<Range<usize>> {
+        let mut hidden: Vec<Range<usize>> = Vec::new();
+
+        for region in &self.folded {
+            let lines = region.hidden();
+
+            match hidden.last_mut() {
+                Some(last) if lines.start <= last.end => last.end = last.end.max(lines.end),
+                _ => hidden.push(lines),
+            }
+        }
+
+        hidden
+    }
+
+    /// The line of the projection showing the document `line`. Hidden lines
+    /// are shown by the first line of the region folding them.
+    pub fn to_view(&self, line: usize) -> usize {
+        let mut view = line;
+
+        for lines in self.hidden() {
+            if line < lines.start {
+                break;
+            }
+
+            view -= lines.len().min(line + 1 - lines.start);
+        }
+
+        view
+    }
+
+    /// The document line shown by the `line` of the projection.
+    pub fn to_document(&self, line: usize) -> usize {
+        let mut document = line;
+
+        for lines in self.hidden() {
+            if document < lines.start {
+                break;
+            }
+
+            document += lines.len();
+        }
+
+        document
+    }
+
+    /// The document position shown at `position` in the projection.
+    pub fn to_document_position(&self, position: Position) -> Position {
+        Position::new(self.to_document(position.line), position.column)
+    }
+
+    /// The lines of `buffer` left visible, joined by its line ending.
+    pub fn project(&self, buffer: &Buffer) -> String {
+        let line_ending = buffer.line_ending().as_str();
+
+        (0..buffer.len_lines())
+            .filter(|&line| !self.is_hidden(line))
+            .filter_map(|line| buffer.line_text(line))
+            .collect::<Vec<_>>()
+            .join(line_ending)
+    }
+
+    /// Moves the folds after `edit`, which is about to be applied to
+    /// `buffer`, and unfolds the ones it touches.
+    ///
+    /// Returns whether any region was unfolded.
+    pub fn edit(&mut self, buffer: &Buffer, edit: &Edit) -> bool {
+        let start = buffer.char_to_line(edit.range.start);
+        let end = buffer.char_to_line(edit.range.end);
+        let inserted = edit.text.matches('\n').count();
+        let before = self.folded.len();
+
+        self.folded = self
+            .folded
+            .iter()
+            .filter(|region| region.end < start || region.start > end)
+            .map(|region| {
+                if region.start > end {
+                    Region {
+                        start: region.start + inserted - (end - start),
+                        end: region.end + inserted - (end - start),
+                    }
+                } else {
+                    *region
+                }
+            })
+            .collect();
+
+        self.folded.len() != before
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn folds(regions: &[(usize, usize)]) -> Folds {
+        let mut folds = Folds::default();
+        folds.fold(regions.iter().map(|&(start, end)| Region { start, end }));
+        folds
+    }
+
+    #[test]
+    fn hidden_lines_are_shown_by_the_first_line_of_the_region() {
+        let folds = folds(&[(1, 3)]);
+
+        assert_eq!(
+            (0..6).map(|line| folds.to_view(line)).collect::<Vec<_>>(),
+            [0, 1, 1, 1, 2, 3]
+        );
+        assert_eq!(
+            (0..4)
+                .map(|line| folds.to_document(line))
+                .collect::<Vec<_>>(),
+            [0, 1, 4, 5]
+        );
+    }
+
+    #[test]
+    fn nested_and_adjacent_regions_hide_their_lines_once() {
+        let folds = folds(&[(1, 5), (2, 3), (6, 7)]);
+
+        assert_eq!(folds.to_view(5), 1);
+        assert_eq!(folds.to_view(6), 2);
+        assert_eq!(folds.to_view(8), 3);
+        assert_eq!(folds.to_document(2), 6);
+        assert_eq!(folds.to_document(3), 8);
+    }
+
+    #[test]
+    fn visible_lines_round_trip() {
+        let folds = folds(&[(2, 4), (7, 9)]);
+
+        for line in (0..12).filter(|&line| !folds.is_hidden(line)) {
+            assert_eq!(folds.to_document(folds.to_view(line)), line);
+        }
+    }
+
+    #[test]
+    fn no_folds_leave_lines_as_they_are() {
+        let folds = Folds::default();
+
+        assert_eq!(folds.to_view(7), 7);
+        assert_eq!(folds.to_document(7), 7);
+    }
+}
@@ -0,0 +1,174 @@
+//! Fuzzy subsequence matching, as used to filter the command palette.
+
+const MATCH: i64 = 16;
+const CONSECUTIVE: i64 = 12;
+const WORD_START: i64 = 8;
+const CAMEL_CASE: i64 = 6;
+const MAX_LEADING_PENALTY: i64 = 8;
+
+/// A successful match of a pattern against a candidate.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Match {
+    pub score: i64,
+    /// The char indices of the candidate that matched the pattern, in order.
+    pub indices: Vec<usize>,
+}
+
+/// Matches `pattern` as a case insensitive subsequence of `candidate`.
+///
+/// Characters at the start of words and runs of consecutive characters score
+/// higher, gaps between matched characters lower. Whitespace in the pattern is
+/// ignored and an empty pattern matches everything with a score of zero.
+pub fn fuzzy_match(pattern: &str, candidate: &str) -> Option<Match> {
+    let pattern = pattern
+        .chars()
+        .filter(|c| !c.is_whitespace())
+        .map(fold)
+        .collect::<Vec<_>>();
+
+    if pattern.is_empty() {
+        return Some(Match {
+            score: 0,
+            indices: vec![],
+        });
+    }
+
+    let original = candidate.chars().collect::<Vec<_>>();
+    let folded = original.iter().copied().map(fold).collect::<Vec<_>>();
+
+    if !is_subsequence(&pattern, &folded) {
+        return None;
+    }
+
+    let bonus = (0..original.len())
+        .map(|j| bonus(&original, j))
+        .collect::<Vec<_>>();
+
+    // scores[i][j] is the best score matching pattern[..=i] with pattern[i]
+    // landing on candidate[j], from[i][j] where pattern[i - 1] landed.
+    let mut scores = vec![vec![None; folded.len()]; pattern.len()];
+    let mut from = vec![vec![0; folded.len()]; pattern.len()];
+
+    for (j, c) in folded.iter().enumerate() {
+        if *c == pattern[0] {
+            let leading = i64::try_from(j)
+                .unwrap_or(i64::MAX)
+                .min(MAX_LEADING_PENALTY);
+
+            scores[0][j] = Some(MATCH + bonus[j] - leading);
+        }
+    }
+
+    for i in 1..pattern.len() {
+        // Best of `scores[i - 1][k] + k` over the k that leave a gap before j,
+        // the gap penalty being one per skipped char.
+        let mut best_gapped: Option<(i64, usize)> = None;
+
+        for j in i..folded.len() {
+            if j >= 2 {
+                let k = j - 2;
+
+                if let Some(score) = scores[i - 1][k] {
+                    let score = score + i64::try_from(k).unwrap_or(i64::MAX);
+
+                    if best_gapped.is_none_or(|(best, _)| score > best) {
+                        best_gapped = Some((score, k));
+                    }
+                }
+            }
+
+            if folded[j] != pattern[i] {
+                continue;
+            }
+
+            let consecutive = scores[i - 1][j - 1].map(|score| (score + CONSECUTIVE, j - 1));
+            let gapped =
+                best_gapped.map(|(score, k)| (score - i64::try_from(j).unwrap_or(i64::MAX) + 1, k));
+
+            let best = match (consecutive, gapped) {
+                (Some(a), Some(b)) => Some(if a.0 >= b.0 { a } else { b }),
+                (a, b) => a.or(b),
+            };
+
+            if let Some((score, k)) = best {
+                scores[i][j] = Some(score + MATCH + bonus[j]);
+                from[i][j] = k;
+            }
+        }
+    }
+
+    let last = pattern.len() - 1;
+    let (mut j, score) = scores[last]
+        .iter()
+        .enumerate()
+        .filter_map(|(j, score)| score.map(|score| (j, score)))
+        .max_by_key(|(_, score)| *score)?;
+
+    let mut indices = vec![0; pattern.len()];
+
+    for i in (0..pattern.len()).rev() {
+        indices[i] = j;
+        j = from[i][j];
+    }
+
+    Some(Match { score, indices })
+}
+
+fn fold(c: char) -> char {
+    c.to_lowercase().next().unwrap_or(c)
+}
+
+fn is_subsequence(pattern: &[char], candidate: &[char]) -> bool {
+    let mut candidate = candidate.iter();
+
+    pattern.iter().all(|p| candidate.any(|c| c == p))
+}
+
+fn bonus(candidate: &[char], j: usize) -> i64 {
+    let Some(previous) = j.checked_sub(1).map(|i| candidate[i]) else {
+        return WORD_START;
+    };
+    let current = candidate[j];
+
+    if matches!(previous, ' ' | '_' | '-' | '/' | '\\' | '.' | ':') {
+        WORD_START
+    } else if previous.is_lowercase() && current.is_uppercase() {
+        CAMEL_CASE
+    } else {
+        0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn score(pattern: &str, candidate: &str) -> i64 {
+        fuzzy_match(pattern, candidate).unwrap().score
+    }
+
+    #[test]
+    fn matches_subsequences_ignoring_case() {
+        let matched = fuzzy_match("SvF", "save file").unwrap();
+
+        assert_eq!(matched.indices, [0, 2, 5]);
+        assert!(fuzzy_match("fs", "save file").is_none());
+        assert_eq!(fuzzy_match(" ", "anything").unwrap().score, 0);
+    }
+
+    #[test]
+    fn word_starts_rank_above_scattered_chars() {
+        assert!(score("of", "Open File") > score("of", "Go to Folder"));
+        assert!(score("gtl", "Go to Line") > score("gtl", "Toggle Sidebar Tool"));
+    }
+
+    #[test]
+    fn consecutive_chars_rank_above_gaps() {
+        assert!(score("save", "Save File") > score("save", "Select All Vertically Everywhere"));
+    }
+
+    #[test]
+    fn earlier_matches_rank_above_later_ones() {
+        assert!(score("file", "file.rs") > score("file", "src/file.rs"));
+    }
+}
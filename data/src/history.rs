@@ -0,0 +1,666 @@
+//! The undo tree of a buffer.
+//!
+//! Every change is a node whose parent is the state it was made in, so
+//! undoing and then editing starts a new branch instead of dropping the
+//! changes that were undone. Consecutive typing is coalesced into a single
+//! node. The history of a file is stored along with a hash of its text, and
+//! only restored if the file still holds that text.
+
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+use std::{io, sync::Arc};
+
+use serde::{Deserialize, Serialize};
+use tokio::fs;
+
+use crate::buffer::{self, Edit};
+use crate::environment;
+
+/// How long after the last keystroke typing still goes into the same node.
+const COALESCE_WINDOW: Duration = Duration::from_secs(1);
+
+/// How many nodes a history keeps, the oldest branches are dropped past it.
+const MAX_NODES: usize = 1000;
+
+/// What kind of change a node is, typing and deleting coalesce with the
+/// changes of the same kind right after them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Kind {
+    Typing,
+    Deleting,
+    Other,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct History {
+    /// The nodes in the order they were created, the first one being the
+    /// state the history started from.
+    nodes: Vec<Node>,
+    current: usize,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Node {
+    parent: usize,
+    kind: Kind,
+    /// The edits of the change, in the order they were applied, each with
+    /// the edit undoing it.
+    changes: Vec<(Edit, Edit)>,
+    /// When the change was last added to.
+    time: SystemTime,
+    /// The child redoing goes to, the one created or undone last.
+    redo: Option<usize>,
+}
+
+/// A node of the history, as listed by [`History::entries`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Entry {
+    pub id: usize,
+    /// How many branches deep the node is.
+    pub depth: usize,
+    pub summary: String,
+    pub time: SystemTime,
+    pub is_current: bool,
+}
+
+impl Default for History {
+    fn default() -> Self {
+        Self {
+            nodes: vec![Node {
+                parent: 0,
+                kind: Kind::Other,
+                changes: Vec::new(),
+                time: SystemTime::now(),
+                redo: None,
+            }],
+            current: 0,
+        }
+    }
+}
+
+impl History {
+    /// Whether nothing was changed since the history started.
+    pub fn is_empty(&self) -> bool {
+        self.nodes.len() == 1
+    }
+
+    pub fn current(&self) -> usize {
+        self.current
+    }
+
+    /// Records the `changes` made by one action, each edit along with the
+    /// edit undoing it, as a child of the current node.
+    ///
+    /// Typing or deleting right after the same kind of change is added to
+    /// its node instead, unless that node was already undone.
+    pub fn record(&mut self, changes: Vec<(Edit, Edit)>, kind: Kind, time: SystemTime) {
+        if changes.is_empty() {
+            return;
+        }
+
+        let node = &mut self.nodes[self.current];
+
+        if self.current != 0
+            && kind != Kind::Other
+            && node.kind == kind
+            && node.redo.is_none()
+            && time
+                .duration_since(node.time)
+                .is_ok_and(|elapsed| elapsed < COALESCE_WINDOW)
+        {
+            node.changes.extend(changes);
+            node.time = time;
+
+            return;
+        }
+
+        let id = self.nodes.len();
+
+        self.nodes[self.current].redo = Some(id);
+        self.nodes.push(Node {
+            parent: self.current,
+            kind,
+            changes,
+            time,
+            redo: None,
+        });
+        self.current = id;
+
+        self.prune();
+    }
+
+    /// Drops the oldest branches until there are at most [`MAX_NODES`]
+    /// nodes, then the oldest changes leading to the current node, which the
+    /// history then starts after.
+    fn prune(&mut self) {
+        while self.nodes.len() > MAX_NODES {
+            let path = self.ancestors(self.current);
+
+            let removed = match (1..self.nodes.len()).find(|node| !path.contains(node)) {
+                // Parents come before their children, so a single pass finds
+                // the whole branch.
+                Some(oldest) => {
+                    let mut removed = vec![false; self.nodes.len()];
+
+                    for id in oldest..self.nodes.len() {
+                        removed[id] = id == oldest || removed[self.nodes[id].parent];
+                    }
+
+                    removed
+                }
+                None => {
+                    let mut removed = vec![false; self.nodes.len()];
+                    removed[0] = true;
+
+                    let root = &mut self.nodes[1];
+                    root.kind = Kind::Other;
+                    root.changes.clear();
+
+                    removed
+                }
+            };
+
+            self.remove(&removed);
+        }
+    }
+
+    /// Removes the nodes marked as `removed`, renumbering the others. Nodes
+    /// left without their parent become the root.
+    fn remove(&mut self, removed: &[bool]) {
+        let mut ids = Vec::with_capacity(removed.len());
+        let mut next = 0;
+
+        for &is_removed in removed {
+            ids.push((!is_removed).then_some(next));
+            next += usize::from(!is_removed);
+        }
+
+        let nodes = std::mem::take(&mut self.nodes);
+
+        self.nodes = nodes
+            .into_iter()
+            .zip(removed)
+            .filter(|(_, is_removed)| !**is_removed)
+            .map(|(node, _)| Node {
+                parent: ids[node.parent].unwrap_or(0),
+                redo: node.redo.and_then(|redo| ids[redo]),
+                ..node
+            })
+            .collect();
+        self.current = ids[self.current].unwrap_or(0);
+    }
+
+    /// The path from `node` up to the first node.
+    fn ancestors(&self, mut node: usize) -> Vec<usize> {
+        let mut path = vec![node];
+
+        while node != 0 {
+            node = self.nodes[node].parent;
+            path.push(node);
+        }
+
+        path
+    }
+
+    /// Steps back to the parent of the current node, returning the edits
+    /// undoing its change.
+    pub fn undo(&mut self) -> Option<Vec<Edit>> {
+        (self.current != 0).then(|| self.travel(self.nodes[self.current].parent))
+    }
+
+    /// Steps forward to the child undone or created last, returning the edits
+    /// redoing its change.
+    pub fn redo(&mut self) -> Option<Vec<Edit>> {
+        let child = self.nodes[self.current].redo?;
+
+        Some(self.travel(child))
+    }
+
+    /// Goes to the state before the current one in time, across branches.
+    pub fn earlier(&mut self) -> Option<Vec<Edit>> {
+        (self.current > 0).then(|| self.travel(self.current - 1))
+    }
+
+    /// Goes to the state after the current one in time, across branches.
+    pub fn later(&mut self) -> Option<Vec<Edit>> {
+        (self.current + 1 < self.nodes.len()).then(|| self.travel(self.current + 1))
+    }
+
+    /// Goes to the state the text was in at `time`, the one of the last node
+    /// changed at or before it, or to the first node if all of them were
+    /// changed after.
+    pub fn travel_to_time(&mut self, time: SystemTime) -> Option<Vec<Edit>> {
+        let target = self
+            .nodes
+            .iter()
+            .rposition(|node| node.time <= time)
+            .unwrap_or(0);
+
+        (target != self.current).then(|| self.travel(target))
+    }
+
+    /// Goes to the state of the node `target`, returning the edits that turn
+    /// the current state into it: undoing up to the node both descend from,
+    /// then redoing down to the target.
+    pub fn travel(&mut self, target: usize) -> Vec<Edit> {
+        if target >= self.nodes.len() {
+            return Vec::new();
+        }
+
+        let up = self.ancestors(self.current);
+        let down = self.ancestors(target);
+        let common = *up.iter().find(|node| down.contains(node)).unwrap_or(&0);
+
+        let mut edits = Vec::new();
+
+        for &node in up.iter().take_while(|&&node| node != common) {
+            edits.extend(
+                self.nodes[node]
+                    .changes
+                    .iter()
+                    .rev()
+                    .map(|(_, inverse)| inverse.clone()),
+            );
+
+            let parent = self.nodes[node].parent;
+            self.nodes[parent].redo = Some(node);
+        }
+
+        let down = down
+            .into_iter()
+            .take_while(|&node| node != common)
+            .collect::<Vec<_>>();
+
+        for &node in down.iter().rev() {
+            edits.extend(
+                self.nodes[node]
+                    .changes
+                    .iter()
+                    .map(|(edit, _)| edit.clone()),
+            );
+
+            let parent = self.nodes[node].parent;
+            self.nodes[parent].redo = Some(node);
+        }
+
+        self.current = target;
+
+        edits
+    }
+
+    /// Every node, depth first with the branches created first on top.
+    pub fn entries(&self) -> Vec<Entry> {
+        let mut entries = Vec::with_capacity(self.nodes.len());
+        let mut stack = vec![(0, 0)];
+
+        while let Some((id, depth)) = stack.pop() {
+            let node = &self.nodes[id];
+
+            entries.push(Entry {
+                id,
+                depth,
+                summary: summary(id, node),
+                time: node.time,
+                is_current: id == self.current,
+            });
+
+            let children = (id + 1..self.nodes.len())
+                .filter(|&child| self.nodes[child].parent == id)
+                .collect::<Vec<_>>();
+
+            // The first child continues the branch of its parent, the
+            // others start branches of their own.
+            for (index, &child) in children.iter().enumerate().rev() {
+                stack.push((child, if index == 0 { depth } else { depth + 1 }));
+            }
+        }
+
+        entries
+    }
+
+    /// # Errors
+    ///
+    /// Will return `Error` if serde is unable to deserialize the data, could
+    /// return `Error` due to IO failure, and returns [`Error::Outdated`] if
+    /// the history was saved for other text than the one hashed to `hash`.
+    pub async fn load(file: PathBuf, hash: u64) -> Result<History, Error> {
+        let path = path(&file)?;
+        let bytes = fs::read(path).await?;
+
+        Self::decode(&bytes, &file, hash)
+    }
+
+    /// Saves the history of `file`, whose text is hashed to `hash`.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Error` if serde is unable to serialize the data, or could
+    /// return `Error` due to IO failure.
+    pub async fn save(self, file: PathBuf, hash: u64) -> Result<(), Error> {
+        let path = path(&file)?;
+        let bytes = self.encode(file, hash)?;
+
+        fs::write(path, &bytes).await?;
+
+        Ok(())
+    }
+
+    /// The stored form of the history of `file`, see [`History::save`].
+    fn encode(self, file: PathBuf, hash: u64) -> Result<Vec<u8>, Error> {
+        Ok(serde_json::to_vec(&Stored {
+            path: file,
+            hash,
+            history: self,
+        })?)
+    }
+
+    /// Reads the history of `file` back from its stored form, see
+    /// [`History::load`].
+    fn decode(bytes: &[u8], file: &Path, hash: u64) -> Result<History, Error> {
+        let stored: Stored = serde_json::from_slice(bytes)?;
+
+        if stored.path != file || stored.hash != hash || stored.history.is_corrupt() {
+            return Err(Error::Outdated);
+        }
+
+        Ok(stored.history)
+    }
+
+    /// Whether the nodes don't form a tree rooted at the first one.
+    fn is_corrupt(&self) -> bool {
+        self.nodes.is_empty()
+            || self.current >= self.nodes.len()
+            || self
+                .nodes
+                .iter()
+                .enumerate()
+                .skip(1)
+                .any(|(id, node)| node.parent >= id)
+    }
+}
+
+fn summary(id: usize, node: &Node) -> String {
+    if id == 0 {
+        return String::from("Original");
+    }
+
+    match node.kind {
+        Kind::Typing => {
+            let typed = node
+                .changes
+                .iter()
+                .map(|(edit, _)| edit.text.as_str())
+                .collect::<String>();
+
+            format!("Typed \"{}\"", truncate(&typed))
+        }
+        Kind::Deleting => {
+            let deleted = node
+                .changes
+                .iter()
+                .map(|(_, inverse)| inverse.text.chars().count())
+                .sum::<usize>();
+
+            format!("Deleted {deleted} chars")
+        }
+        Kind::Other => {
+            let inserted = node
+                .changes
+                .iter()
+                .map(|(edit, _)| edit.text.as_str())
+                .collect::<String>();
+
+            if inserted.is_empty() {
+                String::from("Deleted text")
+            } else {
+                format!("Inserted \"{}\"", truncate(&inserted))
+            }
+        }
+    }
+}
+
+fn truncate(text: &str) -> String {
+    const MAX_CHARS: usize = 24;
+
+    let text = text.replace(['\r', '\n'], "\u{23ce}");
+
+    if text.chars().count() > MAX_CHARS {
+        format!(
+            "{}\u{2026}",
+            text.chars().take(MAX_CHARS).collect::<String>()
+        )
+    } else {
+        text
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Stored {
+    path: PathBuf,
+    hash: u64,
+    history: History,
+}
+
+fn path(file: &Path) -> Result<PathBuf, Error> {
+    let parent = environment::data_dir().join("history");
+
+    if !parent.exists() {
+        std::fs::create_dir_all(&parent)?;
+    }
+
+    let key = buffer::fnv_hash(file.to_string_lossy().as_bytes());
+
+    Ok(parent.join(format!("{key:016x}.json")))
+}
+
+#[derive(Debug, Clone, thiserror::Error)]
+pub enum Error {
+    #[error(transparent)]
+    Serde(Arc<serde_json::Error>),
+    #[error(transparent)]
+    Io(Arc<io::Error>),
+    #[error("the file changed since its history was saved")]
+    Outdated,
+}
+
+impl Error {
+    /// Whether there simply is no history to restore, which is not worth
+    /// reporting.
+    pub fn is_missing(&self) -> bool {
+        match self {
+            Error::Io(error) => error.kind() == io::ErrorKind::NotFound,
+            Error::Outdated => true,
+            Error::Serde(_) => false,
+        }
+    }
+}
+
+impl From<serde_json::Error> for Error {
+    fn from(error: serde_json::Error) -> Self {
+        Self::Serde(Arc::new(error))
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(error: io::Error) -> Self {
+        Self::Io(Arc::new(error))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Buffer;
+
+    fn insert(buffer: &mut Buffer, history: &mut History, at: usize, text: &str) {
+        let edit = Edit::insert(at, text);
+        let inverse = buffer.apply(edit.clone());
+
+        history.record(vec![(edit, inverse)], Kind::Other, SystemTime::now());
+    }
+
+    fn apply(buffer: &mut Buffer, edits: Vec<Edit>) {
+        for edit in edits {
+            buffer.apply(edit);
+        }
+    }
+
+    #[test]
+    fn editing_after_undo_starts_a_branch() {
+        let mut buffer = Buffer::with_text("");
+        let mut history = History::default();
+
+        insert(&mut buffer, &mut history, 0, "a");
+        apply(&mut buffer, history.undo().unwrap());
+        insert(&mut buffer, &mut history, 0, "b");
+
+        assert_eq!(buffer.text(), "b");
+        assert_eq!(
+            history.entries().iter().map(|entry| entry.depth).max(),
+            Some(1)
+        );
+
+        apply(&mut buffer, history.travel(1));
+        assert_eq!(buffer.text(), "a");
+        assert_eq!(history.current(), 1);
+
+        apply(&mut buffer, history.travel(2));
+        assert_eq!(buffer.text(), "b");
+
+        apply(&mut buffer, history.travel(0));
+        assert_eq!(buffer.text(), "");
+    }
+
+    #[test]
+    fn travel_to_time_goes_to_the_last_change_before_it() {
+        let mut buffer = Buffer::with_text("");
+        let mut history = History::default();
+        let start = SystemTime::now();
+        let at = |seconds| start + Duration::from_secs(seconds);
+
+        for (text, seconds) in [("a", 10), ("b", 20)] {
+            let edit = Edit::insert(buffer.len_chars(), text);
+            let inverse = buffer.apply(edit.clone());
+
+            history.record(vec![(edit, inverse)], Kind::Other, at(seconds));
+        }
+
+        apply(&mut buffer, history.undo().unwrap());
+
+        let edit = Edit::insert(1, "c");
+        let inverse = buffer.apply(edit.clone());
+        history.record(vec![(edit, inverse)], Kind::Other, at(30));
+
+        apply(&mut buffer, history.travel_to_time(at(25)).unwrap());
+        assert_eq!(buffer.text(), "ab");
+
+        apply(&mut buffer, history.travel_to_time(at(15)).unwrap());
+        assert_eq!(buffer.text(), "a");
+
+        apply(&mut buffer, history.travel_to_time(at(35)).unwrap());
+        assert_eq!(buffer.text(), "ac");
+        assert!(history.travel_to_time(at(30)).is_none());
+
+        apply(
+            &mut buffer,
+            history.travel_to_time(SystemTime::UNIX_EPOCH).unwrap(),
+        );
+        assert_eq!(buffer.text(), "");
+    }
+
+    #[test]
+    fn redo_follows_the_branch_traveled_last() {
+        let mut buffer = Buffer::with_text("");
+        let mut history = History::default();
+
+        insert(&mut buffer, &mut history, 0, "a");
+        apply(&mut buffer, history.undo().unwrap());
+        insert(&mut buffer, &mut history, 0, "b");
+        apply(&mut buffer, history.travel(1));
+        apply(&mut buffer, history.undo().unwrap());
+        apply(&mut buffer, history.redo().unwrap());
+
+        assert_eq!(buffer.text(), "a");
+    }
+
+    #[test]
+    fn pruning_drops_the_oldest_branch_first() {
+        let mut buffer = Buffer::with_text("");
+        let mut history = History::default();
+
+        insert(&mut buffer, &mut history, 0, "x");
+        apply(&mut buffer, history.undo().unwrap());
+
+        for _ in 0..MAX_NODES - 1 {
+            insert(&mut buffer, &mut history, 0, "a");
+        }
+
+        assert_eq!(history.nodes.len(), MAX_NODES);
+        assert!(!history.is_corrupt());
+        assert!(
+            history
+                .entries()
+                .iter()
+                .all(|entry| entry.summary != "Inserted \"x\"")
+        );
+
+        apply(&mut buffer, history.travel(0));
+        assert_eq!(buffer.text(), "");
+    }
+
+    #[test]
+    fn pruning_a_single_branch_drops_its_oldest_changes() {
+        let mut buffer = Buffer::with_text("");
+        let mut history = History::default();
+
+        for _ in 0..MAX_NODES + 10 {
+            insert(&mut buffer, &mut history, 0, "a");
+        }
+
+        assert_eq!(history.nodes.len(), MAX_NODES);
+        assert_eq!(history.current(), MAX_NODES - 1);
+        assert!(!history.is_corrupt());
+
+        apply(&mut buffer, history.travel(0));
+        assert_eq!(buffer.len_chars(), 11);
+    }
+
+    #[test]
+    fn stored_history_round_trips() {
+        let mut buffer = Buffer::with_text("");
+        let mut history = History::default();
+
+        insert(&mut buffer, &mut history, 0, "a");
+        apply(&mut buffer, history.undo().unwrap());
+        insert(&mut buffer, &mut history, 0, "b");
+
+        let file = PathBuf::from("/tmp/file.txt");
+        let bytes = history.clone().encode(file.clone(), 7).unwrap();
+        let mut loaded = History::decode(&bytes, &file, 7).unwrap();
+
+        assert_eq!(loaded.entries(), history.entries());
+
+        apply(&mut buffer, loaded.travel(1));
+        assert_eq!(buffer.text(), "a");
+    }
+
+    #[test]
+    fn stored_history_of_other_text_is_outdated() {
+        let mut buffer = Buffer::with_text("");
+        let mut history = History::default();
+
+        insert(&mut buffer, &mut history, 0, "a");
+
+        let file = PathBuf::from("/tmp/file.txt");
+        let bytes = history.encode(file.clone(), 7).unwrap();
+
+        assert!(matches!(
+            History::decode(&bytes, &file, 8),
+            Err(Error::Outdated)
+        ));
+        assert!(matches!(
+            History::decode(&bytes, Path::new("/tmp/other.txt"), 7),
+            Err(Error::Outdated)
+        ));
+    }
+}
@@ -0,0 +1,228 @@
+//! The languages a buffer can be highlighted as, and how to tell which one a
+//! file is written in.
+
+use std::fmt;
+use std::path::Path;
+
+use crate::Buffer;
+
+/// How many lines at the start and end of a buffer are searched for a
+/// modeline.
+const MODELINE_LINES: usize = 5;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Language {
+    /// The name shown to the user.
+    pub name: &'static str,
+    /// The identifier used in modelines and `when` clauses.
+    pub id: &'static str,
+    /// The extensions of files in the language, the first one is also the
+    /// token the highlighter knows the language by.
+    pub extensions: &'static [&'static str],
+    /// File names that are in the language regardless of their extension.
+    pub file_names: &'static [&'static str],
+    /// Interpreters named in the shebang line of scripts in the language.
+    pub interpreters: &'static [&'static str],
+    /// Other names the language goes by in modelines.
+    pub aliases: &'static [&'static str],
+}
+
+macro_rules! language {
+    ($name:literal, $id:literal, [$($extension:literal),*], [$($file_name:literal),*], [$($interpreter:literal),*], [$($alias:literal),*]) => {
+        Language {
+            name: $name,
+            id: $id,
+            extensions: &[$($extension),*],
+            file_names: &[$($file_name),*],
+            interpreters: &[$($interpreter),*],
+            aliases: &[$($alias),*],
+        }
+    };
+}
+
+pub const PLAIN_TEXT: Language = language!("Plain Text", "plaintext", ["txt"], [], [], ["text"]);
+
+pub const ALL: &[Language] = &[
+    PLAIN_TEXT,
+    language!("C", "c", ["c", "h"], [], [], []),
+    language!(
+        "C++",
+        "cpp",
+        ["cpp", "cc", "cxx", "hpp", "hh"],
+        [],
+        [],
+        ["c++"]
+    ),
+    language!("CSS", "css", ["css"], [], [], []),
+    language!("Diff", "diff", ["diff", "patch"], [], [], []),
+    language!("Go", "go", ["go"], [], [], ["golang"]),
+    language!("HTML", "html", ["html", "htm"], [], [], []),
+    language!("Java", "java", ["java"], [], [], []),
+    language!(
+        "JavaScript",
+        "javascript",
+        ["js", "mjs", "cjs"],
+        [],
+        ["node"],
+        ["js"]
+    ),
+    language!("JSON", "json", ["json"], [], [], []),
+    language!("Lua", "lua", ["lua"], [], ["lua"], []),
+    language!(
+        "Makefile",
+        "makefile",
+        ["mk"],
+        ["Makefile", "makefile", "GNUmakefile"],
+        ["make"],
+        ["make"]
+    ),
+    language!("Markdown", "markdown", ["md", "markdown"], [], [], []),
+    language!(
+        "Python",
+        "python",
+        ["py", "pyi"],
+        [],
+        ["python", "python3"],
+        ["py"]
+    ),
+    language!(
+        "Ruby",
+        "ruby",
+        ["rb"],
+        ["Rakefile", "Gemfile"],
+        ["ruby"],
+        []
+    ),
+    language!("Rust", "rust", ["rs"], [], [], []),
+    language!(
+        "Shell",
+        "shell",
+        ["sh", "bash", "zsh"],
+        [".bashrc", ".zshrc", ".profile"],
+        ["sh", "bash", "zsh", "dash"],
+        ["bash", "sh", "zsh"]
+    ),
+    language!("SQL", "sql", ["sql"], [], [], []),
+    language!("TOML", "toml", ["toml"], ["Cargo.lock"], [], []),
+    language!("XML", "xml", ["xml", "svg"], [], [], []),
+    language!("YAML", "yaml", ["yaml", "yml"], [], [], []),
+];
+
+impl Language {
+    /// The token the highlighter knows the language by.
+    pub fn token(&self) -> &'static str {
+        self.extensions.first().copied().unwrap_or_default()
+    }
+
+    /// Finds a language by its identifier, name or one of its aliases,
+    /// ignoring case.
+    pub fn find(name: &str) -> Option<Language> {
+        ALL.iter().copied().find(|language| {
+            language.id.eq_ignore_ascii_case(name)
+                || language.name.eq_ignore_ascii_case(name)
+                || language
+                    .aliases
+                    .iter()
+                    .any(|alias| alias.eq_ignore_ascii_case(name))
+        })
+    }
+
+    pub fn from_path(path: &Path) -> Option<Language> {
+        let file_name = path.file_name()?.to_str()?;
+
+        if let Some(language) = ALL
+            .iter()
+            .find(|language| language.file_names.contains(&file_name))
+        {
+            return Some(*language);
+        }
+
+        let extension = path.extension()?.to_str()?.to_ascii_lowercase();
+
+        ALL.iter()
+            .copied()
+            .find(|language| language.extensions.contains(&extension.as_str()))
+    }
+
+    /// Detects scripts from a `#!/usr/bin/env python3` like first line.
+    pub fn from_shebang(line: &str) -> Option<Language> {
+        let command = line.strip_prefix("#!")?;
+        let mut words = command.split_whitespace();
+        let mut program = words.next()?.rsplit('/').next()?;
+
+        if program == "env" {
+            program = words.find(|word| !word.starts_with('-'))?;
+        }
+
+        // `python3.12` and `python3` are both `python`.
+        let program = program.trim_end_matches(|c: char| c.is_ascii_digit() || c == '.');
+
+        ALL.iter().copied().find(|language| {
+            language.interpreters.iter().any(|interpreter| {
+                interpreter.trim_end_matches(|c: char| c.is_ascii_digit()) == program
+            })
+        })
+    }
+
+    /// Detects the language named by a vim (`vim: set ft=rust:`) or emacs
+    /// (`-*- mode: rust -*-`) modeline.
+    pub fn from_modeline(line: &str) -> Option<Language> {
+        if let Some((_, rest)) = line.split_once("-*-") {
+            let (variables, _) = rest.split_once("-*-")?;
+
+            let mode = if variables.contains(':') {
+                variables.split(';').find_map(|variable| {
+                    let (key, value) = variable.split_once(':')?;
+
+                    key.trim()
+                        .eq_ignore_ascii_case("mode")
+                        .then(|| value.trim())
+                })?
+            } else {
+                variables.trim()
+            };
+
+            return Language::find(mode);
+        }
+
+        let (_, options) = ["vim:", "vi:", "ex:"]
+            .iter()
+            .find_map(|marker| line.split_once(marker))?;
+
+        options
+            .split([' ', ':', '\t'])
+            .find_map(|option| {
+                option
+                    .strip_prefix("ft=")
+                    .or_else(|| option.strip_prefix("filetype="))
+                    .or_else(|| option.strip_prefix("syntax="))
+                    .or_else(|| option.strip_prefix("syn="))
+            })
+            .and_then(Language::find)
+    }
+}
+
+impl fmt::Display for Language {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name)
+    }
+}
+
+/// Detects the language of `buffer`, from a modeline first, then from the
+/// name of the file at `path` and finally from its shebang line.
+pub fn detect(path: Option<&Path>, buffer: &Buffer) -> Language {
+    let len = buffer.len_lines();
+    let head = 0..MODELINE_LINES.min(len);
+    let tail = len.saturating_sub(MODELINE_LINES).max(head.end)..len;
+
+    head.chain(tail)
+        .filter_map(|index| buffer.line_text(index))
+        .find_map(|line| Language::from_modeline(&line))
+        .or_else(|| path.and_then(Language::from_path))
+        .or_else(|| {
+            buffer
+                .line_text(0)
+                .and_then(|line| Language::from_shebang(&line))
+        })
+        .unwrap_or(PLAIN_TEXT)
+}
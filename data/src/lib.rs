@@ -1,9 +1,27 @@
 #![allow(clippy::large_enum_variant, clippy::too_many_arguments)]
 pub use self::appearance::Theme;
+pub use self::buffer::Buffer;
+pub use self::session::Session;
 pub use self::window::Window;
 
 pub mod appearance;
+pub mod buffer;
+pub mod caret;
+pub mod command;
 pub mod config;
+pub mod context;
+pub mod encoding;
 pub mod environment;
+pub mod fold;
+pub mod fuzzy;
+pub mod history;
+pub mod language;
+pub mod line_ending;
+pub mod palette;
+pub mod project;
+pub mod session;
 pub mod shortcut;
+pub mod symbol;
+pub mod syntax;
+pub mod title;
 pub mod window;
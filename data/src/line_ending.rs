@@ -0,0 +1,168 @@
+//! The line endings of a [`Buffer`](crate::Buffer).
+//!
+//! Files keep the line endings they were opened with, new lines get the one
+//! most lines of the file end with.
+
+use std::fmt;
+
+use ropey::RopeSlice;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum LineEnding {
+    #[default]
+    Lf,
+    Crlf,
+    /// Classic Mac OS, only ever kept as found.
+    Cr,
+}
+
+impl LineEnding {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            LineEnding::Lf => "\n",
+            LineEnding::Crlf => "\r\n",
+            LineEnding::Cr => "\r",
+        }
+    }
+
+    /// The line ending `line` ends with, if it is not the last line.
+    pub(crate) fn of(line: RopeSlice<'_>) -> Option<Self> {
+        let len = line.len_chars();
+
+        match (
+            len.checked_sub(2).map(|i| line.char(i)),
+            len.checked_sub(1).map(|i| line.char(i)),
+        ) {
+            (Some('\r'), Some('\n')) => Some(LineEnding::Crlf),
+            (_, Some('\n')) => Some(LineEnding::Lf),
+            (_, Some('\r')) => Some(LineEnding::Cr),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for LineEnding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LineEnding::Lf => write!(f, "LF"),
+            LineEnding::Crlf => write!(f, "CRLF"),
+            LineEnding::Cr => write!(f, "CR"),
+        }
+    }
+}
+
+/// How many lines end with each line ending.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Counts {
+    pub lf: usize,
+    pub crlf: usize,
+    pub cr: usize,
+}
+
+impl Counts {
+    /// How many lines of `text` end with each line ending.
+    pub fn of(text: &str) -> Self {
+        let mut counts = Self::default();
+        let mut chars = text.chars().peekable();
+
+        while let Some(c) = chars.next() {
+            match c {
+                '\r' if chars.next_if_eq(&'\n').is_some() => counts.add(LineEnding::Crlf),
+                '\r' => counts.add(LineEnding::Cr),
+                '\n' => counts.add(LineEnding::Lf),
+                _ => {}
+            }
+        }
+
+        counts
+    }
+
+    pub(crate) fn add(&mut self, line_ending: LineEnding) {
+        match line_ending {
+            LineEnding::Lf => self.lf += 1,
+            LineEnding::Crlf => self.crlf += 1,
+            LineEnding::Cr => self.cr += 1,
+        }
+    }
+
+    /// Updates the counts after the `removed` text was replaced by the
+    /// `inserted` one.
+    pub fn replace(&mut self, removed: &str, inserted: &str) {
+        let removed = Self::of(removed);
+        let inserted = Self::of(inserted);
+
+        self.lf = self.lf + inserted.lf - removed.lf;
+        self.crlf = self.crlf + inserted.crlf - removed.crlf;
+        self.cr = self.cr + inserted.cr - removed.cr;
+    }
+
+    /// The line ending most lines end with, LF winning ties. `None` if there
+    /// is a single line.
+    pub fn dominant(&self) -> Option<LineEnding> {
+        [
+            (LineEnding::Cr, self.cr),
+            (LineEnding::Crlf, self.crlf),
+            (LineEnding::Lf, self.lf),
+        ]
+        .into_iter()
+        .filter(|(_, count)| *count > 0)
+        .max_by_key(|(_, count)| *count)
+        .map(|(line_ending, _)| line_ending)
+    }
+
+    /// Whether lines end in more than one way.
+    pub fn is_mixed(&self) -> bool {
+        [self.lf, self.crlf, self.cr]
+            .into_iter()
+            .filter(|count| *count > 0)
+            .count()
+            > 1
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn counts_each_line_ending() {
+        assert_eq!(
+            Counts::of("a\r\nb\nc\rd\r\n"),
+            Counts {
+                lf: 1,
+                crlf: 2,
+                cr: 1,
+            }
+        );
+    }
+
+    #[test]
+    fn replacing_text_moves_the_counts() {
+        let mut counts = Counts::of("a\rb\n");
+
+        // Inserting "\n" right after a lone "\r" turns it into a CRLF.
+        counts.replace("\rb", "\r\nb");
+        counts.replace("b\n", "b");
+
+        assert_eq!(
+            counts,
+            Counts {
+                lf: 0,
+                crlf: 1,
+                cr: 0,
+            }
+        );
+    }
+
+    #[test]
+    fn dominant_line_ending_prefers_lf_on_ties() {
+        assert_eq!(Counts::of("a\r\nb\n").dominant(), Some(LineEnding::Lf));
+        assert_eq!(
+            Counts::of("a\r\nb\r\nc\n").dominant(),
+            Some(LineEnding::Crlf)
+        );
+        assert_eq!(Counts::of("a").dominant(), None);
+        assert!(Counts::of("a\r\nb\n").is_mixed());
+        assert!(!Counts::of("a\nb\n").is_mixed());
+    }
+}
@@ -0,0 +1,233 @@
+use std::path::{Path, PathBuf};
+use std::{io, sync::Arc};
+
+use serde::{Deserialize, Serialize};
+use tokio::fs;
+
+use crate::buffer::Position;
+use crate::environment;
+
+const MAX_ENTRIES: usize = 100;
+
+/// What the command palette lists, picked by the first char of the input.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mode {
+    /// Files of the project, when the input has no prefix.
+    Files,
+    /// `>` commands.
+    Commands,
+    /// `:` a line and optional column to go to.
+    Line,
+    /// `@` symbols of the current file.
+    Symbols,
+    /// Languages to highlight the current file as. It has no prefix, so the
+    /// palette only gets there from its command.
+    Languages,
+    /// Encodings to reopen the current file with, only reached from its
+    /// command as well.
+    ReopenWithEncoding,
+    /// Encodings to save the current file with.
+    SaveWithEncoding,
+}
+
+impl Mode {
+    /// Splits `input` into its mode and the query following the prefix.
+    pub fn parse(input: &str) -> (Mode, &str) {
+        for mode in [Mode::Commands, Mode::Line, Mode::Symbols] {
+            if let Some(query) = input.strip_prefix(mode.prefix()) {
+                return (mode, query);
+            }
+        }
+
+        (Mode::Files, input)
+    }
+
+    pub fn prefix(self) -> &'static str {
+        match self {
+            Mode::Files | Mode::Languages | Mode::ReopenWithEncoding | Mode::SaveWithEncoding => "",
+            Mode::Commands => ">",
+            Mode::Line => ":",
+            Mode::Symbols => "@",
+        }
+    }
+
+    pub fn placeholder(self) -> &'static str {
+        match self {
+            Mode::Files => "Search files by name, > commands, : line, @ symbols",
+            Mode::Commands => "Type a command",
+            Mode::Line => "Type a line number, optionally followed by :column",
+            Mode::Symbols => "Type the name of a symbol",
+            Mode::Languages => "Select a language",
+            Mode::ReopenWithEncoding => "Select an encoding to reopen the file with",
+            Mode::SaveWithEncoding => "Select an encoding to save the file with",
+        }
+    }
+}
+
+/// Parses a 1-based `line[:column]` query into a [`Position`].
+///
+/// The column is counted in chars and still has to be converted to bytes
+/// against the line it lands on.
+pub fn parse_line(query: &str) -> Option<Position> {
+    let query = query.trim();
+    let (line, column) = match query.split_once([':', ',']) {
+        Some((line, column)) => (line, Some(column.trim())),
+        None => (query, None),
+    };
+
+    let line = line.trim().parse::<usize>().ok()?.checked_sub(1)?;
+    let column = match column.filter(|column| !column.is_empty()) {
+        Some(column) => column.parse::<usize>().ok()?.checked_sub(1)?,
+        None => 0,
+    };
+
+    Some(Position::new(line, column))
+}
+
+/// The [`History`] key of a file.
+pub fn file_key(path: &Path) -> String {
+    format!("file:{}", path.display())
+}
+
+/// The entries most recently picked from the command palette, most recent
+/// first.
+///
+/// Commands are keyed by their id, files by [`file_key`].
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct History {
+    #[serde(default)]
+    recent: Vec<String>,
+}
+
+impl History {
+    /// # Errors
+    ///
+    /// Will return `Error` if serde is unable to deserialize the data, or could
+    /// return `Error` due to IO failure.
+    pub async fn load() -> Result<History, Error> {
+        let path = path()?;
+        let bytes = fs::read(path).await?;
+
+        Ok(serde_json::from_slice(&bytes)?)
+    }
+
+    /// # Errors
+    ///
+    /// Will return `Error` if serde is unable to serialize the data, or could
+    /// return `Error` due to IO failure.
+    pub async fn save(self) -> Result<(), Error> {
+        let path = path()?;
+
+        let bytes = serde_json::to_vec(&self)?;
+        fs::write(path, &bytes).await?;
+
+        Ok(())
+    }
+
+    /// Moves `key` to the front of the history.
+    pub fn record(&mut self, key: impl Into<String>) {
+        let key = key.into();
+
+        self.recent.retain(|recent| *recent != key);
+        self.recent.insert(0, key);
+        self.recent.truncate(MAX_ENTRIES);
+    }
+
+    /// How recently `key` was picked, `0` being the last entry picked.
+    pub fn rank(&self, key: &str) -> Option<usize> {
+        self.recent.iter().position(|recent| recent == key)
+    }
+
+    /// A score boost for `key`, larger the more recently it was picked.
+    pub fn boost(&self, key: &str) -> i64 {
+        self.rank(key).map_or(0, |rank| {
+            i64::try_from(MAX_ENTRIES - rank).unwrap_or_default()
+        })
+    }
+}
+
+fn path() -> Result<PathBuf, Error> {
+    let parent = environment::data_dir();
+
+    if !parent.exists() {
+        std::fs::create_dir_all(&parent)?;
+    }
+
+    Ok(parent.join("palette.json"))
+}
+
+#[derive(Debug, Clone, thiserror::Error)]
+pub enum Error {
+    #[error(transparent)]
+    Serde(Arc<serde_json::Error>),
+    #[error(transparent)]
+    Io(Arc<io::Error>),
+}
+
+impl From<serde_json::Error> for Error {
+    fn from(error: serde_json::Error) -> Self {
+        Self::Serde(Arc::new(error))
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(error: io::Error) -> Self {
+        Self::Io(Arc::new(error))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fuzzy::fuzzy_match;
+
+    #[test]
+    fn recently_picked_entries_get_the_larger_boost() {
+        let mut history = History::default();
+
+        history.record("save_file");
+        history.record("open_file");
+
+        assert!(history.boost("open_file") > history.boost("save_file"));
+        assert!(history.boost("save_file") > 0);
+        assert_eq!(history.boost("copy"), 0);
+
+        history.record("save_file");
+
+        assert_eq!(history.rank("save_file"), Some(0));
+        assert_eq!(history.rank("open_file"), Some(1));
+    }
+
+    #[test]
+    fn history_is_capped() {
+        let mut history = History::default();
+
+        for index in 0..=MAX_ENTRIES {
+            history.record(index.to_string());
+        }
+
+        assert_eq!(history.rank("0"), None);
+        assert_eq!(history.boost(&MAX_ENTRIES.to_string()), 100);
+        assert_eq!(history.boost("1"), 1);
+    }
+
+    #[test]
+    fn boost_lifts_a_recent_entry_over_a_closer_match() {
+        let mut history = History::default();
+        history.record("go_to_folder");
+
+        let rank =
+            |key: &str, label: &str| fuzzy_match("of", label).unwrap().score + history.boost(key);
+
+        assert!(rank("go_to_folder", "Go to Folder") > rank("open_file", "Open File"));
+    }
+
+    #[test]
+    fn parses_lines_and_columns() {
+        assert_eq!(parse_line("12"), Some(Position::new(11, 0)));
+        assert_eq!(parse_line(" 3:4 "), Some(Position::new(2, 3)));
+        assert_eq!(parse_line("3,"), Some(Position::new(2, 0)));
+        assert_eq!(parse_line("0"), None);
+        assert_eq!(parse_line("x"), None);
+    }
+}
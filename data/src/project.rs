@@ -0,0 +1,69 @@
+//! The files of the project being edited.
+
+use std::collections::VecDeque;
+use std::path::{Path, PathBuf};
+
+use futures::{Stream, stream};
+use tokio::fs;
+
+const MAX_FILES: usize = 20_000;
+const IGNORED: &[&str] = &["target", "node_modules"];
+
+/// Walks `root` breadth first, yielding the files found in one directory at a
+/// time.
+///
+/// Hidden entries and build output are skipped, and the walk stops after
+/// [`MAX_FILES`] files.
+pub fn files(root: PathBuf) -> impl Stream<Item = Vec<PathBuf>> {
+    stream::unfold(
+        (VecDeque::from([root]), 0),
+        |(mut pending, mut count)| async move {
+            while count < MAX_FILES {
+                let directory = pending.pop_front()?;
+
+                let Ok(mut entries) = fs::read_dir(&directory).await else {
+                    continue;
+                };
+
+                let mut files = vec![];
+
+                while let Ok(Some(entry)) = entries.next_entry().await {
+                    let path = entry.path();
+
+                    if is_ignored(&path) {
+                        continue;
+                    }
+
+                    match entry.file_type().await {
+                        Ok(file_type) if file_type.is_dir() => pending.push_back(path),
+                        Ok(file_type) if file_type.is_file() => files.push(path),
+                        _ => {}
+                    }
+                }
+
+                if !files.is_empty() {
+                    files.sort();
+                    files.truncate(MAX_FILES - count);
+                    count += files.len();
+
+                    return Some((files, (pending, count)));
+                }
+            }
+
+            None
+        },
+    )
+}
+
+/// The name of the project rooted at `root`, the name of its directory.
+pub fn name(root: &Path) -> String {
+    root.file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default()
+}
+
+fn is_ignored(path: &Path) -> bool {
+    path.file_name()
+        .and_then(|name| name.to_str())
+        .is_none_or(|name| name.starts_with('.') || IGNORED.contains(&name))
+}
@@ -0,0 +1,106 @@
+use std::path::{Path, PathBuf};
+use std::{io, sync::Arc};
+
+use serde::{Deserialize, Serialize};
+use tokio::fs;
+
+use crate::environment;
+use crate::fold::Region;
+
+const MAX_FILES: usize = 100;
+
+/// What is remembered of the files edited in earlier runs, the most
+/// recently changed first.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Session {
+    #[serde(default)]
+    files: Vec<File>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct File {
+    path: PathBuf,
+    #[serde(default)]
+    folds: Vec<Region>,
+}
+
+impl Session {
+    /// # Errors
+    ///
+    /// Will return `Error` if serde is unable to deserialize the data, or could
+    /// return `Error` due to IO failure.
+    pub async fn load() -> Result<Session, Error> {
+        let path = path()?;
+        let bytes = fs::read(path).await?;
+
+        Ok(serde_json::from_slice(&bytes)?)
+    }
+
+    /// # Errors
+    ///
+    /// Will return `Error` if serde is unable to serialize the data, or could
+    /// return `Error` due to IO failure.
+    pub async fn save(self) -> Result<(), Error> {
+        let path = path()?;
+
+        let bytes = serde_json::to_vec(&self)?;
+        fs::write(path, &bytes).await?;
+
+        Ok(())
+    }
+
+    /// The regions folded in the file at `path`.
+    pub fn folds(&self, path: &Path) -> &[Region] {
+        self.files
+            .iter()
+            .find(|file| file.path == path)
+            .map_or(&[], |file| file.folds.as_slice())
+    }
+
+    /// Remembers the regions folded in the file at `path`, forgetting the
+    /// file once nothing is folded in it anymore.
+    pub fn set_folds(&mut self, path: &Path, folds: Vec<Region>) {
+        self.files.retain(|file| file.path != path);
+
+        if !folds.is_empty() {
+            self.files.insert(
+                0,
+                File {
+                    path: path.to_path_buf(),
+                    folds,
+                },
+            );
+            self.files.truncate(MAX_FILES);
+        }
+    }
+}
+
+fn path() -> Result<PathBuf, Error> {
+    let parent = environment::data_dir();
+
+    if !parent.exists() {
+        std::fs::create_dir_all(&parent)?;
+    }
+
+    Ok(parent.join("session.json"))
+}
+
+#[derive(Debug, Clone, thiserror::Error)]
+pub enum Error {
+    #[error(transparent)]
+    Serde(Arc<serde_json::Error>),
+    #[error(transparent)]
+    Io(Arc<io::Error>),
+}
+
+impl From<serde_json::Error> for Error {
+    fn from(error: serde_json::Error) -> Self {
+        Self::Serde(Arc::new(error))
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(error: io::Error) -> Self {
+        Self::Io(Arc::new(error))
+    }
+}
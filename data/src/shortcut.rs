@@ -5,32 +5,103 @@ use std::{fmt, ops};
 use iced_core::keyboard::{self, key};
 use serde::Deserialize;
 
-pub fn shortcut(key_bind: KeyBind, command: Command) -> Shortcut {
-    Shortcut { key_bind, command }
+pub use crate::command::Command;
+use crate::context::When;
+
+pub fn shortcut(sequence: KeySequence, when: When, command: Command) -> Shortcut {
+    Shortcut {
+        sequence,
+        when,
+        command,
+    }
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Shortcut {
-    key_bind: KeyBind,
+    sequence: KeySequence,
+    when: When,
     command: Command,
 }
 
 impl Shortcut {
-    pub fn execute(&self, key_bind: &KeyBind) -> Option<Command> {
-        (self.key_bind == *key_bind).then_some(self.command)
+    /// The command, if `keys` is exactly its sequence.
+    pub fn execute(&self, keys: &[KeyBind]) -> Option<Command> {
+        (self.sequence.0 == keys).then_some(self.command)
+    }
+
+    /// Whether `keys` are the start of the sequence, without being all of it.
+    pub fn is_continued_by(&self, keys: &[KeyBind]) -> bool {
+        self.sequence.0.len() > keys.len() && self.sequence.0.starts_with(keys)
+    }
+
+    pub fn sequence(&self) -> &KeySequence {
+        &self.sequence
+    }
+
+    pub fn when(&self) -> &When {
+        &self.when
+    }
+
+    pub fn command(&self) -> Command {
+        self.command
+    }
+}
+
+/// One or more [`KeyBind`]s pressed one after the other, written as
+/// `ctrl+k ctrl+c` in the config.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct KeySequence(Vec<KeyBind>);
+
+impl KeySequence {
+    pub fn keys(&self) -> &[KeyBind] {
+        &self.0
+    }
+
+    pub fn is_chord(&self) -> bool {
+        self.0.len() > 1
+    }
+
+    /// Whether `self` is a strict prefix of `other`.
+    pub fn is_prefix_of(&self, other: &KeySequence) -> bool {
+        other.0.len() > self.0.len() && other.0.starts_with(&self.0)
+    }
+}
+
+impl From<KeyBind> for KeySequence {
+    fn from(key_bind: KeyBind) -> Self {
+        Self(vec![key_bind])
+    }
+}
+
+impl From<Vec<KeyBind>> for KeySequence {
+    fn from(keys: Vec<KeyBind>) -> Self {
+        Self(keys)
+    }
+}
+
+impl fmt::Display for KeySequence {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let keys = self.0.iter().map(ToString::to_string).collect::<Vec<_>>();
+
+        write!(f, "{}", keys.join(", "))
     }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
-pub enum Command {
-    MoveUp,
-    MoveDown,
-    MoveLeft,
-    MoveRight,
-    ToggleSidebar,
-    ToggleFullscreen,
-    ThemeEditor,
-    QuitApplication,
+impl FromStr for KeySequence {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let keys = s
+            .split_whitespace()
+            .map(str::parse)
+            .collect::<Result<Vec<KeyBind>, _>>()?;
+
+        if keys.is_empty() {
+            return Err(ParseError::Empty);
+        }
+
+        Ok(Self(keys))
+    }
 }
 
 macro_rules! default {
@@ -109,6 +180,117 @@ impl KeyBind {
     #[cfg(not(target_os = "macos"))]
     default!(toggle_fullscreen, F11);
     default!(theme_editor, "t", COMMAND);
+    default!(new_file, "n", COMMAND);
+    default!(open_file, "o", COMMAND);
+    default!(save_file, "s", COMMAND);
+    default!(undo, "z", COMMAND);
+    default!(redo, "z", COMMAND | SHIFT);
+    default!(copy, "c", COMMAND);
+    default!(cut, "x", COMMAND);
+    default!(paste, "v", COMMAND);
+    default!(select_all, "a", COMMAND);
+    default!(next_tab, Tab, CTRL);
+    default!(previous_tab, Tab, CTRL | SHIFT);
+    default!(move_tab_left, PageUp, CTRL | SHIFT);
+    default!(move_tab_right, PageDown, CTRL | SHIFT);
+    default!(close_tab, "w", COMMAND);
+    default!(reopen_closed_tab, "t", COMMAND | SHIFT);
+    default!(command_palette, "p", COMMAND | SHIFT);
+    default!(go_to_file, "p", COMMAND);
+    default!(go_to_line, "g", COMMAND);
+    default!(go_to_symbol, "o", COMMAND | SHIFT);
+    default!(unfocus_editor, Escape);
+    default!(expand_selection, ArrowRight, ALT | SHIFT);
+    default!(shrink_selection, ArrowLeft, ALT | SHIFT);
+    default!(add_cursor_above, ArrowUp, ALT | SHIFT);
+    default!(add_cursor_below, ArrowDown, ALT | SHIFT);
+    default!(add_next_occurrence, "d", COMMAND);
+    default!(select_all_occurrences, "l", COMMAND | SHIFT);
+    default!(column_select_up, ArrowUp, COMMAND | ALT | SHIFT);
+    default!(column_select_down, ArrowDown, COMMAND | ALT | SHIFT);
+    default!(column_select_left, ArrowLeft, COMMAND | ALT | SHIFT);
+    default!(column_select_right, ArrowRight, COMMAND | ALT | SHIFT);
+    default!(close_modal, Escape);
+
+    /// The keys bound to `command` unless the config says otherwise.
+    pub fn defaults(command: Command) -> Vec<KeyBind> {
+        let key_bind = match command {
+            Command::NewFile => KeyBind::new_file(),
+            Command::OpenFile => KeyBind::open_file(),
+            Command::SaveFile => KeyBind::save_file(),
+            Command::Undo => KeyBind::undo(),
+            Command::Redo => KeyBind::redo(),
+            Command::Copy => KeyBind::copy(),
+            Command::Cut => KeyBind::cut(),
+            Command::Paste => KeyBind::paste(),
+            Command::SelectAll => KeyBind::select_all(),
+            Command::UnfocusEditor => KeyBind::unfocus_editor(),
+            Command::ExpandSelection => KeyBind::expand_selection(),
+            Command::ShrinkSelection => KeyBind::shrink_selection(),
+            Command::AddCursorAbove => KeyBind::add_cursor_above(),
+            Command::AddCursorBelow => KeyBind::add_cursor_below(),
+            Command::AddNextOccurrence => KeyBind::add_next_occurrence(),
+            Command::SelectAllOccurrences => KeyBind::select_all_occurrences(),
+            Command::ColumnSelectUp => KeyBind::column_select_up(),
+            Command::ColumnSelectDown => KeyBind::column_select_down(),
+            Command::ColumnSelectLeft => KeyBind::column_select_left(),
+            Command::ColumnSelectRight => KeyBind::column_select_right(),
+            Command::NextTab => KeyBind::next_tab(),
+            Command::PreviousTab => KeyBind::previous_tab(),
+            Command::MoveTabLeft => KeyBind::move_tab_left(),
+            Command::MoveTabRight => KeyBind::move_tab_right(),
+            Command::CloseTab => KeyBind::close_tab(),
+            Command::ReopenClosedTab => KeyBind::reopen_closed_tab(),
+            Command::MoveUp => KeyBind::move_up(),
+            Command::MoveDown => KeyBind::move_down(),
+            Command::MoveLeft => KeyBind::move_left(),
+            Command::MoveRight => KeyBind::move_right(),
+            Command::CommandPalette => KeyBind::command_palette(),
+            Command::GoToFile => KeyBind::go_to_file(),
+            Command::GoToLine => KeyBind::go_to_line(),
+            Command::GoToSymbol => KeyBind::go_to_symbol(),
+            Command::ToggleSidebar => KeyBind::toggle_sidebar(),
+            Command::CloseModal => KeyBind::close_modal(),
+            Command::ThemeEditor => KeyBind::theme_editor(),
+            Command::ToggleFullscreen => KeyBind::toggle_fullscreen(),
+            Command::SplitHorizontal
+            | Command::SplitVertical
+            | Command::ClosePane
+            | Command::ToggleMaximizePane
+            | Command::ToggleWordWrap
+            | Command::ClearCursors
+            | Command::Earlier
+            | Command::Later
+            | Command::UndoHistory
+            | Command::ToggleFold
+            | Command::FoldAll
+            | Command::UnfoldAll
+            | Command::ChangeLanguage
+            | Command::ReopenWithEncoding
+            | Command::ConvertToLf
+            | Command::ConvertToCrlf
+            | Command::SaveWithEncoding
+            | Command::QuitApplication => return vec![],
+        };
+
+        vec![key_bind]
+    }
+
+    /// Whether the key is a modifier on its own, which never completes a
+    /// binding.
+    pub fn is_modifier(&self) -> bool {
+        matches!(
+            self.key_code.0,
+            keyboard::Key::Named(
+                key::Named::Shift
+                    | key::Named::Control
+                    | key::Named::Alt
+                    | key::Named::AltGraph
+                    | key::Named::Super
+                    | key::Named::Meta
+            )
+        )
+    }
 
     pub fn is_pressed(
         &self,
@@ -128,34 +310,23 @@ impl From<(keyboard::Key, keyboard::Modifiers)> for KeyBind {
     }
 }
 
-impl<'de> Deserialize<'de> for KeyBind {
-    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
-    where
-        D: serde::Deserializer<'de>,
-    {
-        use serde::de;
-
-        let string = String::deserialize(deserializer)?;
+impl FromStr for KeyBind {
+    type Err = ParseError;
 
-        let parts = string.trim().split('+').collect::<Vec<_>>();
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parts = s.trim().split('+').collect::<Vec<_>>();
 
         let (key_code, modifiers) = match parts.len() {
-            0 => return Err(de::Error::custom("empty keybind")),
-            1 => (
-                parts[0].parse::<KeyCode>().map_err(de::Error::custom)?,
-                Modifiers::default(),
-            ),
+            0 => return Err(ParseError::Empty),
+            1 => (parts[0].parse::<KeyCode>()?, Modifiers::default()),
             _ => {
                 let modifiers = parts[..parts.len() - 1]
                     .iter()
                     .map(|s| s.parse::<Modifiers>())
-                    .collect::<Result<Vec<_>, ParseError>>()
-                    .map_err(de::Error::custom)?
+                    .collect::<Result<Vec<_>, ParseError>>()?
                     .into_iter()
                     .fold(Modifiers::default(), ops::BitOr::bitor);
-                let key_code = parts[parts.len() - 1]
-                    .parse::<KeyCode>()
-                    .map_err(de::Error::custom)?;
+                let key_code = parts[parts.len() - 1].parse::<KeyCode>()?;
                 (key_code, modifiers)
             }
         };
@@ -167,6 +338,19 @@ impl<'de> Deserialize<'de> for KeyBind {
     }
 }
 
+impl<'de> Deserialize<'de> for KeyBind {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        use serde::de;
+
+        let string = String::deserialize(deserializer)?;
+
+        string.parse().map_err(de::Error::custom)
+    }
+}
+
 #[derive(Debug, Hash, Ord, PartialOrd, PartialEq, Eq, Clone)]
 pub struct KeyCode(keyboard::Key);
 
@@ -385,8 +569,65 @@ impl FromStr for Modifiers {
 
 #[derive(Debug, thiserror::Error)]
 pub enum ParseError {
+    #[error("empty keybind")]
+    Empty,
     #[error("invalid keycode: {0}")]
     InvalidKeyCode(String),
     #[error("invalid modifier: {0}")]
     InvalidModifier(String),
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_a_single_key_bind() {
+        let sequence = "ctrl+shift+p".parse::<KeySequence>().unwrap();
+
+        assert!(!sequence.is_chord());
+        assert_eq!(
+            sequence.keys(),
+            [KeyBind {
+                key_code: KeyCode(keyboard::Key::Character("p".into())),
+                modifiers: CTRL | SHIFT,
+            }]
+        );
+    }
+
+    #[test]
+    fn parses_a_chord() {
+        let sequence = "ctrl+k  ctrl+c".parse::<KeySequence>().unwrap();
+
+        assert!(sequence.is_chord());
+        assert_eq!(sequence.keys().len(), 2);
+        assert!(
+            "ctrl+k"
+                .parse::<KeySequence>()
+                .unwrap()
+                .is_prefix_of(&sequence)
+        );
+        assert!(!sequence.is_prefix_of(&sequence));
+    }
+
+    #[test]
+    fn characters_compare_ignoring_case() {
+        assert_eq!(
+            "ctrl+K".parse::<KeySequence>().unwrap(),
+            "ctrl+k".parse::<KeySequence>().unwrap()
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_sequences() {
+        assert!(matches!("".parse::<KeySequence>(), Err(ParseError::Empty)));
+        assert!(matches!(
+            "hyper+k".parse::<KeySequence>(),
+            Err(ParseError::InvalidModifier(_))
+        ));
+        assert!(matches!(
+            "ctrl+nope".parse::<KeySequence>(),
+            Err(ParseError::InvalidKeyCode(_))
+        ));
+    }
+}
@@ -0,0 +1,159 @@
+//! A rough outline of the definitions in a buffer, found line by line.
+
+use std::fmt;
+
+use crate::Buffer;
+use crate::buffer::Position;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Kind {
+    Function,
+    Type,
+    Module,
+    Constant,
+    Implementation,
+    Macro,
+    Section,
+    Heading,
+}
+
+impl fmt::Display for Kind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let kind = match self {
+            Kind::Function => "function",
+            Kind::Type => "type",
+            Kind::Module => "module",
+            Kind::Constant => "constant",
+            Kind::Implementation => "impl",
+            Kind::Macro => "macro",
+            Kind::Section => "section",
+            Kind::Heading => "heading",
+        };
+
+        write!(f, "{kind}")
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Symbol {
+    pub name: String,
+    pub kind: Kind,
+    /// Where the name of the symbol starts.
+    pub position: Position,
+}
+
+/// Lists the symbols of `buffer`, using the rules for files with the given
+/// `extension`.
+pub fn scan(buffer: &Buffer, extension: Option<&str>) -> Vec<Symbol> {
+    let rule = match extension {
+        Some("rs") => rust,
+        Some("toml") => toml,
+        Some("md" | "markdown") => markdown,
+        _ => generic,
+    };
+
+    (0..buffer.len_lines())
+        .filter_map(|index| {
+            let line = buffer.line_text(index)?;
+            let (name, kind) = rule(&line)?;
+            let column = line.find(name.as_str()).unwrap_or_default();
+
+            Some(Symbol {
+                name,
+                kind,
+                position: Position::new(index, column),
+            })
+        })
+        .collect()
+}
+
+fn rust(line: &str) -> Option<(String, Kind)> {
+    let mut rest = line.trim_start();
+
+    if let Some(visibility) = rest.strip_prefix("pub") {
+        rest = match visibility.strip_prefix('(') {
+            Some(scoped) => scoped.split_once(')')?.1,
+            None => visibility,
+        }
+        .trim_start();
+    }
+
+    loop {
+        let (word, after) = rest.split_once(char::is_whitespace)?;
+        let after = after.trim_start();
+
+        match word {
+            "default" | "async" | "unsafe" | "extern" => rest = after,
+            "const" if after.starts_with("fn ") => rest = after,
+            // The ABI of an `extern` function.
+            _ if word.starts_with('"') => rest = after,
+            _ => break,
+        }
+    }
+
+    let (keyword, rest) = rest.split_once(char::is_whitespace)?;
+
+    let kind = match keyword {
+        "fn" => Kind::Function,
+        "struct" | "enum" | "union" | "trait" | "type" => Kind::Type,
+        "mod" => Kind::Module,
+        "const" | "static" => Kind::Constant,
+        "impl" => Kind::Implementation,
+        "macro_rules!" => Kind::Macro,
+        _ if keyword.starts_with("impl<") => Kind::Implementation,
+        _ => return None,
+    };
+
+    let name = if kind == Kind::Implementation {
+        let header = rest.split(['{', ';']).next()?;
+        let header = header.split(" where").next()?.trim();
+
+        header.to_string()
+    } else {
+        identifier(rest.trim_start().trim_start_matches("mut "))?
+    };
+
+    (!name.is_empty()).then_some((name, kind))
+}
+
+fn toml(line: &str) -> Option<(String, Kind)> {
+    let line = line.trim();
+    let name = line
+        .strip_prefix("[[")
+        .and_then(|table| table.strip_suffix("]]"))
+        .or_else(|| line.strip_prefix('[')?.strip_suffix(']'))?;
+
+    Some((name.trim().to_string(), Kind::Section))
+}
+
+fn markdown(line: &str) -> Option<(String, Kind)> {
+    let heading = line.trim_start_matches('#');
+
+    if heading.len() == line.len() || !heading.starts_with(' ') {
+        return None;
+    }
+
+    Some((heading.trim().to_string(), Kind::Heading))
+}
+
+fn generic(line: &str) -> Option<(String, Kind)> {
+    let line = line.trim_start();
+    let (keyword, rest) = line.split_once(char::is_whitespace)?;
+
+    let kind = match keyword {
+        "fn" | "def" | "func" | "function" => Kind::Function,
+        "class" | "struct" | "interface" => Kind::Type,
+        _ => return None,
+    };
+
+    Some((identifier(rest.trim_start())?, kind))
+}
+
+fn identifier(text: &str) -> Option<String> {
+    let identifier = text
+        .chars()
+        .take_while(|c| c.is_alphanumeric() || *c == '_')
+        .collect::<String>();
+
+    (!identifier.is_empty()).then_some(identifier)
+}
@@ -0,0 +1,687 @@
+//! Incremental tree-sitter parsing of the buffers in languages with a
+//! grammar.
+//!
+//! A [`Syntax`] is kept next to every buffer. Edits are reported to it before
+//! they are applied to the buffer, so the next parse only redoes the parts of
+//! the tree they touched.
+
+use std::collections::VecDeque;
+use std::ops::Range;
+use std::sync::{Arc, LazyLock, PoisonError, RwLock};
+use std::{fmt, iter};
+
+use ropey::str_utils;
+use streaming_iterator::StreamingIterator;
+use tree_sitter::{
+    InputEdit, LanguageError, Node, Parser, Point, Query, QueryCursor, QueryError, Tree,
+};
+
+use crate::buffer::{Buffer, Edit};
+use crate::fold::Region;
+use crate::language::Language;
+
+/// How many of the latest changes to [`Highlights`] remember the lines they
+/// touched.
+const CHANGES: usize = 64;
+
+/// A language tree-sitter can parse, with the queries run on its trees.
+struct Grammar {
+    language: tree_sitter::Language,
+    highlights: Query,
+    indents: Query,
+    folds: Query,
+}
+
+impl Grammar {
+    fn new(
+        id: &'static str,
+        language: tree_sitter::Language,
+        highlights: &str,
+        indents: &str,
+        folds: &str,
+    ) -> Result<Self, Error> {
+        let query = |name, source| {
+            Query::new(&language, source).map_err(|error| Error::Query {
+                language: id,
+                name,
+                error: Arc::new(error),
+            })
+        };
+
+        Ok(Self {
+            highlights: query("highlights", highlights)?,
+            indents: query("indents", indents)?,
+            folds: query("folds", folds)?,
+            language,
+        })
+    }
+}
+
+static RUST: LazyLock<Result<Grammar, Error>> = LazyLock::new(|| {
+    Grammar::new(
+        "rust",
+        tree_sitter_rust::LANGUAGE.into(),
+        tree_sitter_rust::HIGHLIGHTS_QUERY,
+        include_str!("../../assets/queries/rust/indents.scm"),
+        include_str!("../../assets/queries/rust/folds.scm"),
+    )
+});
+
+static TOML: LazyLock<Result<Grammar, Error>> = LazyLock::new(|| {
+    Grammar::new(
+        "toml",
+        tree_sitter_toml_ng::LANGUAGE.into(),
+        tree_sitter_toml_ng::HIGHLIGHTS_QUERY,
+        include_str!("../../assets/queries/toml/indents.scm"),
+        include_str!("../../assets/queries/toml/folds.scm"),
+    )
+});
+
+fn grammar(language: &Language) -> Option<&'static Result<Grammar, Error>> {
+    match language.id {
+        "rust" => Some(&RUST),
+        "toml" => Some(&TOML),
+        _ => None,
+    }
+}
+
+/// What a highlighted span of text is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Kind {
+    Comment,
+    Keyword,
+    String,
+    Number,
+    Constant,
+    Function,
+    Type,
+    Property,
+    Attribute,
+    Tag,
+    Operator,
+    Punctuation,
+}
+
+/// The parse tree of a buffer.
+pub struct Syntax {
+    language: Language,
+    grammar: &'static Grammar,
+    parser: Parser,
+    tree: Option<Tree>,
+    highlights: Arc<RwLock<Highlights>>,
+    /// The lines edited since the tree was last updated, highlighted again
+    /// along with the ones whose syntax changed.
+    edited: Option<Range<usize>>,
+}
+
+impl fmt::Debug for Syntax {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Syntax")
+            .field("language", &self.language.id)
+            .field("is_parsed", &self.tree.is_some())
+            .finish()
+    }
+}
+
+impl Syntax {
+    /// Parses `buffer` as `language`, if tree-sitter has a grammar for it.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Error` if the grammar or one of its queries can't be
+    /// loaded.
+    pub fn new(language: Language, buffer: &Buffer) -> Result<Option<Self>, Error> {
+        let Some(grammar) = grammar(&language) else {
+            return Ok(None);
+        };
+        let grammar = grammar.as_ref().map_err(Clone::clone)?;
+
+        let mut parser = Parser::new();
+        parser
+            .set_language(&grammar.language)
+            .map_err(|error| Error::Language(Arc::new(error)))?;
+
+        let mut syntax = Self {
+            language,
+            grammar,
+            parser,
+            tree: None,
+            highlights: Arc::default(),
+            edited: None,
+        };
+
+        syntax.update(buffer);
+
+        Ok(Some(syntax))
+    }
+
+    pub fn language(&self) -> Language {
+        self.language
+    }
+
+    /// Tells the tree about `edit`, before it is applied to `buffer`.
+    pub fn edit(&mut self, buffer: &Buffer, edit: &Edit) {
+        let Some(tree) = &mut self.tree else {
+            return;
+        };
+
+        let start_byte = buffer.char_to_byte(edit.range.start);
+        let old_end_byte = buffer.char_to_byte(edit.range.end.max(edit.range.start));
+        let start_position = point(buffer, edit.range.start);
+
+        // Lines are split like the rope splits them, `\r` included.
+        let new_end_position = match str_utils::byte_to_line_idx(&edit.text, edit.text.len()) {
+            0 => Point::new(start_position.row, start_position.column + edit.text.len()),
+            rows => Point::new(
+                start_position.row + rows,
+                edit.text.len() - str_utils::line_to_byte_idx(&edit.text, rows),
+            ),
+        };
+
+        let old_end_position = point(buffer, edit.range.end);
+
+        tree.edit(&InputEdit {
+            start_byte,
+            old_end_byte,
+            new_end_byte: start_byte + edit.text.len(),
+            start_position,
+            old_end_position,
+            new_end_position,
+        });
+
+        let (start, old_end, new_end) = (
+            start_position.row,
+            old_end_position.row,
+            new_end_position.row,
+        );
+
+        self.highlights
+            .write()
+            .unwrap_or_else(PoisonError::into_inner)
+            .splice(start..old_end + 1, new_end + 1 - start);
+
+        // The lines edited before move along with the lines below the edit.
+        let shift = |line: usize| {
+            if line > old_end {
+                line + new_end - old_end
+            } else {
+                line.min(start)
+            }
+        };
+
+        self.edited = Some(match self.edited.take() {
+            Some(edited) => shift(edited.start).min(start)..shift(edited.end).max(new_end + 1),
+            None => start..new_end + 1,
+        });
+    }
+
+    /// Forgets the tree, for changes to the buffer that were not reported
+    /// through [`Syntax::edit`].
+    pub fn reset(&mut self) {
+        self.tree = None;
+        self.edited = None;
+    }
+
+    /// Parses `buffer` again, reusing the parts of the old tree that were
+    /// not edited.
+    ///
+    /// Only the lines edited since the last update and the ones whose syntax
+    /// changed are highlighted again.
+    pub fn update(&mut self, buffer: &Buffer) {
+        let old = self.tree.take();
+
+        self.tree = self.parser.parse_with_options(
+            &mut |byte, _| buffer.chunk_at_byte(byte),
+            old.as_ref(),
+            None,
+        );
+
+        let edited = self.edited.take();
+        let lines = match (&old, &self.tree) {
+            (Some(old), Some(new)) => old
+                .changed_ranges(new)
+                .map(|range| range.start_point.row..range.end_point.row + 1)
+                .chain(edited)
+                .collect(),
+            _ => iter::once(0..buffer.len_lines()).collect(),
+        };
+
+        self.highlight(buffer, lines);
+    }
+
+    pub fn root(&self) -> Option<Node<'_>> {
+        self.tree.as_ref().map(Tree::root_node)
+    }
+
+    /// The smallest named node spanning the byte `range`.
+    pub fn node_at(&self, range: Range<usize>) -> Option<Node<'_>> {
+        self.root()?
+            .named_descendant_for_byte_range(range.start, range.end)
+    }
+
+    /// The range of the smallest named node around the byte `range`, like the
+    /// expression around an identifier or the block around a statement.
+    pub fn expand(&self, range: Range<usize>) -> Option<Range<usize>> {
+        let mut node = self.node_at(range.clone())?;
+
+        while node.byte_range() == range {
+            node = node.parent()?;
+        }
+
+        Some(node.byte_range())
+    }
+
+    /// The range of the first named node inside the byte `range`, undoing
+    /// [`Syntax::expand`] when there is no history to go back to.
+    pub fn shrink(&self, range: Range<usize>) -> Option<Range<usize>> {
+        let node = self.node_at(range.clone())?;
+        let mut cursor = node.walk();
+
+        node.named_children(&mut cursor)
+            .map(|child| child.byte_range())
+            .find(|child| range.start <= child.start && child.end <= range.end && *child != range)
+    }
+
+    /// The highlights of the buffer, kept up to date with the tree.
+    pub fn highlights(&self) -> &Arc<RwLock<Highlights>> {
+        &self.highlights
+    }
+
+    /// Highlights the `lines` of `buffer` again, which must be the buffer the
+    /// tree was last updated from.
+    fn highlight(&self, buffer: &Buffer, mut lines: Vec<Range<usize>>) {
+        let mut highlights = self
+            .highlights
+            .write()
+            .unwrap_or_else(PoisonError::into_inner);
+
+        highlights.lines.resize_with(buffer.len_lines(), Vec::new);
+
+        let Some(root) = self.root() else {
+            highlights.lines.iter_mut().for_each(Vec::clear);
+            highlights.commit(0);
+
+            return;
+        };
+
+        lines.sort_by_key(|lines| lines.start);
+
+        let Some(first) = lines.first().map(|lines| lines.start) else {
+            return;
+        };
+
+        let query = &self.grammar.highlights;
+        let names = query.capture_names();
+        let mut cursor = QueryCursor::new();
+        let mut done = 0;
+
+        for lines in lines {
+            let lines = lines.start.max(done)..lines.end.min(buffer.len_lines());
+
+            if lines.is_empty() {
+                continue;
+            }
+
+            done = lines.end;
+
+            for spans in &mut highlights.lines[lines.clone()] {
+                spans.clear();
+            }
+
+            cursor.set_byte_range(buffer.line_to_byte(lines.start)..buffer.line_to_byte(lines.end));
+
+            let mut captures = cursor.captures(query, root, |node: Node<'_>| {
+                buffer.chunks_in(node.byte_range())
+            });
+
+            // Captures come in document order, a node captured by several
+            // patterns keeps the first one and nested captures lose to the
+            // node around them.
+            let mut highlighted = 0;
+
+            while let Some((query_match, index)) = captures.next() {
+                let capture = query_match.captures[*index];
+                let node = capture.node;
+
+                if node.start_byte() < highlighted || node.start_byte() == node.end_byte() {
+                    continue;
+                }
+
+                let Some(kind) = kind(names[capture.index as usize]) else {
+                    continue;
+                };
+
+                highlighted = node.end_byte();
+
+                let (start, end) = (node.start_position(), node.end_position());
+
+                for row in start.row.max(lines.start)..=end.row.min(lines.end - 1) {
+                    let from = if row == start.row { start.column } else { 0 };
+                    let to = if row == end.row {
+                        end.column
+                    } else {
+                        buffer.line_len(row).unwrap_or_default()
+                    };
+
+                    if from < to {
+                        highlights.lines[row].push((from..to, kind));
+                    }
+                }
+            }
+        }
+
+        highlights.commit(first);
+    }
+
+    /// How many levels `line` is indented by the nodes around it, like the
+    /// blocks and argument lists it sits in.
+    ///
+    /// A line starting with a closing bracket is indented one level less
+    /// than its contents.
+    pub fn indent_level(&self, buffer: &Buffer, line: usize) -> usize {
+        let Some(root) = self.root() else {
+            return 0;
+        };
+
+        let query = &self.grammar.indents;
+        let names = query.capture_names();
+
+        let line_start = buffer.line_to_byte(line);
+        let first_token = line_start
+            + buffer
+                .line_text(line)
+                .map_or(0, |text| text.len() - text.trim_start().len());
+
+        let mut cursor = QueryCursor::new();
+        cursor.set_byte_range(line_start..first_token + 1);
+
+        let mut captures = cursor.captures(query, root, |node: Node<'_>| {
+            buffer.chunks_in(node.byte_range())
+        });
+        let mut rows = vec![];
+        let mut outdent = false;
+
+        while let Some((query_match, index)) = captures.next() {
+            let capture = query_match.captures[*index];
+            let node = capture.node;
+
+            match names[capture.index as usize] {
+                "indent" => {
+                    let (start, end) = (node.start_position(), node.end_position());
+
+                    // Several nodes opened on one line only indent once.
+                    if start.row < line && end.row >= line && !rows.contains(&start.row) {
+                        rows.push(start.row);
+                    }
+                }
+                "outdent" => outdent = outdent || node.start_byte() == first_token,
+                _ => {}
+            }
+        }
+
+        rows.len().saturating_sub(usize::from(outdent))
+    }
+
+    /// The regions spanned by the nodes captured by the folds query, over
+    /// more than one line.
+    ///
+    /// A closing bracket on the last line of a node is left out of its
+    /// region, so it stays visible when the node is folded.
+    pub fn fold_regions(&self, buffer: &Buffer) -> Vec<Region> {
+        let Some(root) = self.root() else {
+            return Vec::new();
+        };
+
+        let mut cursor = QueryCursor::new();
+        let mut matches = cursor.matches(&self.grammar.folds, root, |node: Node<'_>| {
+            buffer.chunks_in(node.byte_range())
+        });
+        let mut regions = Vec::new();
+
+        while let Some(query_match) = matches.next() {
+            for capture in query_match.captures {
+                let node = capture.node;
+                let (start, end) = (node.start_position().row, node.end_position().row);
+
+                let closes = node
+                    .child(node.child_count().saturating_sub(1))
+                    .is_some_and(|last| !last.is_named() && last.start_position().row == end);
+                let end = if closes { end.saturating_sub(1) } else { end };
+
+                if end > start {
+                    regions.push(Region { start, end });
+                }
+            }
+        }
+
+        regions
+    }
+}
+
+/// The highlighted spans of every line of a buffer, as byte ranges into the
+/// line.
+///
+/// They are patched in place as the buffer is edited, every change making a
+/// new revision.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Highlights {
+    lines: Vec<Vec<(Range<usize>, Kind)>>,
+    revision: u64,
+    /// The revision made by each of the latest changes and the first line it
+    /// touched, oldest first.
+    changes: VecDeque<(u64, usize)>,
+}
+
+impl Highlights {
+    pub fn line(&self, index: usize) -> &[(Range<usize>, Kind)] {
+        self.lines.get(index).map_or(&[], Vec::as_slice)
+    }
+
+    pub fn revision(&self) -> u64 {
+        self.revision
+    }
+
+    /// The first line changed since `revision`, or `None` if none was.
+    pub fn changed_since(&self, revision: u64) -> Option<usize> {
+        if revision >= self.revision {
+            return None;
+        }
+
+        match self.changes.front() {
+            // The changes right after `revision` are remembered.
+            Some((oldest, _)) if *oldest <= revision + 1 => self
+                .changes
+                .iter()
+                .filter(|(change, _)| *change > revision)
+                .map(|(_, line)| *line)
+                .min(),
+            _ => Some(0),
+        }
+    }
+
+    /// Replaces the highlights of the `lines` replaced by an edit with the
+    /// `len` lines it inserted, left unhighlighted.
+    fn splice(&mut self, lines: Range<usize>, len: usize) {
+        let end = lines.end.min(self.lines.len());
+        let start = lines.start.min(end);
+
+        self.lines
+            .splice(start..end, iter::repeat_with(Vec::new).take(len));
+    }
+
+    /// Makes a new revision, changed from `line` on.
+    fn commit(&mut self, line: usize) {
+        self.revision += 1;
+
+        if self.changes.len() == CHANGES {
+            self.changes.pop_front();
+        }
+
+        self.changes.push_back((self.revision, line));
+    }
+}
+
+#[derive(Debug, Clone, thiserror::Error)]
+pub enum Error {
+    #[error("the {name} query of {language} is invalid: {error}")]
+    Query {
+        language: &'static str,
+        name: &'static str,
+        error: Arc<QueryError>,
+    },
+    #[error(transparent)]
+    Language(Arc<LanguageError>),
+}
+
+/// The [`Kind`] of the highlight query capture `name`, like `keyword` or
+/// `function.macro`.
+fn kind(name: &str) -> Option<Kind> {
+    let kind = match name.split('.').next()? {
+        "comment" => Kind::Comment,
+        "keyword" => Kind::Keyword,
+        "string" | "escape" => Kind::String,
+        "number" => Kind::Number,
+        "constant" | "boolean" => Kind::Constant,
+        "function" => Kind::Function,
+        "type" | "constructor" => Kind::Type,
+        "property" => Kind::Property,
+        "attribute" => Kind::Attribute,
+        "tag" | "label" => Kind::Tag,
+        "operator" => Kind::Operator,
+        "punctuation" => Kind::Punctuation,
+        _ => return None,
+    };
+
+    Some(kind)
+}
+
+fn point(buffer: &Buffer, char_index: usize) -> Point {
+    let position = buffer.char_to_position(char_index);
+
+    Point::new(position.line, position.column)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rust(text: &str) -> (Buffer, Syntax) {
+        let buffer = Buffer::with_text(text);
+        let language = Language::find("rust").unwrap();
+        let syntax = Syntax::new(language, &buffer).unwrap().unwrap();
+
+        (buffer, syntax)
+    }
+
+    fn apply(buffer: &mut Buffer, syntax: &mut Syntax, edit: Edit) {
+        syntax.edit(buffer, &edit);
+        buffer.apply(edit);
+        syntax.update(buffer);
+    }
+
+    fn lines(buffer: &Buffer, syntax: &Syntax) -> Vec<Vec<(Range<usize>, Kind)>> {
+        let highlights = syntax.highlights().read().unwrap();
+
+        (0..buffer.len_lines())
+            .map(|line| highlights.line(line).to_vec())
+            .collect()
+    }
+
+    /// Checks the incrementally updated tree and highlights against a parse
+    /// from scratch.
+    fn assert_fresh(buffer: &Buffer, syntax: &Syntax) {
+        let (_, fresh) = rust(&buffer.text());
+
+        assert_eq!(
+            syntax.root().unwrap().to_sexp(),
+            fresh.root().unwrap().to_sexp()
+        );
+        assert_eq!(lines(buffer, syntax), lines(buffer, &fresh));
+    }
+
+    #[test]
+    fn bundled_queries_are_valid() {
+        for language in crate::language::ALL {
+            if let Err(error) = Syntax::new(*language, &Buffer::new()) {
+                panic!("{error}");
+            }
+        }
+    }
+
+    #[test]
+    fn edits_update_the_tree_incrementally() {
+        let (mut buffer, mut syntax) = rust("fn f() {\r\n    let a = 1;\r\n}\r\n");
+
+        apply(
+            &mut buffer,
+            &mut syntax,
+            Edit::insert(24, "\r\n    let b = \"x\r\ny\";"),
+        );
+        assert_fresh(&buffer, &syntax);
+
+        apply(&mut buffer, &mut syntax, Edit::remove(10..26));
+        assert_fresh(&buffer, &syntax);
+
+        apply(&mut buffer, &mut syntax, Edit::replace(3..4, "g"));
+        assert_fresh(&buffer, &syntax);
+        assert_eq!(
+            buffer.text(),
+            "fn g() {\r\n    let b = \"x\r\ny\";\r\n}\r\n"
+        );
+    }
+
+    #[test]
+    fn lines_whose_syntax_changed_are_highlighted_again() {
+        let (mut buffer, mut syntax) = rust("/* a */\nfn f() {}\n// */\n");
+
+        assert!(
+            lines(&buffer, &syntax)[1]
+                .iter()
+                .any(|(_, kind)| *kind == Kind::Keyword)
+        );
+
+        let revision = syntax.highlights().read().unwrap().revision();
+
+        // Block comments nest, so this one now ends on the last line.
+        apply(&mut buffer, &mut syntax, Edit::insert(3, "/* "));
+        assert_fresh(&buffer, &syntax);
+
+        assert_eq!(lines(&buffer, &syntax)[1], [(0..9, Kind::Comment)]);
+        assert_eq!(
+            syntax.highlights().read().unwrap().changed_since(revision),
+            Some(0)
+        );
+    }
+
+    #[test]
+    fn expand_and_shrink_walk_the_named_nodes() {
+        let (buffer, syntax) = rust("fn f() { let a = b + c; }");
+        let b = buffer.char_to_byte(17)..buffer.char_to_byte(18);
+
+        assert_eq!(syntax.expand(b.clone()), Some(17..22));
+        assert_eq!(syntax.expand(17..22), Some(9..23));
+        assert_eq!(syntax.shrink(17..22), Some(b));
+        assert_eq!(syntax.shrink(17..18), None);
+    }
+
+    #[test]
+    fn lines_are_indented_by_the_nodes_around_them() {
+        let (buffer, syntax) = rust("fn f() {\n    g(\n        1,\n    );\n}\n");
+
+        let levels = (0..5)
+            .map(|line| syntax.indent_level(&buffer, line))
+            .collect::<Vec<_>>();
+
+        assert_eq!(levels, [0, 1, 2, 1, 0]);
+    }
+
+    #[test]
+    fn fold_regions_leave_the_closing_bracket_visible() {
+        let (buffer, syntax) = rust("fn f() {\n    g(\n        1,\n    );\n}\n");
+
+        assert_eq!(
+            syntax.fold_regions(&buffer),
+            [Region { start: 0, end: 3 }, Region { start: 1, end: 2 }]
+        );
+    }
+}
@@ -0,0 +1,108 @@
+//! The title of the window, filled in from a template.
+//!
+//! The template is plain text with placeholders in braces, which are replaced
+//! by what they stand for in the active buffer:
+//!
+//! - `{dirty}`: a dot when the buffer has unsaved changes, nothing otherwise
+//! - `{filename}`: the name of the file of the buffer
+//! - `{path}`: the full path of the file of the buffer
+//! - `{project}`: the name of the directory being edited
+//!
+//! Anything else in braces is left as it is.
+
+use std::path::Path;
+
+use serde::Deserialize;
+
+pub const PLACEHOLDERS: &[&str] = &["dirty", "filename", "path", "project"];
+
+const DEFAULT_TEMPLATE: &str = "{dirty}{filename} \u{2014} {project} \u{2014} tsu";
+const DIRTY: &str = "\u{2022} ";
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(from = "String")]
+pub struct Title {
+    template: String,
+}
+
+impl Default for Title {
+    fn default() -> Self {
+        Self::from(String::from(DEFAULT_TEMPLATE))
+    }
+}
+
+impl From<String> for Title {
+    fn from(template: String) -> Self {
+        Self { template }
+    }
+}
+
+/// What the placeholders of a [`Title`] are replaced with.
+#[derive(Debug, Clone, Copy)]
+pub struct Fields<'a> {
+    pub is_dirty: bool,
+    pub filename: &'a str,
+    /// The path of the file, if the buffer was ever saved.
+    pub path: Option<&'a Path>,
+    pub project: &'a str,
+}
+
+impl Title {
+    pub fn format(&self, fields: Fields<'_>) -> String {
+        let mut title = String::with_capacity(self.template.len());
+        let mut rest = self.template.as_str();
+
+        while let Some((before, placeholder, after)) = next_placeholder(rest) {
+            title.push_str(before);
+
+            match placeholder {
+                "dirty" => {
+                    if fields.is_dirty {
+                        title.push_str(DIRTY);
+                    }
+                }
+                "filename" => title.push_str(fields.filename),
+                "path" => match fields.path {
+                    Some(path) => title.push_str(&path.to_string_lossy()),
+                    None => title.push_str(fields.filename),
+                },
+                "project" => title.push_str(fields.project),
+                _ => {
+                    title.push('{');
+                    title.push_str(placeholder);
+                    title.push('}');
+                }
+            }
+
+            rest = after;
+        }
+
+        title.push_str(rest);
+        title
+    }
+
+    /// The placeholders of the template that are not replaced by anything.
+    pub fn unknown_placeholders(&self) -> Vec<&str> {
+        let mut unknown = vec![];
+        let mut rest = self.template.as_str();
+
+        while let Some((_, placeholder, after)) = next_placeholder(rest) {
+            if !PLACEHOLDERS.contains(&placeholder) {
+                unknown.push(placeholder);
+            }
+
+            rest = after;
+        }
+
+        unknown
+    }
+}
+
+/// Splits `text` around its first placeholder, into the text before it, its
+/// name and the text after it.
+fn next_placeholder(text: &str) -> Option<(&str, &str, &str)> {
+    let start = text.find('{')?;
+    let end = start + text[start..].find('}')?;
+
+    Some((&text[..start], &text[start + 1..end], &text[end + 1..]))
+}
@@ -24,9 +24,9 @@ pub fn buffer(theme: &Theme, selected: bool) -> Style {
             radius: 4.0.into(),
             width: 1.0,
             color: if selected {
-                buffer.background
-            } else {
                 buffer.border
+            } else {
+                buffer.background
             },
         },
         ..Default::default()
@@ -148,3 +148,17 @@ pub fn transparent_overlay(theme: &Theme) -> Style {
         ..Default::default()
     }
 }
+
+pub fn gutter(theme: &Theme) -> Style {
+    Style {
+        background: Some(Background::Color(theme.colors().editor.gutter)),
+        ..Default::default()
+    }
+}
+
+pub fn current_line(theme: &Theme) -> Style {
+    Style {
+        background: Some(Background::Color(theme.colors().editor.current_line)),
+        ..Default::default()
+    }
+}
@@ -47,3 +47,27 @@ pub fn success(theme: &Theme) -> Style {
         color: Some(theme.colors().text.success),
     }
 }
+
+pub fn line_number(theme: &Theme) -> Style {
+    let color = theme.colors().editor.line_number;
+
+    Style {
+        color: Some(if color.a > 0.0 {
+            color
+        } else {
+            theme.colors().text.tertiary
+        }),
+    }
+}
+
+pub fn line_number_active(theme: &Theme) -> Style {
+    let color = theme.colors().editor.line_number_active;
+
+    Style {
+        color: Some(if color.a > 0.0 {
+            color
+        } else {
+            theme.colors().text.primary
+        }),
+    }
+}
@@ -1,5 +1,8 @@
+use data::syntax::Kind;
+use iced::advanced::text::highlighter::Format;
+use iced::highlighter;
 use iced::widget::text_editor::{Catalog, Status, Style, StyleFn};
-use iced::{Background, Border, Color};
+use iced::{Background, Border, Color, Font};
 
 use super::Theme;
 
@@ -17,6 +20,7 @@ impl Catalog for Theme {
 
 pub fn primary(theme: &Theme, status: Status) -> Style {
     let general = theme.colors().general;
+    let editor = theme.colors().editor;
 
     let active = Style {
         background: iced::Background::Color(general.background),
@@ -28,7 +32,14 @@ pub fn primary(theme: &Theme, status: Status) -> Style {
         icon: theme.colors().text.primary,
         placeholder: theme.colors().text.secondary,
         value: theme.colors().text.primary,
-        selection: theme.colors().general.background,
+        selection: if editor.selection.a > 0.0 {
+            editor.selection
+        } else {
+            Color {
+                a: 0.3,
+                ..theme.colors().text.secondary
+            }
+        },
     };
 
     match status {
@@ -48,3 +59,51 @@ pub fn primary(theme: &Theme, status: Status) -> Style {
         },
     }
 }
+
+/// The format of code highlighted from its parse tree, a transparent color in
+/// the theme leaves the text in its primary color.
+pub fn highlight(kind: Kind, theme: &Theme) -> Format<Font> {
+    let syntax = theme.colors().syntax;
+
+    let color = match kind {
+        Kind::Comment => syntax.comment,
+        Kind::Keyword => syntax.keyword,
+        Kind::String => syntax.string,
+        Kind::Number => syntax.number,
+        Kind::Constant => syntax.constant,
+        Kind::Function => syntax.function,
+        Kind::Type => syntax.r#type,
+        Kind::Property => syntax.property,
+        Kind::Attribute => syntax.attribute,
+        Kind::Tag => syntax.tag,
+        Kind::Operator => syntax.operator,
+        Kind::Punctuation => syntax.punctuation,
+    };
+
+    Format {
+        color: (color.a > 0.0).then_some(color),
+        font: None,
+    }
+}
+
+/// The syntect theme matching the background of the editor, for languages
+/// without a parse tree.
+pub fn highlighter(theme: &Theme) -> highlighter::Theme {
+    let [r, g, b, _] = theme.colors().general.background.into_linear();
+    let luminance = 0.2126 * r + 0.7152 * g + 0.0722 * b;
+
+    if luminance > 0.5 {
+        highlighter::Theme::InspiredGitHub
+    } else {
+        highlighter::Theme::Base16Ocean
+    }
+}
+
+/// The style of editors whose background is drawn by [`crate::widget::editor`],
+/// behind their decorations.
+pub fn transparent(theme: &Theme, status: Status) -> Style {
+    Style {
+        background: Background::Color(Color::TRANSPARENT),
+        ..primary(theme, status)
+    }
+}
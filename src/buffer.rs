@@ -0,0 +1,1216 @@
+use std::borrow::Cow;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{self, AtomicU64};
+use std::sync::{Arc, RwLock};
+use std::time::SystemTime;
+
+use data::buffer::{Checkpoint, Edit, Position};
+use data::caret::{self, Caret, Cell, Input};
+use data::encoding::Encoding;
+use data::fold::{self, Folds, Region};
+use data::history::{self, History};
+use data::language::{self, Language};
+use data::line_ending::{self, LineEnding};
+use data::syntax::{Highlights, Syntax};
+use iced::widget::text_editor;
+use tracing::error;
+use unicode_segmentation::UnicodeSegmentation;
+
+/// How many edits are replayed on the other panes showing a buffer, past
+/// which rebuilding them is cheaper.
+const MAX_REPLAYS: usize = 64;
+
+static NEXT_ID: AtomicU64 = AtomicU64::new(0);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Id(u64);
+
+impl Id {
+    fn unique() -> Self {
+        Self(NEXT_ID.fetch_add(1, atomic::Ordering::Relaxed))
+    }
+}
+
+/// An open document.
+///
+/// The [`data::Buffer`] is the source of truth for the text, every pane
+/// showing the buffer mirrors it in its own `text_editor::Content`.
+#[derive(Debug)]
+pub struct Buffer {
+    pub id: Id,
+    pub path: Option<PathBuf>,
+    pub document: data::Buffer,
+    /// The encoding the file is read and written in.
+    pub encoding: Encoding,
+    /// The text of the document as it was last opened or saved.
+    saved: Checkpoint,
+    /// Whether the document holds other text than the one saved.
+    is_dirty: bool,
+    /// Where the cursor was last seen, restored when the buffer is shown
+    /// again.
+    pub cursor: Position,
+    /// The language detected from the path and content of the document.
+    pub detected_language: Language,
+    /// The language picked by the user instead of the detected one.
+    pub language_override: Option<Language>,
+    /// The parse tree of the document, for languages with a grammar.
+    pub syntax: Option<Syntax>,
+    /// The regions of the document that can be folded.
+    pub fold_regions: Vec<Region>,
+    /// The folded regions, whose lines are left out of the panes showing the
+    /// buffer.
+    pub folds: Folds,
+    /// The undo tree of the document.
+    pub history: History,
+    /// How many lines of the document end with each line ending.
+    line_endings: line_ending::Counts,
+    /// The edits of the action being performed, each with the edit undoing
+    /// it, recorded in the history once the action is done.
+    pending: Vec<(Edit, Edit)>,
+    /// The edits the other panes showing the buffer have yet to replay, or
+    /// `None` when they have to be rebuilt instead.
+    replays: Option<Vec<Replay>>,
+}
+
+/// An edit of the document as the panes showing the buffer see it, in the
+/// lines left visible by the folds and with lines joined by `\n`.
+#[derive(Debug, Clone)]
+pub struct Replay {
+    range: Range<Position>,
+    text: String,
+}
+
+impl Default for Buffer {
+    fn default() -> Self {
+        Self::new(None, data::Buffer::default())
+    }
+}
+
+impl Buffer {
+    pub fn new(path: Option<PathBuf>, document: data::Buffer) -> Self {
+        let mut buffer = Self {
+            id: Id::unique(),
+            detected_language: language::detect(path.as_deref(), &document),
+            language_override: None,
+            path,
+            saved: document.checkpoint(),
+            document,
+            encoding: Encoding::default(),
+            is_dirty: false,
+            cursor: Position::default(),
+            syntax: None,
+            fold_regions: Vec::new(),
+            folds: Folds::default(),
+            history: History::default(),
+            line_endings: line_ending::Counts::default(),
+            pending: Vec::new(),
+            replays: Some(Vec::new()),
+        };
+
+        buffer.line_endings = buffer.document.line_endings();
+        buffer.parse();
+
+        buffer
+    }
+
+    /// Creates a new `text_editor::Content` for the document, with the cursor
+    /// placed at `cursor`.
+    ///
+    /// The lines of folded regions are left out of the content, a cursor
+    /// inside of one is placed on the line the region starts at.
+    pub fn content(&self, cursor: Position) -> text_editor::Content {
+        let mut content = text_editor::Content::with_text(&self.view().text());
+
+        self.walk(&mut content, 0, cursor, text_editor::Action::Move);
+
+        content
+    }
+
+    /// The document as shown by the panes, without the lines of its folded
+    /// regions.
+    fn view(&self) -> View<'_> {
+        View {
+            document: &self.document,
+            folds: &self.folds,
+        }
+    }
+
+    /// How many lines the panes showing the buffer have.
+    pub fn visible_lines(&self) -> usize {
+        self.view().len_lines()
+    }
+
+    /// Where the cursor of `content` is in the document.
+    pub fn cursor_position(&self, content: &text_editor::Content) -> Position {
+        self.folds.to_document_position(cursor(content))
+    }
+
+    /// Selects `range` in `content`, leaving the cursor at its end.
+    pub fn select(&self, content: &mut text_editor::Content, range: Range<Position>) {
+        content.perform(text_editor::Action::Move(
+            text_editor::Motion::DocumentStart,
+        ));
+
+        self.walk(content, 0, range.start, text_editor::Action::Move);
+        self.walk(
+            content,
+            range.start.line,
+            range.end,
+            text_editor::Action::Select,
+        );
+    }
+
+    /// Places the cursor of `content` at the head of `caret`, selecting from
+    /// its anchor. The head is expected on the line of the anchor or below
+    /// it.
+    pub fn set_caret(&self, content: &mut text_editor::Content, caret: Caret) {
+        content.perform(text_editor::Action::Move(
+            text_editor::Motion::DocumentStart,
+        ));
+
+        self.walk(content, 0, caret.anchor, text_editor::Action::Move);
+
+        if caret.is_selection() {
+            self.walk(
+                content,
+                caret.anchor.line,
+                caret.head,
+                text_editor::Action::Select,
+            );
+        }
+    }
+
+    /// The range of the whole `line` and its line break, up to the next line
+    /// left visible by the folds.
+    pub fn line_range(&self, line: usize) -> Range<Position> {
+        let next = self.folds.to_document(self.folds.to_view(line) + 1);
+
+        if next < self.document.len_lines() {
+            Position::new(line, 0)..Position::new(next, 0)
+        } else {
+            Position::new(line, 0)
+                ..Position::new(line, self.document.line_len(line).unwrap_or_default())
+        }
+    }
+
+    /// The selected range of `content`, which may have its cursor at either
+    /// end.
+    pub fn selection(&self, content: &text_editor::Content) -> Option<Range<Position>> {
+        let selection = content.selection()?;
+        let cursor = cursor(content);
+        let segments = split_lines(&selection);
+        let view = self.view();
+
+        [
+            selection_start(view, cursor, &selection).map(|start| start..cursor),
+            selection_end(view, cursor, &selection).map(|end| cursor..end),
+        ]
+        .into_iter()
+        .flatten()
+        .find(|range| {
+            view.segments(range.clone())
+                .is_some_and(|text| text == segments)
+        })
+        .map(|range| {
+            self.folds.to_document_position(range.start)..self.folds.to_document_position(range.end)
+        })
+    }
+
+    /// Walks the cursor of `content` from the start of the document `line`
+    /// down to `position`, moving or selecting depending on `action`.
+    fn walk(
+        &self,
+        content: &mut text_editor::Content,
+        line: usize,
+        position: Position,
+        action: fn(text_editor::Motion) -> text_editor::Action,
+    ) {
+        let position = self.visible(position);
+        let last = self.visible_lines().saturating_sub(1);
+
+        for _ in self.folds.to_view(line)..self.folds.to_view(position.line).min(last) {
+            content.perform(action(text_editor::Motion::Down));
+        }
+
+        content.perform(action(text_editor::Motion::Home));
+
+        if let Some(text) = self.document.line_text(position.line) {
+            let prefix = text.get(..position.column).unwrap_or(&text);
+
+            for _ in prefix.graphemes(true) {
+                content.perform(action(text_editor::Motion::Right));
+            }
+        }
+    }
+
+    /// Moves a `position` hidden by a folded region to the start of the line
+    /// the region starts at.
+    fn visible(&self, position: Position) -> Position {
+        if self.folds.is_hidden(position.line) {
+            Position::new(self.folds.to_document(self.folds.to_view(position.line)), 0)
+        } else {
+            position
+        }
+    }
+
+    /// Folds the innermost region around `line`, or unfolds the one starting
+    /// at it.
+    pub fn toggle_fold(&mut self, line: usize) {
+        if !self.folds.unfold(line..line + 1)
+            && let Some(region) = self
+                .fold_regions
+                .iter()
+                .rev()
+                .find(|region| region.start <= line && line <= region.end)
+        {
+            self.folds.toggle(*region);
+        }
+    }
+
+    pub fn fold_all(&mut self) {
+        self.folds.fold(self.fold_regions.iter().copied());
+    }
+
+    pub fn unfold_all(&mut self) {
+        self.folds.unfold_all();
+    }
+
+    /// Unfolds the regions hiding `line`, returning whether there were any.
+    pub fn reveal(&mut self, line: usize) -> bool {
+        let revealed = self.folds.reveal(line);
+
+        if revealed {
+            self.replays = None;
+        }
+
+        revealed
+    }
+
+    /// Folds the `saved` regions again, like the ones of the file in an
+    /// earlier session, if they can still be folded.
+    pub fn restore_folds(&mut self, saved: &[Region]) {
+        self.folds = Folds::restore(saved, &self.fold_regions);
+        self.replays = None;
+    }
+
+    /// Replaces the document with the text of the file read again, like in
+    /// another encoding. The history and the folds of the old text are
+    /// dropped along with it.
+    pub fn reopen(&mut self, document: data::Buffer, encoding: Encoding) {
+        self.saved = document.checkpoint();
+        self.document = document;
+        self.encoding = encoding;
+        self.is_dirty = false;
+        self.history = History::default();
+        self.pending.clear();
+        self.replays = None;
+        self.folds = Folds::default();
+        self.line_endings = self.document.line_endings();
+        self.syntax = None;
+        self.detect_language();
+    }
+
+    /// How many lines of the document end with each line ending.
+    pub fn line_endings(&self) -> line_ending::Counts {
+        self.line_endings
+    }
+
+    /// The line ending most lines end with, the one new lines get.
+    pub fn line_ending(&self) -> LineEnding {
+        self.line_endings.dominant().unwrap_or_default()
+    }
+
+    /// Turns every line ending of the document into `line_ending`, as a
+    /// single change of the history.
+    ///
+    /// Returns whether the document changed.
+    pub fn convert_line_endings(&mut self, line_ending: LineEnding) -> bool {
+        let edits = self.document.convert_line_endings(line_ending);
+
+        if edits.is_empty() {
+            return false;
+        }
+
+        for edit in edits.into_iter().rev() {
+            self.apply(edit);
+        }
+
+        self.commit(history::Kind::Other);
+        self.reparse();
+
+        true
+    }
+
+    pub fn title(&self) -> String {
+        self.path.as_deref().and_then(Path::file_name).map_or_else(
+            || String::from("New file"),
+            |name| name.to_string_lossy().to_string(),
+        )
+    }
+
+    /// The language the buffer is highlighted as.
+    pub fn language(&self) -> Language {
+        self.language_override.unwrap_or(self.detected_language)
+    }
+
+    /// Detects the language again, after the path of the buffer changed.
+    pub fn detect_language(&mut self) {
+        self.detected_language = language::detect(self.path.as_deref(), &self.document);
+        self.parse();
+    }
+
+    pub fn set_language_override(&mut self, language: Option<Language>) {
+        self.language_override = language;
+        self.parse();
+    }
+
+    /// Parses the document from scratch when its language changed.
+    fn parse(&mut self) {
+        let language = self.language();
+
+        if self.syntax.as_ref().map(Syntax::language) != Some(language) {
+            self.syntax = Syntax::new(language, &self.document).unwrap_or_else(|error| {
+                error!("failed to parse the buffer: {error}");
+
+                None
+            });
+            self.fold_regions = fold::regions(&self.document, self.syntax.as_ref());
+            self.folds = Folds::restore(
+                &self.folds.regions().collect::<Vec<_>>(),
+                &self.fold_regions,
+            );
+        }
+    }
+
+    /// The highlights of the parse tree, shared with the editors showing the
+    /// buffer.
+    pub fn highlights(&self) -> Option<&Arc<RwLock<Highlights>>> {
+        self.syntax.as_ref().map(Syntax::highlights)
+    }
+
+    /// Whether the document holds other text than it was last opened or
+    /// saved with.
+    pub fn is_dirty(&self) -> bool {
+        self.is_dirty
+    }
+
+    /// Marks the document as saved with the text it held at `checkpoint`.
+    pub fn mark_saved(&mut self, checkpoint: Checkpoint) {
+        self.saved = checkpoint;
+        self.is_dirty = !self.saved.matches(&self.document);
+    }
+
+    /// Whether this is an untouched, never saved buffer that can be replaced
+    /// by a file being opened.
+    pub fn is_scratch(&self) -> bool {
+        self.path.is_none() && !self.is_dirty && self.document.is_empty()
+    }
+
+    /// Performs the `action` on `content`, mirroring any edit into the
+    /// document.
+    ///
+    /// Returns whether the document changed.
+    pub fn perform(
+        &mut self,
+        content: &mut text_editor::Content,
+        action: text_editor::Action,
+    ) -> bool {
+        let revision = self.document.revision();
+
+        let mut kind = history::Kind::Other;
+
+        if let text_editor::Action::Edit(edit) = action {
+            kind = self.input(&edit).as_ref().map_or(kind, history_kind);
+
+            self.unfold_around(content, &edit);
+
+            let edit = Mirror {
+                view: self.view(),
+                content,
+                line_ending: self.line_ending(),
+            }
+            .edit(edit);
+
+            if !edit.is_empty() {
+                self.apply(edit);
+            }
+        } else {
+            content.perform(action);
+        }
+
+        self.cursor = self.cursor_position(content);
+
+        let changed = self.document.revision() != revision;
+
+        if changed {
+            self.commit(kind);
+            self.reparse();
+        }
+
+        changed
+    }
+
+    /// Performs the `action` at the cursor of `content` and at the `carets`
+    /// besides it, mirroring any edit into the document.
+    ///
+    /// Actions with a single target, like clicking or selecting everything,
+    /// drop the carets. Returns whether the document changed.
+    pub fn perform_carets(
+        &mut self,
+        content: &mut text_editor::Content,
+        carets: &mut Vec<Caret>,
+        action: text_editor::Action,
+    ) -> bool {
+        if carets.is_empty() {
+            return self.perform(content, action);
+        }
+
+        match action {
+            text_editor::Action::Edit(edit) => {
+                if let Some(input) = self.input(&edit) {
+                    self.type_at_carets(content, carets, input)
+                } else {
+                    carets.clear();
+                    self.perform(content, text_editor::Action::Edit(edit))
+                }
+            }
+            text_editor::Action::Move(motion) | text_editor::Action::Select(motion) => {
+                let select = matches!(action, text_editor::Action::Select(_));
+
+                content.perform(action);
+
+                for caret in carets.iter_mut() {
+                    *caret = caret.moved(&self.document, motion, select);
+                }
+
+                self.cursor = self.cursor_position(content);
+                self.merge_carets(content, carets);
+
+                false
+            }
+            text_editor::Action::Scroll { .. } => self.perform(content, action),
+            _ => {
+                carets.clear();
+                self.perform(content, action)
+            }
+        }
+    }
+
+    /// What `edit` types at each caret, or `None` for edits that only apply
+    /// to the cursor of the editor.
+    fn input(&self, edit: &text_editor::Edit) -> Option<Input> {
+        let line_ending = self.line_ending().as_str();
+
+        match edit {
+            text_editor::Edit::Insert(c) => Some(Input::Insert(c.to_string())),
+            text_editor::Edit::Paste(text) => {
+                Some(Input::Insert(split_lines(text).join(line_ending)))
+            }
+            text_editor::Edit::Enter => Some(Input::Insert(line_ending.to_owned())),
+            text_editor::Edit::Backspace => Some(Input::Backspace),
+            text_editor::Edit::Delete => Some(Input::Delete),
+            text_editor::Edit::Indent | text_editor::Edit::Unindent => None,
+        }
+    }
+
+    /// Types `input` at the cursor of `content` and at every one of the
+    /// `carets`, replaying the edits on the content.
+    ///
+    /// Text pasted with as many lines as there are cursors is spread over
+    /// them, a line each.
+    fn type_at_carets(
+        &mut self,
+        content: &mut text_editor::Content,
+        carets: &mut Vec<Caret>,
+        input: Input,
+    ) -> bool {
+        let revision = self.document.revision();
+        let line_ending = self.line_ending().as_str();
+        let kind = history_kind(&input);
+
+        let primary = self.caret(content);
+        let all = caret::merge(carets.iter().copied().chain([primary]).collect());
+        let primary = all
+            .iter()
+            .position(|caret| caret.overlaps(&primary) || caret.range().contains(&primary.head))
+            .unwrap_or_default();
+
+        let inputs = match &input {
+            Input::Insert(text) if text.split(line_ending).count() == all.len() => text
+                .split(line_ending)
+                .map(|line| Input::Insert(line.to_owned()))
+                .collect(),
+            _ => vec![input; all.len()],
+        };
+
+        let (edits, heads) = caret::edits(&self.document, &all, &inputs);
+        let mut replays = Vec::with_capacity(edits.len());
+
+        for edit in edits.into_iter().rev() {
+            if !edit.is_empty() {
+                replays.push(self.replay_of(&edit));
+                self.apply(edit);
+            }
+        }
+
+        let mut heads = heads
+            .into_iter()
+            .map(|head| self.document.char_to_position(head))
+            .collect::<Vec<_>>();
+        let cursor = heads.remove(primary);
+
+        let is_replayed = replays
+            .into_iter()
+            .collect::<Option<Vec<_>>>()
+            .is_some_and(|replays| self.replay(content, &replays));
+
+        if is_replayed {
+            let visible = self.visible(cursor);
+
+            move_to(
+                content,
+                Position::new(self.folds.to_view(visible.line), visible.column),
+                text_editor::Action::Move,
+            );
+        } else {
+            *content = self.content(cursor);
+        }
+
+        *carets = heads.into_iter().map(Caret::new).collect();
+        self.cursor = cursor;
+
+        let changed = self.document.revision() != revision;
+
+        if changed {
+            self.commit(kind);
+            self.reparse();
+        }
+
+        changed
+    }
+
+    /// Pastes the lines of a rectangular selection one below the other, at
+    /// the column of the cursor of `content`.
+    ///
+    /// Lines shorter than the column are padded with spaces, and lines are
+    /// added past the end of the document as needed.
+    pub fn paste_block(
+        &mut self,
+        content: &mut text_editor::Content,
+        carets: &mut Vec<Caret>,
+        text: &str,
+    ) -> bool {
+        let revision = self.document.revision();
+        let line_ending = self.line_ending().as_str();
+        let lines = split_lines(text);
+        let Cell {
+            line: first,
+            column,
+        } = Cell::at(&self.document, self.cursor_position(content));
+
+        for line in first..first + lines.len() {
+            if line >= self.document.len_lines() {
+                self.apply(Edit::insert(self.document.len_chars(), line_ending));
+            }
+
+            let len = self
+                .document
+                .line_text(line)
+                .map_or(0, |text| text.chars().count());
+
+            if len < column {
+                let end = self
+                    .document
+                    .position_to_char(Position::new(line, usize::MAX));
+
+                self.apply(Edit::insert(end, " ".repeat(column - len)));
+            }
+        }
+
+        let position = |line| Cell { line, column }.position(&self.document);
+
+        *content = self.content(position(first));
+        *carets = (first + 1..first + lines.len())
+            .map(|line| Caret::new(position(line)))
+            .collect();
+
+        let typed = self.type_at_carets(content, carets, Input::Insert(lines.join(line_ending)));
+        let changed = self.document.revision() != revision;
+
+        if changed && !typed {
+            self.commit(history::Kind::Other);
+            self.reparse();
+        }
+
+        changed
+    }
+
+    /// The cursor of `content` and its selection, in the document.
+    pub fn caret(&self, content: &text_editor::Content) -> Caret {
+        let head = self.cursor_position(content);
+
+        match self.selection(content) {
+            Some(range) if range.start == head => Caret {
+                anchor: range.end,
+                head,
+            },
+            Some(range) => Caret {
+                anchor: range.start,
+                head,
+            },
+            None => Caret::new(head),
+        }
+    }
+
+    /// Merges the `carets` that ran into each other, or into the cursor of
+    /// `content`.
+    pub fn merge_carets(&self, content: &text_editor::Content, carets: &mut Vec<Caret>) {
+        let primary = self.caret(content);
+
+        *carets = caret::merge(std::mem::take(carets))
+            .into_iter()
+            .filter(|caret| !caret.overlaps(&primary))
+            .collect();
+    }
+
+    /// Brings the syntax tree, foldable regions and highlights up to date
+    /// after the document changed.
+    fn reparse(&mut self) {
+        if let Some(syntax) = &mut self.syntax {
+            syntax.update(&self.document);
+        }
+
+        self.fold_regions = fold::regions(&self.document, self.syntax.as_ref());
+    }
+
+    /// Unfolds the regions starting on the lines an edit at the cursor of
+    /// `content` reaches, so edits only ever touch visible lines.
+    fn unfold_around(&mut self, content: &mut text_editor::Content, edit: &text_editor::Edit) {
+        if self.folds.is_empty() {
+            return;
+        }
+
+        let cursor = self.cursor_position(content);
+        let selection = self.selection(content);
+        let range = selection.clone().unwrap_or(cursor..cursor);
+
+        // Deleting the line break before the cursor joins its line to the
+        // one above, which may start a folded region.
+        let first = if selection.is_none()
+            && cursor.column == 0
+            && matches!(edit, text_editor::Edit::Backspace)
+        {
+            self.folds
+                .to_document(self.folds.to_view(cursor.line).saturating_sub(1))
+        } else {
+            range.start.line
+        };
+
+        if self.folds.unfold(first..range.end.line + 1) {
+            self.replays = None;
+            *content = self.content(cursor);
+
+            if let Some(selection) = selection {
+                self.select(content, selection);
+            }
+        }
+    }
+
+    fn apply(&mut self, edit: Edit) {
+        self.record_replay(&edit);
+
+        if self.folds.edit(&self.document, &edit) {
+            self.replays = None;
+        }
+
+        if let Some(syntax) = &mut self.syntax {
+            syntax.edit(&self.document, &edit);
+        }
+
+        // The chars on either side of the edit are counted along with it, as
+        // they may pair up with it into a CRLF or stop doing so.
+        let len = self.document.len_chars();
+        let end = edit.range.end.min(len);
+        let around = edit.range.start.min(end).saturating_sub(1)..(end + 1).min(len);
+        let before = self.document.slice(around.clone()).into_owned();
+
+        let inverse = self.document.apply(edit.clone());
+
+        let after = self
+            .document
+            .slice(around.start..inverse.range.end + (around.end - end));
+        self.line_endings.replace(&before, &after);
+
+        self.pending.push((edit, inverse));
+    }
+
+    /// Records `edit`, about to be applied, for the other panes showing the
+    /// buffer to replay.
+    fn record_replay(&mut self, edit: &Edit) {
+        if self
+            .replays
+            .as_ref()
+            .is_some_and(|replays| replays.len() < MAX_REPLAYS)
+            && let Some(replay) = self.replay_of(edit)
+            && let Some(replays) = &mut self.replays
+        {
+            replays.push(replay);
+        } else {
+            self.replays = None;
+        }
+    }
+
+    /// The [`Replay`] of `edit`, about to be applied, or `None` if it
+    /// touches the lines of a folded region.
+    fn replay_of(&self, edit: &Edit) -> Option<Replay> {
+        let start = self.document.char_to_position(edit.range.start);
+        let end = self.document.char_to_position(edit.range.end);
+
+        if self.folds.is_hidden(start.line) || self.folds.is_hidden(end.line) {
+            return None;
+        }
+
+        Some(Replay {
+            range: Position::new(self.folds.to_view(start.line), start.column)
+                ..Position::new(self.folds.to_view(end.line), end.column),
+            text: split_lines(&edit.text).join("\n"),
+        })
+    }
+
+    /// Takes the edits made since the other panes showing the buffer last
+    /// caught up with it, `None` if they have to be rebuilt instead.
+    pub fn take_replays(&mut self) -> Option<Vec<Replay>> {
+        self.replays
+            .replace(Vec::new())
+            .filter(|replays| !replays.is_empty())
+    }
+
+    /// Replays the edits taken with [`Buffer::take_replays`] on the
+    /// `content` of another pane, keeping its cursor in place.
+    ///
+    /// Returns whether the content shows the document afterwards, it has to
+    /// be rebuilt otherwise.
+    pub fn replay(&self, content: &mut text_editor::Content, replays: &[Replay]) -> bool {
+        let mut position = cursor(content);
+
+        for replay in replays {
+            let edit = if !replay.text.is_empty() {
+                text_editor::Edit::Paste(Arc::new(replay.text.clone()))
+            } else if replay.range.start != replay.range.end {
+                text_editor::Edit::Delete
+            } else {
+                continue;
+            };
+
+            move_to(content, replay.range.start, text_editor::Action::Move);
+            move_to(content, replay.range.end, text_editor::Action::Select);
+            content.perform(text_editor::Action::Edit(edit));
+
+            position = replay.shift(position);
+        }
+
+        // The lines each edit left behind, moved by the ones after it, have
+        // to be the lines of the document.
+        let is_mirrored = content.line_count() == self.visible_lines()
+            && replays.iter().enumerate().all(|(index, replay)| {
+                let lines = replays[index + 1..]
+                    .iter()
+                    .fold(replay.range.start..replay.end(), |lines, later| {
+                        later.shift(lines.start)..later.shift(lines.end)
+                    });
+
+                (lines.start.line..=lines.end.line).all(|line| {
+                    content.line(line).map(|line| line.text.into_owned())
+                        == self
+                            .document
+                            .line_text(self.folds.to_document(line))
+                            .map(Cow::into_owned)
+                })
+            });
+
+        if is_mirrored {
+            move_to(content, position, text_editor::Action::Move);
+        }
+
+        is_mirrored
+    }
+
+    /// Records the edits of the action just performed in the history.
+    fn commit(&mut self, kind: history::Kind) {
+        self.history
+            .record(std::mem::take(&mut self.pending), kind, SystemTime::now());
+        self.is_dirty = !self.saved.matches(&self.document);
+    }
+
+    /// Undoes the last change, returning where the cursor goes.
+    pub fn undo(&mut self) -> Option<Position> {
+        let edits = self.history.undo()?;
+
+        Some(self.travel_with(edits))
+    }
+
+    /// Redoes the change undone last, returning where the cursor goes.
+    pub fn redo(&mut self) -> Option<Position> {
+        let edits = self.history.redo()?;
+
+        Some(self.travel_with(edits))
+    }
+
+    /// Goes back to the state before the current one in time, even if it is
+    /// on another branch of the history.
+    pub fn earlier(&mut self) -> Option<Position> {
+        let edits = self.history.earlier()?;
+
+        Some(self.travel_with(edits))
+    }
+
+    /// Goes forward to the state after the current one in time.
+    pub fn later(&mut self) -> Option<Position> {
+        let edits = self.history.later()?;
+
+        Some(self.travel_with(edits))
+    }
+
+    /// Goes to the state the document was in at `time`.
+    pub fn travel_to_time(&mut self, time: SystemTime) -> Option<Position> {
+        let edits = self.history.travel_to_time(time)?;
+
+        Some(self.travel_with(edits))
+    }
+
+    /// Goes to the state of a node of the history.
+    pub fn travel(&mut self, node: usize) -> Option<Position> {
+        let edits = self.history.travel(node);
+
+        (!edits.is_empty()).then(|| self.travel_with(edits))
+    }
+
+    /// Applies the `edits` moving through the history, without recording
+    /// them, returning where the last one ends.
+    fn travel_with(&mut self, edits: Vec<Edit>) -> Position {
+        let mut end = self.document.position_to_char(self.cursor);
+
+        for edit in edits {
+            end = edit.range.start + edit.text.chars().count();
+            self.apply(edit);
+        }
+
+        self.pending.clear();
+        self.is_dirty = !self.saved.matches(&self.document);
+        self.cursor = self.document.char_to_position(end);
+        self.reparse();
+
+        self.cursor
+    }
+}
+
+pub fn cursor(content: &text_editor::Content) -> Position {
+    content.cursor_position().into()
+}
+
+/// Moves the cursor of `content` from where it is to the `position` of its
+/// lines, moving or selecting depending on `action`.
+fn move_to(
+    content: &mut text_editor::Content,
+    position: Position,
+    action: fn(text_editor::Motion) -> text_editor::Action,
+) {
+    let line = cursor(content).line;
+    let motion = if position.line > line {
+        text_editor::Motion::Down
+    } else {
+        text_editor::Motion::Up
+    };
+
+    for _ in 0..line.abs_diff(position.line) {
+        content.perform(action(motion));
+    }
+
+    content.perform(action(text_editor::Motion::Home));
+
+    let graphemes = content.line(position.line).map_or(0, |line| {
+        let prefix = line.text.get(..position.column).unwrap_or(&line.text);
+
+        prefix.graphemes(true).count()
+    });
+
+    for _ in 0..graphemes {
+        content.perform(action(text_editor::Motion::Right));
+    }
+}
+
+impl Replay {
+    /// Where the text inserted by the edit ends.
+    fn end(&self) -> Position {
+        let segments = self.text.split('\n').collect::<Vec<_>>();
+        let last = segments[segments.len() - 1].len();
+
+        match segments.len() - 1 {
+            0 => Position::new(self.range.start.line, self.range.start.column + last),
+            lines => Position::new(self.range.start.line + lines, last),
+        }
+    }
+
+    /// Where `position` is once the edit is made, positions inside of the
+    /// replaced range ending up at its start.
+    fn shift(&self, position: Position) -> Position {
+        let Range { start, end } = self.range;
+
+        if position <= start {
+            position
+        } else if position < end {
+            start
+        } else if position.line == end.line {
+            let new_end = self.end();
+
+            Position::new(new_end.line, new_end.column + position.column - end.column)
+        } else {
+            Position::new(position.line - end.line + self.end().line, position.column)
+        }
+    }
+}
+
+/// A document as shown by the panes, without the lines of its folded
+/// regions.
+///
+/// Lines and positions are the ones of the view, while char indices are the
+/// ones of the document.
+#[derive(Clone, Copy)]
+struct View<'a> {
+    document: &'a data::Buffer,
+    folds: &'a Folds,
+}
+
+impl View<'_> {
+    fn len_lines(&self) -> usize {
+        self.folds
+            .to_view(self.document.len_lines().saturating_sub(1))
+            + 1
+    }
+
+    fn line_text(&self, line: usize) -> Option<Cow<'_, str>> {
+        self.document.line_text(self.folds.to_document(line))
+    }
+
+    fn line_len(&self, line: usize) -> Option<usize> {
+        self.document.line_len(self.folds.to_document(line))
+    }
+
+    /// The char index of the document shown at `position`.
+    fn position_to_char(&self, position: Position) -> usize {
+        self.document
+            .position_to_char(self.folds.to_document_position(position))
+    }
+
+    /// The visible lines, joined by the line ending of the document.
+    fn text(&self) -> String {
+        if self.folds.is_empty() {
+            self.document.text()
+        } else {
+            self.folds.project(self.document)
+        }
+    }
+
+    /// The visible text in `range`, split into its lines.
+    fn segments(&self, range: Range<Position>) -> Option<Vec<String>> {
+        (range.start.line..=range.end.line)
+            .map(|line| {
+                let text = self.line_text(line)?;
+                let start = if line == range.start.line {
+                    range.start.column
+                } else {
+                    0
+                };
+                let end = if line == range.end.line {
+                    range.end.column
+                } else {
+                    text.len()
+                };
+
+                text.get(start..end).map(str::to_owned)
+            })
+            .collect()
+    }
+
+    /// The edit turning the visible lines into `text`.
+    ///
+    /// The lines are copied when some are folded, so this is only meant for
+    /// edits that can't be figured out otherwise.
+    fn diff(&self, text: &str) -> Edit {
+        if self.folds.is_empty() {
+            return self.document.diff(text);
+        }
+
+        let projection = data::Buffer::with_text(&self.text());
+        let edit = projection.diff(text);
+
+        let start = self.position_to_char(projection.char_to_position(edit.range.start));
+        let end = self.position_to_char(projection.char_to_position(edit.range.end));
+
+        Edit::replace(start..end, edit.text)
+    }
+}
+
+/// Figures out the edits done to a `text_editor::Content` showing a
+/// document.
+struct Mirror<'a> {
+    view: View<'a>,
+    content: &'a mut text_editor::Content,
+    /// What the lines of the content are joined with in the document.
+    line_ending: LineEnding,
+}
+
+impl Mirror<'_> {
+    /// Performs `edit` on the content, returning the [`Edit`] that does the
+    /// same to the document.
+    fn edit(&mut self, edit: text_editor::Edit) -> Edit {
+        let inserted = match &edit {
+            text_editor::Edit::Insert(c) => Some(c.to_string()),
+            text_editor::Edit::Paste(text) => Some(text.to_string()),
+            text_editor::Edit::Enter => Some(String::from("\n")),
+            text_editor::Edit::Backspace | text_editor::Edit::Delete => Some(String::new()),
+            _ => None,
+        };
+        let is_backspace = matches!(edit, text_editor::Edit::Backspace);
+
+        let before = cursor(self.content);
+        let selection = self.content.selection();
+
+        self.content.perform(text_editor::Action::Edit(edit));
+
+        let Some(inserted) = inserted else {
+            return self.resync();
+        };
+
+        // The edit starts either at the cursor or, when the cursor sits at the
+        // end of a selection, at the other end of it.
+        let candidates = match &selection {
+            Some(selection) => vec![Some(before), selection_start(self.view, before, selection)],
+            None if is_backspace => vec![Some(cursor(self.content))],
+            None => vec![Some(before)],
+        };
+
+        candidates
+            .into_iter()
+            .flatten()
+            .find_map(|start| self.diff(start, &inserted))
+            .unwrap_or_else(|| self.resync())
+    }
+
+    /// Figures out the [`Edit`] that turns the document into the current
+    /// content, assuming `inserted` was inserted at `start`.
+    fn diff(&self, start: Position, inserted: &str) -> Option<Edit> {
+        let segments = split_lines(inserted);
+        let inserted_lines = segments.len() - 1;
+
+        let removed_lines =
+            (self.view.len_lines() + inserted_lines).checked_sub(self.content.line_count())?;
+        let end_line = start.line + removed_lines;
+
+        let inserted_end = if inserted_lines == 0 {
+            start.column + segments[0].len()
+        } else {
+            segments[inserted_lines].len()
+        };
+        let suffix_len = self
+            .content_line(start.line + inserted_lines)?
+            .len()
+            .checked_sub(inserted_end)?;
+        let end_column = self.view.line_len(end_line)?.checked_sub(suffix_len)?;
+        let end = Position::new(end_line, end_column);
+
+        if end < start {
+            return None;
+        }
+
+        let first = self.view.line_text(start.line)?;
+        let prefix = first.get(..start.column)?;
+        let last = self.view.line_text(end_line)?;
+        let suffix = last.get(end_column..)?;
+
+        for (i, segment) in segments.iter().enumerate() {
+            let expected = format!(
+                "{}{segment}{}",
+                if i == 0 { prefix } else { "" },
+                if i == inserted_lines { suffix } else { "" },
+            );
+
+            if self.content_line(start.line + i)? != expected {
+                return None;
+            }
+        }
+
+        let line_ending = self.line_ending.as_str();
+
+        Some(Edit::replace(
+            self.view.position_to_char(start)..self.view.position_to_char(end),
+            segments.join(line_ending),
+        ))
+    }
+
+    fn content_line(&self, index: usize) -> Option<String> {
+        self.content.line(index).map(|line| line.text.into_owned())
+    }
+
+    /// Compares the whole content to the document, for edits that can't be
+    /// mirrored.
+    fn resync(&self) -> Edit {
+        let line_ending = self.line_ending.as_str();
+        let text = (0..self.content.line_count())
+            .filter_map(|index| self.content_line(index))
+            .collect::<Vec<_>>()
+            .join(line_ending);
+
+        self.view.diff(&text)
+    }
+}
+
+/// The start of `selection`, assuming the cursor sits at its end.
+fn selection_start(view: View<'_>, cursor: Position, selection: &str) -> Option<Position> {
+    let segments = split_lines(selection);
+    let first = segments[0].len();
+
+    match segments.len() - 1 {
+        0 => Some(Position::new(
+            cursor.line,
+            cursor.column.checked_sub(first)?,
+        )),
+        lines => {
+            let line = cursor.line.checked_sub(lines)?;
+            let column = view.line_len(line)?.checked_sub(first)?;
+
+            Some(Position::new(line, column))
+        }
+    }
+}
+
+/// The end of `selection`, assuming the cursor sits at its start.
+fn selection_end(view: View<'_>, cursor: Position, selection: &str) -> Option<Position> {
+    let segments = split_lines(selection);
+    let last = segments[segments.len() - 1].len();
+
+    let end = match segments.len() - 1 {
+        0 => Position::new(cursor.line, cursor.column + last),
+        lines => Position::new(cursor.line + lines, last),
+    };
+
+    (end.column <= view.line_len(end.line)?).then_some(end)
+}
+
+/// The kind of change typing `input` is, for coalescing it in the history.
+fn history_kind(input: &Input) -> history::Kind {
+    match input {
+        Input::Insert(text) if text.chars().count() == 1 && !text.contains(['\r', '\n']) => {
+            history::Kind::Typing
+        }
+        Input::Backspace | Input::Delete => history::Kind::Deleting,
+        Input::Insert(_) => history::Kind::Other,
+    }
+}
+
+fn split_lines(text: &str) -> Vec<&str> {
+    text.split('\n')
+        .map(|line| line.strip_suffix('\r').unwrap_or(line))
+        .collect()
+}
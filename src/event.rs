@@ -1,10 +1,17 @@
 use iced::{Subscription, event, keyboard, mouse, window};
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum Event {
-    Escape,
     LeftClick,
-    OpenControlPalette,
+    /// Every key press, including the ones a widget already handled, so key
+    /// bindings can still apply while e.g. a text input has focus.
+    KeyPressed {
+        key: keyboard::Key,
+        modifiers: keyboard::Modifiers,
+        captured: bool,
+    },
+    /// The modifiers held, for mouse interactions they change.
+    ModifiersChanged(keyboard::Modifiers),
 }
 
 pub fn events() -> Subscription<(window::Id, Event)> {
@@ -19,19 +26,18 @@ fn filtered_events(
     let ignored = |status| matches!(status, iced::event::Status::Ignored);
 
     let event = match &event {
-        iced::Event::Keyboard(keyboard::Event::KeyPressed {
-            key: keyboard::Key::Named(keyboard::key::Named::Escape),
-            ..
-        }) => Some(Event::Escape),
         iced::Event::Mouse(mouse::Event::ButtonPressed(mouse::Button::Left)) if ignored(status) => {
             Some(Event::LeftClick)
         }
-        iced::Event::Keyboard(keyboard::Event::KeyPressed {
-            key: keyboard::Key::Character(p),
-            modifiers,
-            ..
-        }) if p.as_str() == "p" && modifiers.command() && modifiers.shift() => {
-            Some(Event::OpenControlPalette)
+        iced::Event::Keyboard(keyboard::Event::KeyPressed { key, modifiers, .. }) => {
+            Some(Event::KeyPressed {
+                key: key.clone(),
+                modifiers: *modifiers,
+                captured: !ignored(status),
+            })
+        }
+        iced::Event::Keyboard(keyboard::Event::ModifiersChanged(modifiers)) => {
+            Some(Event::ModifiersChanged(*modifiers))
         }
         _ => None,
     };
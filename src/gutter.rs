@@ -0,0 +1,150 @@
+//! The column left of the editor of a pane, with the line numbers and
+//! chevrons on the lines starting a region that can be folded.
+//!
+//! Only the lines scrolled into view are built, the space above them is kept
+//! empty so the rows line up with the lines of the editor. A wrapped line
+//! gets its number on its first row.
+
+use std::ops::Range;
+
+use data::config::LineNumbers;
+use iced::widget::{
+    button, column, container, horizontal_space, mouse_area, row, text, vertical_space,
+};
+use iced::{Length, alignment};
+
+use crate::appearance::theme;
+use crate::buffer::Buffer;
+use crate::widget::Element;
+use crate::wrap::Wrap;
+
+/// The width of the column of chevrons.
+const CHEVRON_WIDTH: f32 = 16.0;
+
+/// The width of a digit relative to the text size, for monospace fonts.
+const DIGIT_WIDTH: f32 = 0.6;
+
+#[derive(Debug, Clone, Copy)]
+pub struct Settings {
+    pub line_numbers: LineNumbers,
+    pub text_size: f32,
+    pub line_height: f32,
+    pub padding: f32,
+}
+
+/// The gutter of `buffer` for the `lines` of the editor in view, with the
+/// cursor on the editor line `cursor` and the lines wrapped as in `wrap`.
+///
+/// Chevrons fold and unfold with `on_toggle`, line numbers select their line
+/// with `on_select`. Both are given the line of the document.
+pub fn view<'a, Message: Clone + 'a>(
+    buffer: &'a Buffer,
+    lines: Range<usize>,
+    cursor: usize,
+    wrap: &Wrap,
+    settings: Settings,
+    on_toggle: impl Fn(usize) -> Message + 'a,
+    on_select: impl Fn(usize) -> Message + 'a,
+) -> Element<'a, Message> {
+    let Settings {
+        line_numbers,
+        text_size,
+        line_height,
+        padding,
+    } = settings;
+
+    let lines = lines.start..lines.end.min(buffer.visible_lines());
+    let number_width = width(buffer, settings) - CHEVRON_WIDTH;
+
+    let rows = lines.clone().map(|line| {
+        let document_line = buffer.folds.to_document(line);
+        let is_cursor = line == cursor;
+
+        let number: Element<'a, Message> = if line_numbers == LineNumbers::Off {
+            horizontal_space().width(0).into()
+        } else {
+            let label = match line_numbers {
+                LineNumbers::Relative if !is_cursor => line.abs_diff(cursor),
+                _ => document_line + 1,
+            };
+
+            mouse_area(
+                container(text(label.to_string()).size(text_size).style(if is_cursor {
+                    theme::text::line_number_active
+                } else {
+                    theme::text::line_number
+                }))
+                .width(number_width)
+                .height(line_height)
+                .align_x(alignment::Horizontal::Right)
+                .align_y(alignment::Vertical::Center),
+            )
+            .on_press(on_select(document_line))
+            .into()
+        };
+
+        let chevron: Element<'a, Message> = if buffer
+            .fold_regions
+            .iter()
+            .any(|region| region.start == document_line)
+        {
+            let glyph = if buffer.folds.is_folded(document_line) {
+                "\u{25b8}"
+            } else {
+                "\u{25be}"
+            };
+
+            button(text(glyph).style(theme::text::tertiary))
+                .padding(0)
+                .style(theme::button::bare)
+                .on_press(on_toggle(document_line))
+                .into()
+        } else {
+            horizontal_space().into()
+        };
+
+        let row = container(
+            row![
+                number,
+                container(chevron)
+                    .width(CHEVRON_WIDTH)
+                    .height(line_height)
+                    .align_x(alignment::Horizontal::Center)
+                    .align_y(alignment::Vertical::Center),
+            ]
+            .height(line_height),
+        )
+        .height(wrap.rows(line) as f32 * line_height);
+
+        if is_cursor {
+            row.style(theme::container::current_line).into()
+        } else {
+            row.into()
+        }
+    });
+
+    let top = padding + wrap.top(lines.start) as f32 * line_height;
+    let height = padding * 2.0 + wrap.top(buffer.visible_lines()) as f32 * line_height;
+
+    container(column![vertical_space().height(top)].extend(rows))
+        .width(Length::Shrink)
+        .height(height)
+        .style(theme::container::gutter)
+        .into()
+}
+
+/// The width of the gutter of `buffer`.
+pub fn width(buffer: &Buffer, settings: Settings) -> f32 {
+    if settings.line_numbers == LineNumbers::Off {
+        return CHEVRON_WIDTH;
+    }
+
+    let digits = buffer.document.len_lines().to_string().len().max(2);
+
+    digits as f32 * settings.text_size * DIGIT_WIDTH + settings.padding + CHEVRON_WIDTH
+}
+
+/// The width of a char of the editor, for monospace fonts.
+pub fn char_width(text_size: f32) -> f32 {
+    text_size * DIGIT_WIDTH
+}
@@ -0,0 +1,183 @@
+//! Highlighting of the lines of an editor.
+//!
+//! Buffers with a parse tree are highlighted from it, in the colors of the
+//! `[syntax]` section of the theme. Other languages are left to the syntect
+//! highlighter of iced, with the syntect theme matching the background.
+
+use std::ops::Range;
+use std::sync::{Arc, PoisonError, RwLock, RwLockReadGuard};
+
+use data::fold::Folds;
+use data::language::{self, Language};
+use data::syntax::{Highlights, Kind};
+use iced::Font;
+use iced::advanced::text::highlighter::{self, Format};
+use iced::highlighter as syntect;
+
+use crate::appearance::{Theme, theme};
+use crate::buffer::Buffer;
+
+#[derive(Debug, Clone)]
+pub struct Settings {
+    language: Language,
+    /// The syntect theme of languages without a parse tree.
+    theme: syntect::Theme,
+    /// The highlights of the parse tree of the buffer, if it has one.
+    highlights: Option<Arc<RwLock<Highlights>>>,
+    /// The revision of the highlights when the settings were made.
+    revision: u64,
+    /// The folds of the buffer, hiding lines from the editor.
+    folds: Folds,
+}
+
+impl Settings {
+    pub fn new(buffer: Option<&Buffer>, theme: &Theme) -> Self {
+        let highlights = buffer.and_then(Buffer::highlights).cloned();
+
+        Self {
+            language: buffer.map_or(language::PLAIN_TEXT, Buffer::language),
+            theme: theme::text_editor::highlighter(theme),
+            revision: highlights
+                .as_ref()
+                .map_or(0, |highlights| read(highlights).revision()),
+            highlights,
+            folds: buffer
+                .map(|buffer| buffer.folds.clone())
+                .unwrap_or_default(),
+        }
+    }
+
+    fn syntect(&self) -> syntect::Settings {
+        syntect::Settings {
+            theme: self.theme,
+            token: self.language.token().to_owned(),
+        }
+    }
+}
+
+impl PartialEq for Settings {
+    fn eq(&self, other: &Self) -> bool {
+        // The highlights are patched in place, their revision tells whether
+        // they changed.
+        self.language == other.language
+            && self.theme == other.theme
+            && self.revision == other.revision
+            && self.folds == other.folds
+            && match (&self.highlights, &other.highlights) {
+                (Some(a), Some(b)) => Arc::ptr_eq(a, b),
+                (None, None) => true,
+                _ => false,
+            }
+    }
+}
+
+pub enum Highlighter {
+    Syntax {
+        highlights: Arc<RwLock<Highlights>>,
+        revision: u64,
+        folds: Folds,
+        current_line: usize,
+    },
+    Syntect(syntect::Highlighter),
+}
+
+pub enum Highlight {
+    Syntax(Kind),
+    Syntect(syntect::Highlight),
+}
+
+impl highlighter::Highlighter for Highlighter {
+    type Settings = Settings;
+    type Highlight = Highlight;
+    type Iterator<'a> = Box<dyn Iterator<Item = (Range<usize>, Highlight)> + 'a>;
+
+    fn new(settings: &Self::Settings) -> Self {
+        match &settings.highlights {
+            Some(highlights) => Self::Syntax {
+                highlights: highlights.clone(),
+                revision: settings.revision,
+                folds: settings.folds.clone(),
+                current_line: 0,
+            },
+            None => Self::Syntect(syntect::Highlighter::new(&settings.syntect())),
+        }
+    }
+
+    fn update(&mut self, new_settings: &Self::Settings) {
+        match (&mut *self, &new_settings.highlights) {
+            (
+                Self::Syntax {
+                    highlights,
+                    revision,
+                    folds,
+                    current_line,
+                },
+                Some(new),
+            ) if Arc::ptr_eq(highlights, new) && *folds == new_settings.folds => {
+                // Only the lines from the first one whose highlights changed
+                // are highlighted again.
+                if let Some(line) = read(highlights).changed_since(*revision) {
+                    *current_line = (*current_line).min(folds.to_view(line));
+                }
+
+                *revision = new_settings.revision;
+            }
+            (Self::Syntect(highlighter), None) => {
+                highlighter.update(&new_settings.syntect());
+            }
+            _ => *self = Self::new(new_settings),
+        }
+    }
+
+    fn change_line(&mut self, line: usize) {
+        match self {
+            Self::Syntax { current_line, .. } => *current_line = (*current_line).min(line),
+            Self::Syntect(highlighter) => highlighter.change_line(line),
+        }
+    }
+
+    fn highlight_line(&mut self, line: &str) -> Self::Iterator<'_> {
+        match self {
+            Self::Syntax {
+                highlights,
+                folds,
+                current_line,
+                ..
+            } => {
+                let spans = read(highlights)
+                    .line(folds.to_document(*current_line))
+                    .to_vec();
+                *current_line += 1;
+
+                Box::new(
+                    spans
+                        .into_iter()
+                        .map(|(range, kind)| (range, Highlight::Syntax(kind))),
+                )
+            }
+            Self::Syntect(highlighter) => Box::new(
+                highlighter
+                    .highlight_line(line)
+                    .map(|(range, highlight)| (range, Highlight::Syntect(highlight))),
+            ),
+        }
+    }
+
+    fn current_line(&self) -> usize {
+        match self {
+            Self::Syntax { current_line, .. } => *current_line,
+            Self::Syntect(highlighter) => highlighter.current_line(),
+        }
+    }
+}
+
+pub fn format(highlight: &Highlight, theme: &Theme) -> Format<Font> {
+    match highlight {
+        Highlight::Syntax(kind) => theme::text_editor::highlight(*kind, theme),
+        Highlight::Syntect(highlight) => highlight.to_format(),
+    }
+}
+
+fn read(highlights: &RwLock<Highlights>) -> RwLockReadGuard<'_, Highlights> {
+    highlights.read().unwrap_or_else(PoisonError::into_inner)
+}
@@ -1,32 +1,57 @@
 #![allow(clippy::large_enum_variant, clippy::too_many_arguments)]
 
 mod appearance;
+mod buffer;
 mod event;
 mod font;
+mod gutter;
+mod highlighter;
 mod icon;
 mod modal;
+mod pane;
+mod tabs;
 mod widget;
 mod window;
+mod wrap;
 
 use std::env;
+use std::fmt;
 use std::io;
+use std::ops::Range;
 use std::path::{Path, PathBuf};
 use std::sync::Arc;
+use std::time::Duration;
 
 use appearance::{Theme, theme};
 use clap::Parser;
+use data::buffer::{Checkpoint, Position, Snapshot};
+use data::caret::{self, Block, Caret, Cell};
+use data::command::{self, Command};
 use data::config::{self, Config};
+use data::context::Context;
+use data::encoding::Encoding;
 use data::environment;
-use iced::keyboard;
-use iced::widget::{column, container, horizontal_space, row, text, text_editor};
-use iced::{Fill, Subscription, Task};
+use data::line_ending::{self, LineEnding};
+use data::palette;
+use data::shortcut::{KeyBind, KeySequence};
+use data::title::{self, Title};
+use iced::widget::{
+    PaneGrid, button, column, container, horizontal_space, pane_grid, row, scrollable, text,
+    text_editor,
+};
+use iced::{Fill, Subscription, Task, keyboard};
 use tokio::runtime;
-use tracing::{debug, error, info};
+use tracing::{error, info, warn};
 
+use self::buffer::Buffer;
 use self::event::{Event, events};
 use self::modal::Modal;
-use self::widget::Element;
+use self::modal::unsaved_changes;
+use self::pane::Pane;
+use self::tabs::Tabs;
+use self::widget::{Element, TitleBar};
 use self::window::Window;
+use self::wrap::Wrap;
 
 #[derive(Parser, Debug)]
 #[clap(name = "tsu")]
@@ -49,10 +74,18 @@ struct Args {
     /// File to open
     #[arg(default_value = "")]
     file: String,
+    /// Validate the config and themes, then exit
+    #[arg(long, help = "Validates the config file and themes, then exits")]
+    check_config: bool,
 }
 
 pub fn main() -> Result<(), Box<dyn std::error::Error>> {
     let args = Args::parse();
+
+    if args.check_config {
+        return check_config();
+    }
+
     let user_level = match args.verbose {
         0 => "warn",
         1 => "info",
@@ -72,7 +105,7 @@ pub fn main() -> Result<(), Box<dyn std::error::Error>> {
     info!("tsu config dir: {:?}", environment::config_dir());
     info!("tsu data dir: {:?}", environment::data_dir());
 
-    let (config_load, window_load) = {
+    let (config_load, window_load, palette_load, session_load) = {
         let rt = runtime::Builder::new_current_thread()
             .enable_all()
             .build()?;
@@ -80,8 +113,10 @@ pub fn main() -> Result<(), Box<dyn std::error::Error>> {
         rt.block_on(async {
             let config = Config::load().await;
             let window = data::Window::load().await;
+            let palette = data::palette::History::load().await;
+            let session = data::Session::load().await;
 
-            (config, window)
+            (config, window, palette, session)
         })
     };
 
@@ -91,7 +126,15 @@ pub fn main() -> Result<(), Box<dyn std::error::Error>> {
     let settings = settings(&config_load);
 
     iced::daemon(
-        move || Tsu::new(filename.clone(), window_load.clone()),
+        move || {
+            Tsu::new(
+                filename.clone(),
+                config_load.clone(),
+                window_load.clone(),
+                palette_load.clone(),
+                session_load.clone(),
+            )
+        },
         Tsu::update,
         Tsu::view,
     )
@@ -105,6 +148,33 @@ pub fn main() -> Result<(), Box<dyn std::error::Error>> {
     Ok(())
 }
 
+/// Prints every problem found in the config file and themes, exiting with a
+/// non-zero code if any of them is an error.
+fn check_config() -> Result<(), Box<dyn std::error::Error>> {
+    let report = runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()?
+        .block_on(config::check::run());
+
+    for diagnostic in &report.diagnostics {
+        eprintln!("{diagnostic}");
+    }
+
+    let errors = report.count(config::check::Severity::Error);
+    let warnings = report.count(config::check::Severity::Warning);
+
+    println!(
+        "{}: {errors} error(s), {warnings} warning(s)",
+        Config::path().display()
+    );
+
+    if report.has_errors() {
+        std::process::exit(1);
+    }
+
+    Ok(())
+}
+
 fn settings(config_load: &Result<Config, config::Error>) -> iced::Settings {
     let default_text_size = config_load
         .as_ref()
@@ -121,36 +191,98 @@ fn settings(config_load: &Result<Config, config::Error>) -> iced::Settings {
     }
 }
 
+/// How long to wait for the next key of a chord before giving up on it.
+const CHORD_TIMEOUT: Duration = Duration::from_millis(1500);
+
+/// The padding around the text of the editors, which the gutter lines up
+/// with.
+const EDITOR_PADDING: f32 = 5.0;
+
+/// The height of a line of the editors, relative to the text size.
+const LINE_HEIGHT: f32 = 1.3;
+
 struct Tsu {
-    file: Option<PathBuf>,
-    content: text_editor::Content,
+    tabs: Tabs,
+    panes: pane_grid::State<Pane>,
+    focus: pane_grid::Pane,
+    commands: command::Registry,
+    /// The keys of a chord pressed so far.
+    chord: Vec<KeyBind>,
+    /// Bumped for every key of a chord, so only the latest timeout fires.
+    chord_generation: u64,
+    /// Whether the editor of the focused pane has keyboard focus, as far as
+    /// key bindings are concerned.
+    editor_focused: bool,
+    /// The keyboard modifiers held, alt+click places a cursor.
+    modifiers: keyboard::Modifiers,
+    /// The text last copied from a rectangular selection, pasted back as one.
+    block_clipboard: Option<String>,
+    palette_history: palette::History,
+    session: data::Session,
     theme: Theme,
+    text_size: f32,
+    line_numbers: config::LineNumbers,
+    /// The template of the title of the main window.
+    title: Title,
+    /// The directory of the project being edited, the one tsu was started
+    /// in until a file outside of it is opened.
+    root: PathBuf,
+    /// The name of the project, shown in the title.
+    project: String,
     word_wrap: bool,
     is_loading: bool,
-    is_dirty: bool,
+    /// Where the cursor of the focused pane was before the palette started
+    /// previewing positions in it.
+    preview_origin: Option<Position>,
+    /// Continued once the buffer currently being saved has been written.
+    after_save: Option<unsaved_changes::Action>,
+    /// Why the last save failed, shown in the status row until the next one.
+    save_error: Option<String>,
     modal: Option<Modal>,
     main_window: Window,
 }
 
 #[derive(Debug, Clone)]
 pub enum Message {
-    ActionPerformed(text_editor::Action),
+    ActionPerformed(pane_grid::Pane, text_editor::Action),
     ThemeSelected(Theme),
     Event(window::Id, Event),
     Window(window::Id, window::Event),
     NewFile,
     OpenFile,
-    FileOpened(Result<(PathBuf, Arc<String>), Error>),
+    FileOpened(Result<(PathBuf, data::Buffer, Encoding), Error>),
+    FileReopened(buffer::Id, Result<(PathBuf, data::Buffer, Encoding), Error>),
     SaveFile,
-    FileSaved(Result<PathBuf, Error>),
+    FileSaved(
+        buffer::Id,
+        Checkpoint,
+        data::history::History,
+        Encoding,
+        Result<PathBuf, Error>,
+    ),
+    Tabs(tabs::Message),
+    Pane(pane::Message),
+    Shortcut(Command),
+    Chord(KeyBind),
+    ChordTimedOut(u64),
     Modal(modal::Message),
-    OpenedCommandPalette,
+    OpenPalette(palette::Mode),
+    PaletteHistorySaved(Result<(), palette::Error>),
+    SessionSaved(Result<(), data::session::Error>),
+    HistoryLoaded(
+        buffer::Id,
+        Result<data::history::History, data::history::Error>,
+    ),
+    HistorySaved(Result<(), data::history::Error>),
 }
 
 impl Tsu {
     fn new(
         filename: String,
+        config_load: Result<Config, config::Error>,
         window_load: Result<data::Window, window::Error>,
+        palette_load: Result<palette::History, palette::Error>,
+        session_load: Result<data::Session, data::session::Error>,
     ) -> (Self, Task<Message>) {
         let data::Window { size, position } = window_load.unwrap_or_default();
         let position = position.map(window::Position::Specific).unwrap_or_default();
@@ -165,20 +297,57 @@ impl Tsu {
 
         let main_window = Window::new(main_window);
 
+        let config = config_load.unwrap_or_else(|error| {
+            if !error.is_missing() {
+                error!("failed to load config: {error}");
+            }
+
+            Config::default()
+        });
+
+        for skipped in config.keyboard.skipped() {
+            warn!("skipped key binding {skipped}");
+        }
+
+        for conflict in config.keyboard.conflicts() {
+            warn!("{conflict}");
+        }
+
+        let root = env::current_dir().unwrap_or_default();
+
+        let tabs = Tabs::default();
+        let (panes, focus) = pane_grid::State::new(Pane::new(tabs.active(), Default::default()));
+
         let commands = vec![
             open_main_window.then(|_| Task::none()),
-            Task::perform(load_file(filename), Message::FileOpened),
+            Task::perform(load_file(filename, None), Message::FileOpened),
             iced::widget::focus_next(),
         ];
 
         (
             Self {
-                file: None,
-                content: text_editor::Content::new(),
+                tabs,
+                panes,
+                focus,
+                commands: command::Registry::new(config.keyboard.shortcuts()),
+                chord: vec![],
+                chord_generation: 0,
+                editor_focused: true,
+                modifiers: keyboard::Modifiers::default(),
+                block_clipboard: None,
+                palette_history: palette_load.unwrap_or_default(),
+                session: session_load.unwrap_or_default(),
                 theme: appearance::Theme::default(),
+                text_size: config.font.size.map_or(theme::TEXT_SIZE, f32::from),
+                line_numbers: config.editor.line_numbers,
+                title: config.window.title,
+                project: data::project::name(&root),
+                root,
                 word_wrap: true,
                 is_loading: true,
-                is_dirty: false,
+                preview_origin: None,
+                after_save: None,
+                save_error: None,
                 modal: None,
                 main_window,
             },
@@ -186,25 +355,67 @@ impl Tsu {
         )
     }
 
+    /// The title of the window, asked for again after every update, so it
+    /// follows saves, renames and the focused pane as they happen.
     fn title(&self, _window_id: window::Id) -> String {
-        String::from("tsu")
+        let buffer = self.tabs.active();
+
+        self.title.format(title::Fields {
+            is_dirty: buffer.is_dirty(),
+            filename: &buffer.title(),
+            path: buffer.path.as_deref(),
+            project: &self.project,
+        })
     }
 
     fn update(&mut self, message: Message) -> Task<Message> {
         match message {
-            Message::ActionPerformed(action) => {
-                self.is_dirty = self.is_dirty || action.is_edit();
+            Message::ActionPerformed(id, action) => {
+                self.set_focus(id);
+                self.editor_focused = true;
+                self.perform(id, action);
 
-                self.content.perform(action);
-
-                Task::none()
+                self.scroll_to_cursor(id)
             }
             Message::ThemeSelected(theme) => {
                 self.theme = theme;
 
                 Task::none()
             }
-            Message::Event(_window, _event) => Task::none(),
+            Message::Event(_window, event) => match event {
+                Event::KeyPressed {
+                    key,
+                    modifiers,
+                    captured,
+                } => {
+                    if let Some(Modal::CommandPalette(_)) = &self.modal
+                        && let Some(message) =
+                            modal::command_palette::State::key_pressed(&key, modifiers)
+                    {
+                        return self
+                            .update(Message::Modal(modal::Message::CommandPalette(message)));
+                    }
+
+                    // Keys captured by the focused editor already went through
+                    // its key binding, while a modal is open they are
+                    // captured by its inputs instead.
+                    if captured && self.modal.is_none() {
+                        return Task::none();
+                    }
+
+                    self.key_pressed(KeyBind::from((key, modifiers)))
+                }
+                Event::LeftClick => {
+                    self.editor_focused = false;
+
+                    Task::none()
+                }
+                Event::ModifiersChanged(modifiers) => {
+                    self.modifiers = modifiers;
+
+                    Task::none()
+                }
+            },
             Message::Window(id, event) => {
                 if id == self.main_window.id {
                     match event {
@@ -224,7 +435,7 @@ impl Tsu {
                             self.main_window.opened(position, size);
                         }
                         window::Event::CloseRequested => {
-                            return iced::exit();
+                            return self.confirm(unsaved_changes::Action::CloseWindow);
                         }
                     }
                     Task::none()
@@ -234,11 +445,10 @@ impl Tsu {
             }
             Message::NewFile => {
                 if !self.is_loading {
-                    self.file = None;
-                    self.content = text_editor::Content::new();
+                    self.tabs.open(Buffer::default());
                 }
 
-                Task::none()
+                self.sync_panes()
             }
             Message::OpenFile => {
                 if self.is_loading {
@@ -251,41 +461,222 @@ impl Tsu {
             }
             Message::FileOpened(result) => {
                 self.is_loading = false;
-                self.is_dirty = false;
 
-                if let Ok((path, contents)) = result {
-                    self.file = Some(path);
-                    self.content = text_editor::Content::with_text(&contents);
+                let (path, document, encoding) = match result {
+                    Ok(opened) => opened,
+                    Err(error) => {
+                        if let Error::Encoding(error) = error {
+                            error!("failed to open file: {error}");
+                        }
+
+                        return self.sync_panes();
+                    }
+                };
+
+                if path.is_absolute()
+                    && !path.starts_with(&self.root)
+                    && let Some(parent) = path.parent()
+                {
+                    self.root = parent.to_path_buf();
+                    self.project = data::project::name(&self.root);
+                }
+
+                let mut buffer = Buffer::new(Some(path.clone()), document);
+                buffer.encoding = encoding;
+                buffer.restore_folds(self.session.folds(&path));
+
+                if buffer.line_endings().is_mixed() {
+                    warn!("{} has mixed line endings", path.display());
+                }
+
+                let load_history = load_history(&buffer, path);
+
+                self.tabs.open(buffer);
+
+                Task::batch([self.sync_panes(), load_history])
+            }
+            Message::FileReopened(id, result) => {
+                self.is_loading = false;
+
+                match (result, self.tabs.get_mut(id)) {
+                    (Ok((path, document, encoding)), Some(buffer)) => {
+                        buffer.reopen(document, encoding);
+                        buffer.restore_folds(self.session.folds(&path));
+
+                        let load_history = load_history(buffer, path);
+
+                        self.reload_panes(id, None);
+
+                        return load_history;
+                    }
+                    (Err(Error::Encoding(error)), _) => {
+                        error!("failed to reopen file: {error}");
+                    }
+                    _ => {}
+                }
+
+                Task::none()
+            }
+            Message::SaveFile => self.save(self.tabs.active().id),
+            Message::FileSaved(id, checkpoint, history, encoding, result) => {
+                self.is_loading = false;
+
+                match (result, self.tabs.get_mut(id)) {
+                    (Ok(path), Some(buffer)) => {
+                        buffer.path = Some(path);
+                        buffer.encoding = encoding;
+                        buffer.mark_saved(checkpoint);
+                        buffer.detect_language();
+
+                        self.reload_panes(id, None);
+
+                        // The folds and the history are remembered for the
+                        // text that was saved, which is what the file will
+                        // open with.
+                        let save_session = Task::batch([
+                            self.save_folds(id),
+                            self.save_history(id, checkpoint, history),
+                        ]);
+
+                        if let Some(action) = self.after_save {
+                            return Task::batch([save_session, self.save_next(action)]);
+                        }
+
+                        return save_session;
+                    }
+                    (Err(error), _) => {
+                        if !matches!(error, Error::DialogClosed) {
+                            error!("failed to save file: {error}");
+                            self.save_error = Some(format!("Failed to save: {error}"));
+                        }
+
+                        // What was waiting for the save doesn't happen, the
+                        // changes are asked about again instead.
+                        if let Some(action) = self.after_save.take() {
+                            return self.confirm(action);
+                        }
+                    }
+                    (Ok(_), None) => {
+                        self.after_save = None;
+                    }
                 }
 
                 Task::none()
             }
-            Message::SaveFile => {
-                if self.is_loading {
+            Message::Tabs(message) => {
+                let closing = match message {
+                    tabs::Message::Close(index) => self.tabs.iter().nth(index),
+                    tabs::Message::CloseActive => Some(self.tabs.active()),
+                    _ => None,
+                };
+
+                if let Some(buffer) = closing.filter(|buffer| buffer.is_dirty()) {
+                    return self.confirm(unsaved_changes::Action::CloseTab(buffer.id));
+                }
+
+                self.tabs.update(message);
+
+                self.sync_panes()
+            }
+            Message::Pane(message) => match message {
+                pane::Message::Clicked(id) => {
+                    self.set_focus(id);
+
                     Task::none()
-                } else {
-                    self.is_loading = true;
+                }
+                pane::Message::Dragged(pane_grid::DragEvent::Dropped { pane, target }) => {
+                    self.panes.drop(pane, target);
 
-                    let mut text = self.content.text();
+                    Task::none()
+                }
+                pane::Message::Dragged(_) => Task::none(),
+                pane::Message::Resized(pane_grid::ResizeEvent { split, ratio }) => {
+                    self.panes.resize(split, ratio);
 
-                    if let Some(ending) = self.content.line_ending() {
-                        if !text.ends_with(ending.as_str()) {
-                            text.push_str(ending.as_str());
-                        }
+                    Task::none()
+                }
+                pane::Message::Split(axis, id) => {
+                    let Some(pane) = self.panes.get(id) else {
+                        return Task::none();
+                    };
+                    let Some(buffer) = self.tabs.get(pane.buffer) else {
+                        return Task::none();
+                    };
+
+                    let pane = Pane::new(buffer, pane.cursor(buffer));
+
+                    match self.panes.split(axis, id, pane) {
+                        Some((new, _)) => self.focus_pane(new),
+                        None => Task::none(),
+                    }
+                }
+                pane::Message::Close(id) => {
+                    if self.panes.len() > 1
+                        && let Some((_, sibling)) = self.panes.close(id)
+                    {
+                        return self.focus_pane(sibling);
+                    }
+
+                    Task::none()
+                }
+                pane::Message::Maximize(id) => {
+                    self.panes.maximize(id);
+
+                    self.focus_pane(id)
+                }
+                pane::Message::Restore => {
+                    self.panes.restore();
+
+                    Task::none()
+                }
+                pane::Message::Scrolled(id, viewport) => {
+                    if let Some(pane) = self.panes.get_mut(id) {
+                        pane.viewport = viewport;
+                    }
+
+                    Task::none()
+                }
+                pane::Message::SelectLine(id, line) => {
+                    self.set_focus(id);
+
+                    if let Some(pane) = self.panes.get_mut(id)
+                        && let Some(buffer) = self.tabs.get(pane.buffer)
+                    {
+                        buffer.select(&mut pane.content, buffer.line_range(line));
+                        pane.expansions.clear();
                     }
 
-                    Task::perform(save_file(self.file.clone(), text), Message::FileSaved)
+                    self.focus_pane(id)
+                }
+                pane::Message::ToggleFold(id, line) => {
+                    self.set_focus(id);
+
+                    self.fold(|buffer| buffer.toggle_fold(line))
                 }
+            },
+            Message::Shortcut(command) => {
+                self.editor_focused = true;
+
+                self.execute(command)
             }
-            Message::FileSaved(result) => {
-                self.is_loading = false;
+            Message::Chord(key_bind) => {
+                self.editor_focused = true;
 
-                if let Ok(path) = result {
-                    self.file = Some(path);
-                    self.is_dirty = false;
+                self.key_pressed(key_bind)
+            }
+            Message::ChordTimedOut(generation) => {
+                if generation != self.chord_generation || self.chord.is_empty() {
+                    return Task::none();
                 }
 
-                Task::none()
+                let keys = std::mem::take(&mut self.chord);
+
+                match self.commands.lookup(&keys, &self.context()) {
+                    command::Lookup::Pending {
+                        exact: Some(command),
+                    } => self.execute(command),
+                    _ => Task::none(),
+                }
             }
             Message::Modal(message) => {
                 let Some(modal) = &mut self.modal else {
@@ -293,26 +684,158 @@ impl Tsu {
                 };
 
                 let (command, event) = modal.update(&message);
+                let command = command.map(Message::Modal);
 
-                if let Some(event) = event {
-                    match event {
-                        modal::Event::CloseModal => {
-                            self.modal = None;
-                        }
+                let Some(event) = event else {
+                    return command;
+                };
+
+                if let modal::Event::Preview(position) = event {
+                    self.preview(position);
+
+                    return Task::batch([command, self.scroll_to_cursor(self.focus)]);
+                }
+
+                self.modal = None;
+
+                match event {
+                    modal::Event::CloseModal => {
+                        self.end_preview();
+
+                        command
+                    }
+                    modal::Event::ExecuteCommand(executed) => {
+                        self.end_preview();
+                        self.palette_history.record(executed.id());
+
+                        Task::batch([command, self.save_palette_history(), self.execute(executed)])
+                    }
+                    modal::Event::OpenFile(path) => {
+                        self.end_preview();
+                        self.palette_history.record(palette::file_key(&path));
+
+                        let open = if self.is_loading {
+                            Task::none()
+                        } else {
+                            self.is_loading = true;
+
+                            Task::perform(load_file(path, None), Message::FileOpened)
+                        };
+
+                        Task::batch([command, self.save_palette_history(), open])
+                    }
+                    modal::Event::GoTo(position) => {
+                        self.preview_origin = None;
+                        self.go_to(position);
+
+                        Task::batch([
+                            command,
+                            self.focus_pane(self.focus),
+                            self.scroll_to_cursor(self.focus),
+                        ])
+                    }
+                    modal::Event::Preview(_) => command,
+                    modal::Event::SetLanguage(language) => {
+                        self.end_preview();
+                        self.tabs.active_mut().set_language_override(language);
+                        self.reload_panes(self.tabs.active().id, None);
+
+                        Task::batch([command, self.focus_pane(self.focus)])
+                    }
+                    modal::Event::TravelHistory(node) => {
+                        Task::batch([command, self.step_history(|buffer| buffer.travel(node))])
+                    }
+                    modal::Event::TravelHistoryTo(time) => Task::batch([
+                        command,
+                        self.step_history(|buffer| buffer.travel_to_time(time)),
+                    ]),
+                    modal::Event::ReopenWithEncoding(encoding) => {
+                        let id = self.tabs.active().id;
+
+                        Task::batch([
+                            command,
+                            self.confirm(unsaved_changes::Action::Reopen(id, encoding)),
+                        ])
                     }
+                    modal::Event::SaveWithEncoding(encoding) => {
+                        Task::batch([command, self.save_with(self.tabs.active().id, encoding)])
+                    }
+                    modal::Event::Save(action) => {
+                        self.after_save = Some(action);
+
+                        Task::batch([command, self.save_next(action)])
+                    }
+                    modal::Event::Discard(action) => Task::batch([command, self.proceed(action)]),
                 }
+            }
+            Message::OpenPalette(mode) => {
+                let commands = self
+                    .commands
+                    .entries()
+                    // Nothing to show for the first two until there is a
+                    // sidebar and a theme editor, the others only make sense
+                    // from the keyboard.
+                    .filter(|entry| {
+                        !matches!(
+                            entry.command,
+                            Command::ToggleSidebar
+                                | Command::ThemeEditor
+                                | Command::UnfocusEditor
+                                | Command::CloseModal
+                        )
+                    })
+                    .collect();
+
+                let buffer = self.tabs.active();
+
+                let (state, task) = modal::command_palette::State::new(
+                    mode,
+                    commands,
+                    buffer.document.clone(),
+                    buffer.language(),
+                    buffer.encoding,
+                    env::current_dir().unwrap_or_default(),
+                    self.palette_history.clone(),
+                );
+
+                self.modal = Some(Modal::CommandPalette(state));
 
-                command.map(Message::Modal)
+                task.map(|message| Message::Modal(modal::Message::CommandPalette(message)))
             }
-            Message::OpenedCommandPalette => {
-                self.modal = Some(Modal::CommandPalette(modal::command_palette::State::new(
-                    vec![
-                        "Copy".into(),
-                        "Close Window".into(),
-                        "Cut".into(),
-                        "Paste".into(),
-                    ],
-                )));
+            Message::PaletteHistorySaved(result) => {
+                if let Err(error) = result {
+                    error!("failed to save palette history: {error}");
+                }
+
+                Task::none()
+            }
+            Message::HistoryLoaded(id, result) => {
+                match (result, self.tabs.get_mut(id)) {
+                    // Edits made while the history was loading would not be
+                    // part of it.
+                    (Ok(history), Some(buffer)) if buffer.history.is_empty() => {
+                        buffer.history = history;
+                    }
+                    (Err(error), _) if !error.is_missing() => {
+                        error!("failed to load undo history: {error}");
+                    }
+                    _ => {}
+                }
+
+                Task::none()
+            }
+            Message::HistorySaved(result) => {
+                if let Err(error) = result {
+                    error!("failed to save undo history: {error}");
+                }
+
+                Task::none()
+            }
+            Message::SessionSaved(result) => {
+                if let Err(error) = result {
+                    error!("failed to save session: {error}");
+                }
+
                 Task::none()
             }
         }
@@ -321,7 +844,7 @@ impl Tsu {
     fn view(&self, id: window::Id) -> Element<Message> {
         if id == self.main_window.id {
             let status = row![
-                text(if let Some(path) = &self.file {
+                text(if let Some(path) = &self.tabs.active().path {
                     let path = path.display().to_string();
 
                     if path.len() > 60 {
@@ -333,50 +856,45 @@ impl Tsu {
                     String::from("New file")
                 }),
                 horizontal_space(),
+                text(self.save_error.clone().unwrap_or_default()).style(theme::text::error),
+                text(if self.chord.is_empty() {
+                    String::new()
+                } else {
+                    format!("{}, \u{2026}", KeySequence::from(self.chord.clone()))
+                }),
                 text({
-                    let (line, column) = self.content.cursor_position();
+                    let cursor = self
+                        .panes
+                        .get(self.focus)
+                        .map(|pane| pane.cursor(self.tabs.active()))
+                        .unwrap_or_default();
 
-                    format!("{}:{}", line + 1, column + 1)
-                })
+                    format!("{}:{}", cursor.line + 1, cursor.column + 1)
+                }),
+                line_endings(self.tabs.active().line_endings()),
+                button(text(self.tabs.active().encoding.to_string()))
+                    .padding(0)
+                    .style(theme::button::bare)
+                    .on_press(Message::OpenPalette(palette::Mode::ReopenWithEncoding)),
+                button(text(self.tabs.active().language().name))
+                    .padding(0)
+                    .style(theme::button::bare)
+                    .on_press(Message::OpenPalette(palette::Mode::Languages)),
             ]
             .spacing(10);
 
+            let panes = PaneGrid::new(&self.panes, |id, pane, is_maximized| {
+                self.view_pane(id, pane, is_maximized)
+            })
+            .spacing(4)
+            .on_click(|pane| Message::Pane(pane::Message::Clicked(pane)))
+            .on_drag(|event| Message::Pane(pane::Message::Dragged(event)))
+            .on_resize(10, |event| Message::Pane(pane::Message::Resized(event)));
+
             let base = container(
-                column![
-                    text_editor(&self.content)
-                        .height(Fill)
-                        .on_action(Message::ActionPerformed)
-                        .wrapping(if self.word_wrap {
-                            text::Wrapping::Word
-                        } else {
-                            text::Wrapping::None
-                        })
-                        .key_binding(|key_press| {
-                            match key_press.key.as_ref() {
-                                keyboard::Key::Character("s") if key_press.modifiers.control() => {
-                                    debug!("CTRL + S pressed");
-                                    Some(text_editor::Binding::Custom(Message::SaveFile))
-                                }
-                                keyboard::Key::Named(keyboard::key::Named::Escape) => {
-                                    debug!("ESC pressed");
-                                    Some(text_editor::Binding::Unfocus)
-                                }
-                                keyboard::Key::Character("p")
-                                    if key_press.modifiers.shift()
-                                        && key_press.modifiers.control() =>
-                                {
-                                    debug!("CTRL + SHIFT + P pressed");
-                                    Some(text_editor::Binding::Custom(
-                                        Message::OpenedCommandPalette,
-                                    ))
-                                }
-                                _ => text_editor::Binding::from_key_press(key_press),
-                            }
-                        }),
-                    status,
-                ]
-                .spacing(10)
-                .padding(10),
+                column![self.tabs.view().map(Message::Tabs), panes, status]
+                    .spacing(10)
+                    .padding(10),
             );
 
             let modal = &self.modal;
@@ -397,63 +915,1237 @@ impl Tsu {
         }
     }
 
-    fn theme(&self, _window_id: window::Id) -> Theme {
-        self.theme.clone()
-    }
+    /// The decorations of the editor of `pane`, which shows `buffer`, in the
+    /// lines of its content.
+    fn decorations(&self, pane: &Pane, buffer: &Buffer) -> widget::editor::Decorations {
+        let colors = self.theme.colors();
+        let mut decorations = widget::editor::Decorations::default();
 
-    fn subscription(&self) -> Subscription<Message> {
-        let subscriptions = vec![
-            events().map(|(window, event)| Message::Event(window, event)),
-            window::events().map(|(window, event)| Message::Window(window, event)),
-        ];
+        if colors.editor.current_line.a > 0.0 {
+            let line = buffer::cursor(&pane.content).line;
 
-        Subscription::batch(subscriptions)
+            decorations.push(
+                Position::new(line, 0)..Position::new(line + 1, 0),
+                widget::editor::Style::Background(colors.editor.current_line),
+            );
+        }
+
+        for caret in &pane.carets {
+            if buffer.folds.is_hidden(caret.head.line) {
+                continue;
+            }
+
+            let view = |position: Position| {
+                Position::new(buffer.folds.to_view(position.line), position.column)
+            };
+            let range = caret.range();
+
+            if caret.is_selection() {
+                decorations.push(
+                    view(range.start)..view(range.end),
+                    widget::editor::Style::Background(colors.editor.selection),
+                );
+            }
+
+            decorations.push(
+                view(caret.head)..view(caret.head),
+                widget::editor::Style::Caret(colors.editor.cursor),
+            );
+        }
+
+        for region in buffer.folds.regions() {
+            if buffer.folds.is_hidden(region.start) {
+                continue;
+            }
+
+            let line = buffer.folds.to_view(region.start);
+            let end = Position::new(
+                line,
+                pane.content.line(line).map_or(0, |line| line.text.len()),
+            );
+
+            decorations.push(
+                end..end,
+                widget::editor::Style::Glyph(" \u{22ef}", colors.text.tertiary),
+            );
+        }
+
+        decorations
     }
-}
 
-#[derive(Debug, Clone)]
-pub enum Error {
-    DialogClosed,
-    IoError(io::ErrorKind),
-}
+    fn view_pane<'a>(
+        &'a self,
+        id: pane_grid::Pane,
+        pane: &'a Pane,
+        is_maximized: bool,
+    ) -> widget::Content<'a, Message> {
+        let is_focused = id == self.focus;
 
-async fn open_file() -> Result<(PathBuf, Arc<String>), Error> {
-    let picked_file = rfd::AsyncFileDialog::new()
-        .set_title("Open a text file")
-        .pick_file()
-        .await
-        .ok_or(Error::DialogClosed)?;
+        let title = self
+            .tabs
+            .get(pane.buffer)
+            .map_or_else(String::new, |buffer| {
+                if buffer.is_dirty() {
+                    format!("\u{2022} {}", buffer.title())
+                } else {
+                    buffer.title()
+                }
+            });
 
-    load_file(picked_file).await
-}
+        let control = |label, message| {
+            button(text(label).style(theme::text::secondary))
+                .padding([0, 4])
+                .style(theme::button::bare)
+                .on_press(Message::Pane(message))
+        };
 
-async fn load_file(path: impl Into<PathBuf>) -> Result<(PathBuf, Arc<String>), Error> {
-    let path = path.into();
+        let mut controls = row![
+            control(
+                "\u{2502}",
+                pane::Message::Split(pane_grid::Axis::Vertical, id)
+            ),
+            control(
+                "\u{2500}",
+                pane::Message::Split(pane_grid::Axis::Horizontal, id)
+            ),
+        ]
+        .spacing(2);
 
-    let contents = tokio::fs::read_to_string(&path)
-        .await
-        .map(Arc::new)
-        .map_err(|error| Error::IoError(error.kind()))?;
+        if self.panes.len() > 1 {
+            controls = controls
+                .push(if is_maximized {
+                    control("\u{25a3}", pane::Message::Restore)
+                } else {
+                    control("\u{25a1}", pane::Message::Maximize(id))
+                })
+                .push(control("\u{00d7}", pane::Message::Close(id)));
+        }
 
-    Ok((path, contents))
-}
+        let title_bar = TitleBar::new(text(title))
+            .controls(Element::from(controls))
+            .padding([2, 6])
+            .style(theme::container::buffer_title_bar);
 
-async fn save_file(path: Option<PathBuf>, contents: String) -> Result<PathBuf, Error> {
-    let path = if let Some(path) = path {
-        path
-    } else {
-        rfd::AsyncFileDialog::new()
-            .save_file()
-            .await
-            .as_ref()
-            .map(rfd::FileHandle::path)
-            .map(Path::to_owned)
-            .ok_or(Error::DialogClosed)?
-    };
+        let buffer = self.tabs.get(pane.buffer);
 
-    tokio::fs::write(&path, contents)
-        .await
-        .map_err(|error| Error::IoError(error.kind()))?;
+        let editor = text_editor(&pane.content)
+            .id(pane.editor.clone())
+            .size(self.text_size)
+            .line_height(text::LineHeight::Relative(LINE_HEIGHT))
+            .padding(EDITOR_PADDING)
+            .on_action(move |action| Message::ActionPerformed(id, action))
+            .wrapping(if self.word_wrap {
+                text::Wrapping::Word
+            } else {
+                text::Wrapping::None
+            })
+            .key_binding(|key_press| self.key_binding(key_press))
+            .highlight_with::<highlighter::Highlighter>(
+                highlighter::Settings::new(buffer, &self.theme),
+                highlighter::format,
+            );
+
+        // The editor grows to fit its text and is scrolled along with the
+        // gutter.
+        let body: Element<'a, Message> = match buffer {
+            Some(buffer) => {
+                let line_height = self.line_height();
+                let viewport = pane.viewport;
+                let height = if viewport.height > 0.0 {
+                    viewport.height
+                } else {
+                    self.main_window.size.height
+                };
+
+                let first = ((viewport.offset - EDITOR_PADDING) / line_height).max(0.0) as usize;
+                let last = ((viewport.offset + height) / line_height).ceil() as usize + 1;
+
+                let wrap = self.wrap(pane, buffer, |content, columns| {
+                    Wrap::down_to_row(content, columns, last)
+                });
+
+                let gutter = gutter::view(
+                    buffer,
+                    wrap.line_at(first)..wrap.line_at(last) + 1,
+                    buffer::cursor(&pane.content).line,
+                    &wrap,
+                    self.gutter_settings(),
+                    move |line| Message::Pane(pane::Message::ToggleFold(id, line)),
+                    move |line| Message::Pane(pane::Message::SelectLine(id, line)),
+                );
+
+                let editor = widget::editor(
+                    editor
+                        .style(theme::text_editor::transparent)
+                        .min_height(viewport.height),
+                    &pane.content,
+                    self.decorations(pane, buffer),
+                    wrap,
+                    widget::editor::Metrics {
+                        text_size: self.text_size,
+                        line_height,
+                        padding: EDITOR_PADDING,
+                    },
+                );
+
+                scrollable(row![gutter, editor])
+                    .id(pane.scrollable.clone())
+                    .height(Fill)
+                    .on_scroll(move |viewport| {
+                        Message::Pane(pane::Message::Scrolled(
+                            id,
+                            pane::Viewport {
+                                offset: viewport.absolute_offset().y,
+                                width: viewport.bounds().width,
+                                height: viewport.bounds().height,
+                            },
+                        ))
+                    })
+                    .into()
+            }
+            None => editor.height(Fill).into(),
+        };
+
+        widget::Content::new(body)
+            .title_bar(title_bar)
+            .style(move |theme| theme::container::buffer(theme, is_focused))
+    }
+
+    fn key_binding(
+        &self,
+        key_press: text_editor::KeyPress,
+    ) -> Option<text_editor::Binding<Message>> {
+        let key_bind = KeyBind::from((key_press.key.clone(), key_press.modifiers));
+
+        // A pending chord swallows every key until it is resolved, Escape
+        // included.
+        if !self.chord.is_empty() && !key_bind.is_modifier() {
+            return Some(text_editor::Binding::Custom(Message::Chord(key_bind)));
+        }
+
+        let context = Context {
+            editor_focused: true,
+            ..self.context()
+        };
+
+        // The clipboard commands are left to the editor so they act on its
+        // own selection, unless there are carets besides its cursor. Escape
+        // then drops the carets before unfocusing the editor.
+        let has_carets = self
+            .panes
+            .get(self.focus)
+            .is_some_and(|pane| !pane.carets.is_empty());
+
+        match self
+            .commands
+            .lookup(std::slice::from_ref(&key_bind), &context)
+        {
+            command::Lookup::Command(Command::Copy) if !has_carets => {
+                Some(text_editor::Binding::Copy)
+            }
+            command::Lookup::Command(Command::Cut) if !has_carets => {
+                Some(text_editor::Binding::Cut)
+            }
+            command::Lookup::Command(Command::Paste) => Some(text_editor::Binding::Paste),
+            command::Lookup::Command(Command::SelectAll) => Some(text_editor::Binding::SelectAll),
+            command::Lookup::Command(Command::UnfocusEditor) if has_carets => Some(
+                text_editor::Binding::Custom(Message::Shortcut(Command::ClearCursors)),
+            ),
+            command::Lookup::Command(Command::UnfocusEditor) => {
+                Some(text_editor::Binding::Sequence(vec![
+                    text_editor::Binding::Unfocus,
+                    text_editor::Binding::Custom(Message::Shortcut(Command::UnfocusEditor)),
+                ]))
+            }
+            command::Lookup::Command(command) => {
+                Some(text_editor::Binding::Custom(Message::Shortcut(command)))
+            }
+            command::Lookup::Pending { .. } => {
+                Some(text_editor::Binding::Custom(Message::Chord(key_bind)))
+            }
+            command::Lookup::None => text_editor::Binding::from_key_press(key_press),
+        }
+    }
+
+    /// Adds `key_bind` to the pending chord and runs the command it completes.
+    ///
+    /// While the keys are still the start of a longer chord the next key is
+    /// waited for, up to [`CHORD_TIMEOUT`].
+    fn key_pressed(&mut self, key_bind: KeyBind) -> Task<Message> {
+        if key_bind.is_modifier() {
+            return Task::none();
+        }
+
+        let mut keys = std::mem::take(&mut self.chord);
+        keys.push(key_bind);
+
+        match self.commands.lookup(&keys, &self.context()) {
+            command::Lookup::Command(command) => self.execute(command),
+            command::Lookup::Pending { .. } => {
+                self.chord = keys;
+                self.chord_generation += 1;
+
+                let generation = self.chord_generation;
+
+                Task::perform(tokio::time::sleep(CHORD_TIMEOUT), move |()| {
+                    Message::ChordTimedOut(generation)
+                })
+            }
+            command::Lookup::None => Task::none(),
+        }
+    }
+
+    /// The state key bindings are resolved against.
+    fn context(&self) -> Context {
+        Context {
+            editor_focused: self.editor_focused && self.modal.is_none(),
+            modal_open: self.modal.is_some(),
+            palette_open: matches!(self.modal, Some(Modal::CommandPalette(_))),
+            has_selection: self
+                .panes
+                .get(self.focus)
+                .is_some_and(|pane| pane.content.selection().is_some()),
+            language: Some(self.tabs.active().language().id.to_string()),
+        }
+    }
+
+    /// Runs `command`, whether it came from a key binding or the palette.
+    fn execute(&mut self, command: Command) -> Task<Message> {
+        let focus = self.focus;
+
+        match command {
+            Command::NewFile => self.update(Message::NewFile),
+            Command::OpenFile => self.update(Message::OpenFile),
+            Command::SaveFile => self.update(Message::SaveFile),
+            Command::Undo => self.step_history(Buffer::undo),
+            Command::Redo => self.step_history(Buffer::redo),
+            Command::Earlier => self.step_history(Buffer::earlier),
+            Command::Later => self.step_history(Buffer::later),
+            Command::UndoHistory => {
+                self.modal = Some(Modal::UndoHistory(modal::undo_history::State::new(
+                    self.tabs.active().history.entries(),
+                )));
+
+                Task::none()
+            }
+            Command::ConvertToLf => self.convert_line_endings(LineEnding::Lf),
+            Command::ConvertToCrlf => self.convert_line_endings(LineEnding::Crlf),
+            Command::Copy => self.copy(),
+            Command::Cut => {
+                let task = self.copy();
+
+                // Carets without a selection, like the ones on lines shorter
+                // than a rectangular selection, have nothing to cut.
+                let edit = if self
+                    .panes
+                    .get(focus)
+                    .is_some_and(|pane| pane.carets.is_empty())
+                {
+                    text_editor::Edit::Delete
+                } else {
+                    text_editor::Edit::Paste(Arc::new(String::new()))
+                };
+
+                self.perform(focus, text_editor::Action::Edit(edit));
+
+                task
+            }
+            Command::Paste => iced::clipboard::read().and_then(move |text| {
+                Task::done(Message::ActionPerformed(
+                    focus,
+                    text_editor::Action::Edit(text_editor::Edit::Paste(Arc::new(text))),
+                ))
+            }),
+            Command::SelectAll => {
+                self.perform(focus, text_editor::Action::SelectAll);
+
+                Task::none()
+            }
+            Command::UnfocusEditor => {
+                self.editor_focused = false;
+
+                Task::none()
+            }
+            Command::ExpandSelection => {
+                self.expand_selection();
+
+                Task::none()
+            }
+            Command::ShrinkSelection => {
+                self.shrink_selection();
+
+                Task::none()
+            }
+            Command::AddCursorAbove => self.add_cursor(text_editor::Motion::Up),
+            Command::AddCursorBelow => self.add_cursor(text_editor::Motion::Down),
+            Command::AddNextOccurrence => self.add_next_occurrence(),
+            Command::SelectAllOccurrences => self.select_all_occurrences(),
+            Command::ColumnSelectUp => self.column_select(text_editor::Motion::Up),
+            Command::ColumnSelectDown => self.column_select(text_editor::Motion::Down),
+            Command::ColumnSelectLeft => self.column_select(text_editor::Motion::Left),
+            Command::ColumnSelectRight => self.column_select(text_editor::Motion::Right),
+            Command::ClearCursors => {
+                if let Some(pane) = self.panes.get_mut(focus) {
+                    pane.carets.clear();
+                    pane.block = None;
+                }
+
+                Task::none()
+            }
+            Command::NextTab => self.update(Message::Tabs(tabs::Message::Next)),
+            Command::PreviousTab => self.update(Message::Tabs(tabs::Message::Previous)),
+            Command::MoveTabLeft => self.update(Message::Tabs(tabs::Message::MoveLeft)),
+            Command::MoveTabRight => self.update(Message::Tabs(tabs::Message::MoveRight)),
+            Command::CloseTab => self.update(Message::Tabs(tabs::Message::CloseActive)),
+            Command::ReopenClosedTab => self.update(Message::Tabs(tabs::Message::ReopenClosed)),
+            Command::SplitHorizontal => self.update(Message::Pane(pane::Message::Split(
+                pane_grid::Axis::Horizontal,
+                focus,
+            ))),
+            Command::SplitVertical => self.update(Message::Pane(pane::Message::Split(
+                pane_grid::Axis::Vertical,
+                focus,
+            ))),
+            Command::ClosePane => self.update(Message::Pane(pane::Message::Close(focus))),
+            Command::ToggleMaximizePane => {
+                self.update(Message::Pane(if self.panes.maximized().is_some() {
+                    pane::Message::Restore
+                } else {
+                    pane::Message::Maximize(focus)
+                }))
+            }
+            Command::MoveUp => self.focus_adjacent(pane_grid::Direction::Up),
+            Command::MoveDown => self.focus_adjacent(pane_grid::Direction::Down),
+            Command::MoveLeft => self.focus_adjacent(pane_grid::Direction::Left),
+            Command::MoveRight => self.focus_adjacent(pane_grid::Direction::Right),
+            Command::CommandPalette => self.update(Message::OpenPalette(palette::Mode::Commands)),
+            Command::GoToFile => self.update(Message::OpenPalette(palette::Mode::Files)),
+            Command::GoToLine => self.update(Message::OpenPalette(palette::Mode::Line)),
+            Command::GoToSymbol => self.update(Message::OpenPalette(palette::Mode::Symbols)),
+            Command::ChangeLanguage => self.update(Message::OpenPalette(palette::Mode::Languages)),
+            Command::ReopenWithEncoding => {
+                self.update(Message::OpenPalette(palette::Mode::ReopenWithEncoding))
+            }
+            Command::SaveWithEncoding => {
+                self.update(Message::OpenPalette(palette::Mode::SaveWithEncoding))
+            }
+            Command::ToggleWordWrap => {
+                self.word_wrap = !self.word_wrap;
+
+                Task::none()
+            }
+            Command::ToggleFold => {
+                let line = self
+                    .panes
+                    .get(focus)
+                    .map(|pane| pane.cursor(self.tabs.active()).line)
+                    .unwrap_or_default();
+
+                self.fold(|buffer| buffer.toggle_fold(line))
+            }
+            Command::FoldAll => self.fold(Buffer::fold_all),
+            Command::UnfoldAll => self.fold(Buffer::unfold_all),
+            Command::CloseModal => self.update(Message::Modal(modal::Message::Cancel)),
+            Command::ToggleFullscreen => window::toggle_fullscreen(),
+            Command::QuitApplication => self.confirm(unsaved_changes::Action::CloseWindow),
+            Command::ToggleSidebar | Command::ThemeEditor => Task::none(),
+        }
+    }
+
+    /// Performs `action` in the pane, keeping the other panes showing the
+    /// same buffer in sync.
+    fn perform(&mut self, id: pane_grid::Pane, action: text_editor::Action) {
+        let Some(pane) = self.panes.get_mut(id) else {
+            return;
+        };
+        let buffer_id = pane.buffer;
+
+        pane.expansions.clear();
+
+        let Some(buffer) = self.tabs.get_mut(buffer_id) else {
+            return;
+        };
+
+        let block = pane.block.take();
+
+        let changed = match action {
+            // Alt+click leaves a caret where the cursor was, and starts a
+            // rectangular selection in case the mouse is dragged.
+            text_editor::Action::Click(_) if self.modifiers.alt() => {
+                let caret = buffer.caret(&pane.content);
+
+                buffer.perform(&mut pane.content, action);
+                pane.carets.push(caret);
+                buffer.merge_carets(&pane.content, &mut pane.carets);
+                pane.block = Some(Block::new(Cell::at(&buffer.document, pane.cursor(buffer))));
+
+                false
+            }
+            text_editor::Action::Drag(_) if block.is_some() => {
+                buffer.perform(&mut pane.content, action);
+
+                let head = Cell::at(&buffer.document, pane.cursor(buffer));
+
+                if let Some(block) = block {
+                    pane.select_block(buffer, Block { head, ..block });
+                }
+
+                false
+            }
+            text_editor::Action::Edit(text_editor::Edit::Paste(text))
+                if pane.carets.is_empty()
+                    && self.block_clipboard.as_deref() == Some(text.as_str()) =>
+            {
+                buffer.paste_block(&mut pane.content, &mut pane.carets, &text)
+            }
+            _ => buffer.perform_carets(&mut pane.content, &mut pane.carets, action),
+        };
+
+        if changed {
+            self.reload_panes(buffer_id, Some(id));
+        }
+    }
+
+    /// Brings the content of the panes showing the buffer up to date, except
+    /// for the pane `except` the buffer was changed from.
+    fn reload_panes(&mut self, buffer_id: buffer::Id, except: Option<pane_grid::Pane>) {
+        let Some(buffer) = self.tabs.get_mut(buffer_id) else {
+            return;
+        };
+
+        let replays = buffer.take_replays();
+
+        for (id, pane) in self.panes.iter_mut() {
+            if Some(*id) != except && pane.buffer == buffer_id {
+                pane.reload(buffer, replays.as_deref());
+            }
+        }
+    }
+
+    /// Changes the folds of the active buffer with `fold`, keeping the
+    /// cursors of the panes showing it in place.
+    fn fold(&mut self, fold: impl FnOnce(&mut Buffer)) -> Task<Message> {
+        let buffer = self.tabs.active_mut();
+        let id = buffer.id;
+
+        let cursors = self
+            .panes
+            .iter()
+            .filter(|(_, pane)| pane.buffer == id)
+            .map(|(pane_id, pane)| (*pane_id, pane.cursor(buffer)))
+            .collect::<Vec<_>>();
+
+        fold(buffer);
+
+        for (pane_id, cursor) in cursors {
+            if let Some(pane) = self.panes.get_mut(pane_id) {
+                pane.go_to(buffer, cursor);
+            }
+        }
+
+        Task::batch([self.save_folds(id), self.scroll_to_cursor(self.focus)])
+    }
+
+    /// Moves the active buffer through its history with `step`, placing the
+    /// cursor of the focused pane where the document changed.
+    fn step_history(
+        &mut self,
+        step: impl FnOnce(&mut Buffer) -> Option<Position>,
+    ) -> Task<Message> {
+        let buffer = self.tabs.active_mut();
+        let id = buffer.id;
+
+        let Some(position) = step(buffer) else {
+            return Task::none();
+        };
+
+        if let Some(pane) = self.panes.get_mut(self.focus) {
+            pane.go_to(buffer, position);
+        }
+
+        self.reload_panes(id, Some(self.focus));
+
+        Task::batch([
+            self.focus_pane(self.focus),
+            self.scroll_to_cursor(self.focus),
+        ])
+    }
+
+    /// Turns every line ending of the active buffer into `line_ending`.
+    fn convert_line_endings(&mut self, line_ending: LineEnding) -> Task<Message> {
+        let buffer = self.tabs.active_mut();
+        let id = buffer.id;
+
+        if buffer.convert_line_endings(line_ending) {
+            self.reload_panes(id, None);
+        }
+
+        Task::none()
+    }
+
+    /// Stores the `history` the buffer had when its text was saved at
+    /// `checkpoint`, if it is a file.
+    fn save_history(
+        &self,
+        id: buffer::Id,
+        checkpoint: Checkpoint,
+        history: data::history::History,
+    ) -> Task<Message> {
+        let Some(path) = self.tabs.get(id).and_then(|buffer| buffer.path.clone()) else {
+            return Task::none();
+        };
+
+        Task::perform(history.save(path, checkpoint.hash()), Message::HistorySaved)
+    }
+
+    /// Remembers the folds of the buffer in the session, if it is a file.
+    fn save_folds(&mut self, id: buffer::Id) -> Task<Message> {
+        let Some(buffer) = self.tabs.get(id) else {
+            return Task::none();
+        };
+        let Some(path) = &buffer.path else {
+            return Task::none();
+        };
+
+        self.session
+            .set_folds(path, buffer.folds.regions().collect());
+
+        Task::perform(self.session.clone().save(), Message::SessionSaved)
+    }
+
+    fn line_height(&self) -> f32 {
+        self.text_size * LINE_HEIGHT
+    }
+
+    fn gutter_settings(&self) -> gutter::Settings {
+        gutter::Settings {
+            line_numbers: self.line_numbers,
+            text_size: self.text_size,
+            line_height: self.line_height(),
+            padding: EDITOR_PADDING,
+        }
+    }
+
+    /// How the lines of `pane` are wrapped, laid out by `wrap` with the
+    /// columns that fit next to the gutter.
+    fn wrap(
+        &self,
+        pane: &Pane,
+        buffer: &Buffer,
+        wrap: impl FnOnce(&text_editor::Content, usize) -> Wrap,
+    ) -> Wrap {
+        if !self.word_wrap {
+            return Wrap::none();
+        }
+
+        let width = if pane.viewport.width > 0.0 {
+            pane.viewport.width
+        } else {
+            self.main_window.size.width
+        };
+        let text_width =
+            width - gutter::width(buffer, self.gutter_settings()) - EDITOR_PADDING * 2.0;
+        let columns = (text_width / gutter::char_width(self.text_size)).max(1.0) as usize;
+
+        wrap(&pane.content, columns)
+    }
+
+    /// Scrolls the cursor of the pane into view, which the editor does on
+    /// its own when it isn't inside of a scrollable.
+    fn scroll_to_cursor(&mut self, id: pane_grid::Pane) -> Task<Message> {
+        let line_height = self.line_height();
+
+        let Some(pane) = self.panes.get(id) else {
+            return Task::none();
+        };
+        let Some(buffer) = self.tabs.get(pane.buffer) else {
+            return Task::none();
+        };
+
+        let line = buffer::cursor(&pane.content).line;
+        let wrap = self.wrap(pane, buffer, |content, columns| {
+            Wrap::down_to_line(content, columns, line)
+        });
+
+        match self.panes.get_mut(id) {
+            Some(pane) => pane.scroll_to_cursor(&wrap, line_height, EDITOR_PADDING),
+            None => Task::none(),
+        }
+    }
+
+    /// Grows the selection of the focused pane to the syntax node around it.
+    fn expand_selection(&mut self) {
+        let Some(pane) = self.panes.get_mut(self.focus) else {
+            return;
+        };
+        let Some(buffer) = self.tabs.get(pane.buffer) else {
+            return;
+        };
+        let Some(syntax) = &buffer.syntax else {
+            return;
+        };
+
+        let document = &buffer.document;
+        let selection = buffer.selection(&pane.content).unwrap_or_else(|| {
+            let cursor = pane.cursor(buffer);
+
+            cursor..cursor
+        });
+
+        if let Some(expanded) = syntax.expand(
+            document.position_to_byte(selection.start)..document.position_to_byte(selection.end),
+        ) {
+            buffer.select(
+                &mut pane.content,
+                document.byte_to_position(expanded.start)..document.byte_to_position(expanded.end),
+            );
+            pane.expansions.push(selection);
+        }
+    }
+
+    /// Shrinks the selection of the focused pane back to what it was before
+    /// it was last expanded, or to the first syntax node inside of it.
+    fn shrink_selection(&mut self) {
+        let Some(pane) = self.panes.get_mut(self.focus) else {
+            return;
+        };
+        let Some(buffer) = self.tabs.get(pane.buffer) else {
+            return;
+        };
+
+        if let Some(previous) = pane.expansions.pop() {
+            buffer.select(&mut pane.content, previous);
+            return;
+        }
+
+        let Some(syntax) = &buffer.syntax else {
+            return;
+        };
+        let Some(selection) = buffer.selection(&pane.content) else {
+            return;
+        };
+
+        let document = &buffer.document;
+
+        if let Some(shrunk) = syntax.shrink(
+            document.position_to_byte(selection.start)..document.position_to_byte(selection.end),
+        ) {
+            buffer.select(
+                &mut pane.content,
+                document.byte_to_position(shrunk.start)..document.byte_to_position(shrunk.end),
+            );
+        }
+    }
+
+    /// Moves the cursor of the focused pane to `position`.
+    fn go_to(&mut self, position: Position) {
+        let Some(pane) = self.panes.get_mut(self.focus) else {
+            return;
+        };
+
+        let Some(buffer) = self.tabs.get_mut(pane.buffer) else {
+            return;
+        };
+
+        let revealed = buffer.reveal(position.line);
+
+        buffer.cursor = position;
+        pane.go_to(buffer, position);
+
+        if revealed {
+            let buffer_id = buffer.id;
+
+            self.reload_panes(buffer_id, Some(self.focus));
+        }
+    }
+
+    fn preview(&mut self, position: Position) {
+        if self.preview_origin.is_none() {
+            self.preview_origin = self
+                .panes
+                .get(self.focus)
+                .map(|pane| pane.cursor(self.tabs.active()));
+        }
+
+        self.go_to(position);
+    }
+
+    /// Puts the cursor back to where it was before previewing.
+    fn end_preview(&mut self) {
+        if let Some(origin) = self.preview_origin.take() {
+            self.go_to(origin);
+        }
+    }
+
+    fn save_palette_history(&self) -> Task<Message> {
+        Task::perform(
+            self.palette_history.clone().save(),
+            Message::PaletteHistorySaved,
+        )
+    }
+
+    /// Copies the selection, or the selections of every cursor a line each.
+    ///
+    /// The lines of a rectangular selection are remembered, so pasting them
+    /// keeps their shape.
+    fn copy(&mut self) -> Task<Message> {
+        let Some(pane) = self.panes.get(self.focus) else {
+            return Task::none();
+        };
+
+        let Some(buffer) = self
+            .tabs
+            .get(pane.buffer)
+            .filter(|_| !pane.carets.is_empty())
+        else {
+            return pane
+                .content
+                .selection()
+                .map_or_else(Task::none, iced::clipboard::write);
+        };
+
+        let carets = pane
+            .carets
+            .iter()
+            .copied()
+            .chain([buffer.caret(&pane.content)])
+            .collect();
+
+        let text = caret::merge(carets)
+            .iter()
+            .map(|caret| {
+                let range = caret.range();
+
+                buffer
+                    .document
+                    .slice(
+                        buffer.document.position_to_char(range.start)
+                            ..buffer.document.position_to_char(range.end),
+                    )
+                    .into_owned()
+            })
+            .collect::<Vec<_>>()
+            .join("\n");
+
+        self.block_clipboard = pane.block.is_some().then(|| text.clone());
+
+        iced::clipboard::write(text)
+    }
+
+    /// Grows the rectangular selection of the focused pane by a line or a
+    /// column with `motion`, starting one at the cursor if there is none.
+    fn column_select(&mut self, motion: text_editor::Motion) -> Task<Message> {
+        let Some(pane) = self.panes.get_mut(self.focus) else {
+            return Task::none();
+        };
+
+        let Some(buffer) = self.tabs.get(pane.buffer) else {
+            return Task::none();
+        };
+
+        let block = pane
+            .block
+            .unwrap_or_else(|| Block::new(Cell::at(&buffer.document, pane.cursor(buffer))));
+
+        pane.select_block(buffer, block.moved(&buffer.document, motion));
+
+        self.scroll_to_cursor(self.focus)
+    }
+
+    /// Leaves a caret at the cursor of the focused pane and moves the cursor
+    /// with `motion`.
+    fn add_cursor(&mut self, motion: text_editor::Motion) -> Task<Message> {
+        let Some(pane) = self.panes.get_mut(self.focus) else {
+            return Task::none();
+        };
+
+        let Some(buffer) = self.tabs.get_mut(pane.buffer) else {
+            return Task::none();
+        };
+
+        let caret = Caret::new(pane.cursor(buffer));
+
+        buffer.perform(&mut pane.content, text_editor::Action::Move(motion));
+        pane.carets.push(caret);
+        buffer.merge_carets(&pane.content, &mut pane.carets);
+
+        self.scroll_to_cursor(self.focus)
+    }
+
+    /// Selects the next occurrence of the selection of the focused pane,
+    /// leaving a caret on the current one. Without a selection the word at
+    /// the cursor is selected first.
+    fn add_next_occurrence(&mut self) -> Task<Message> {
+        let Some(pane) = self.panes.get_mut(self.focus) else {
+            return Task::none();
+        };
+
+        let Some(buffer) = self.tabs.get_mut(pane.buffer) else {
+            return Task::none();
+        };
+
+        let Some(selection) = buffer.selection(&pane.content) else {
+            if let Some(word) = caret::word_at(&buffer.document, pane.cursor(buffer)) {
+                buffer.select(&mut pane.content, word);
+            }
+
+            return Task::none();
+        };
+
+        let needle = buffer.document.slice(
+            buffer.document.position_to_char(selection.start)
+                ..buffer.document.position_to_char(selection.end),
+        );
+        let occurrences = caret::occurrences(&buffer.document, &needle);
+
+        let is_taken = |range: &Range<Position>| {
+            *range == selection || pane.carets.iter().any(|caret| caret.range() == *range)
+        };
+
+        let Some(next) = occurrences
+            .iter()
+            .filter(|range| range.start >= selection.end)
+            .chain(&occurrences)
+            .find(|range| !is_taken(range))
+            .cloned()
+        else {
+            return Task::none();
+        };
+
+        pane.carets.push(Caret::select(selection));
+        self.select_occurrences(next, &[])
+    }
+
+    /// Selects every occurrence of the selection of the focused pane, or of
+    /// the word at its cursor, placing a caret on each.
+    fn select_all_occurrences(&mut self) -> Task<Message> {
+        let Some(pane) = self.panes.get_mut(self.focus) else {
+            return Task::none();
+        };
+
+        let Some(buffer) = self.tabs.get_mut(pane.buffer) else {
+            return Task::none();
+        };
+
+        let Some(selection) = buffer
+            .selection(&pane.content)
+            .or_else(|| caret::word_at(&buffer.document, pane.cursor(buffer)))
+        else {
+            return Task::none();
+        };
+
+        let needle = buffer.document.slice(
+            buffer.document.position_to_char(selection.start)
+                ..buffer.document.position_to_char(selection.end),
+        );
+        let others = caret::occurrences(&buffer.document, &needle)
+            .into_iter()
+            .filter(|range| *range != selection)
+            .collect::<Vec<_>>();
+
+        self.select_occurrences(selection, &others)
+    }
+
+    /// Selects `primary` with the cursor of the focused pane and adds a caret
+    /// selecting each of the `others`, unfolding the regions hiding them.
+    fn select_occurrences(
+        &mut self,
+        primary: Range<Position>,
+        others: &[Range<Position>],
+    ) -> Task<Message> {
+        let focus = self.focus;
+
+        let Some(pane) = self.panes.get_mut(focus) else {
+            return Task::none();
+        };
+
+        let Some(buffer) = self.tabs.get_mut(pane.buffer) else {
+            return Task::none();
+        };
+
+        let mut revealed = false;
+
+        for range in others.iter().chain([&primary]) {
+            revealed |= buffer.reveal(range.start.line);
+            revealed |= buffer.reveal(range.end.line);
+        }
+
+        if revealed {
+            pane.content = buffer.content(primary.start);
+        }
+
+        buffer.select(&mut pane.content, primary);
+        pane.carets
+            .extend(others.iter().cloned().map(Caret::select));
+        buffer.merge_carets(&pane.content, &mut pane.carets);
+
+        if revealed {
+            let buffer_id = buffer.id;
+
+            self.reload_panes(buffer_id, Some(focus));
+        }
+
+        self.scroll_to_cursor(focus)
+    }
+
+    fn focus_adjacent(&mut self, direction: pane_grid::Direction) -> Task<Message> {
+        match self.panes.adjacent(self.focus, direction) {
+            Some(adjacent) => self.focus_pane(adjacent),
+            None => Task::none(),
+        }
+    }
+
+    fn save(&mut self, id: buffer::Id) -> Task<Message> {
+        let Some(buffer) = self.tabs.get(id) else {
+            return Task::none();
+        };
+
+        self.save_with(id, buffer.encoding)
+    }
+
+    /// Saves the buffer in `encoding`, which it keeps once saved.
+    fn save_with(&mut self, id: buffer::Id, encoding: Encoding) -> Task<Message> {
+        // Nothing waits for a save that doesn't happen.
+        if self.is_loading {
+            self.after_save = None;
+
+            return Task::none();
+        }
+
+        let Some(buffer) = self.tabs.get(id) else {
+            self.after_save = None;
+
+            return Task::none();
+        };
+
+        self.is_loading = true;
+        self.save_error = None;
+
+        let snapshot = buffer.document.snapshot();
+        let checkpoint = buffer.document.checkpoint();
+        // Edits made while saving are not part of the file, nor of its
+        // history.
+        let history = buffer.history.clone();
+        // Only files that have line breaks get one added at the end.
+        let line_ending = buffer.line_endings().dominant();
+
+        Task::perform(
+            save_file(buffer.path.clone(), snapshot, line_ending, encoding),
+            move |result| Message::FileSaved(id, checkpoint, history, encoding, result),
+        )
+    }
+
+    /// Buffers with unsaved changes that `action` would throw away.
+    fn unsaved(&self, action: unsaved_changes::Action) -> impl Iterator<Item = &Buffer> {
+        self.tabs.iter().filter(move |buffer| {
+            buffer.is_dirty()
+                && match action {
+                    unsaved_changes::Action::CloseWindow => true,
+                    unsaved_changes::Action::CloseTab(id)
+                    | unsaved_changes::Action::Reopen(id, _) => buffer.id == id,
+                }
+        })
+    }
+
+    /// Runs `action` right away, or asks what to do with the unsaved changes
+    /// it would discard first.
+    fn confirm(&mut self, action: unsaved_changes::Action) -> Task<Message> {
+        let titles = self.unsaved(action).map(Buffer::title).collect::<Vec<_>>();
+
+        if titles.is_empty() {
+            return self.proceed(action);
+        }
+
+        self.modal = Some(Modal::UnsavedChanges(unsaved_changes::State::new(
+            action, titles,
+        )));
+
+        Task::none()
+    }
+
+    /// Saves the next buffer blocking `action`, running it once none is left.
+    fn save_next(&mut self, action: unsaved_changes::Action) -> Task<Message> {
+        let next = self.unsaved(action).next().map(|buffer| buffer.id);
+
+        match next {
+            Some(id) => self.save(id),
+            None => {
+                self.after_save = None;
+
+                self.proceed(action)
+            }
+        }
+    }
+
+    fn proceed(&mut self, action: unsaved_changes::Action) -> Task<Message> {
+        match action {
+            unsaved_changes::Action::CloseWindow => iced::exit(),
+            unsaved_changes::Action::CloseTab(id) => {
+                self.tabs.close_buffer(id);
+
+                self.sync_panes()
+            }
+            unsaved_changes::Action::Reopen(id, encoding) => {
+                let Some(path) = self.tabs.get(id).and_then(|buffer| buffer.path.clone()) else {
+                    return Task::none();
+                };
+
+                if self.is_loading {
+                    return Task::none();
+                }
+
+                self.is_loading = true;
+
+                Task::perform(load_file(path, Some(encoding)), move |result| {
+                    Message::FileReopened(id, result)
+                })
+            }
+        }
+    }
+
+    fn set_focus(&mut self, id: pane_grid::Pane) {
+        if let Some(pane) = self.panes.get(id) {
+            self.focus = id;
+            self.tabs.select(pane.buffer);
+        }
+    }
+
+    fn focus_pane(&mut self, id: pane_grid::Pane) -> Task<Message> {
+        self.set_focus(id);
+        self.editor_focused = true;
+
+        self.panes.get(id).map_or_else(Task::none, Pane::focus)
+    }
+
+    /// Points the focused pane at the active tab, and any pane whose buffer
+    /// was closed as well.
+    fn sync_panes(&mut self) -> Task<Message> {
+        let active = self.tabs.active();
+
+        for (id, pane) in self.panes.iter_mut() {
+            if (*id == self.focus && pane.buffer != active.id)
+                || self.tabs.get(pane.buffer).is_none()
+            {
+                pane.show(active);
+            }
+        }
+
+        self.editor_focused = true;
+
+        self.panes
+            .get(self.focus)
+            .map_or_else(Task::none, Pane::focus)
+    }
+
+    fn theme(&self, _window_id: window::Id) -> Theme {
+        self.theme.clone()
+    }
+
+    fn subscription(&self) -> Subscription<Message> {
+        let subscriptions = vec![
+            events().map(|(window, event)| Message::Event(window, event)),
+            window::events().map(|(window, event)| Message::Window(window, event)),
+        ];
+
+        Subscription::batch(subscriptions)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum Error {
+    DialogClosed,
+    IoError(io::ErrorKind),
+    Encoding(data::encoding::Error),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::DialogClosed => write!(f, "no file was picked"),
+            Error::IoError(kind) => write!(f, "{}", io::Error::from(*kind)),
+            Error::Encoding(error) => write!(f, "{error}"),
+        }
+    }
+}
+
+impl From<data::buffer::Error> for Error {
+    fn from(error: data::buffer::Error) -> Self {
+        match error {
+            data::buffer::Error::Io(error) => Error::IoError(error.kind()),
+            data::buffer::Error::Encoding(error) => Error::Encoding(error),
+        }
+    }
+}
+
+async fn open_file() -> Result<(PathBuf, data::Buffer, Encoding), Error> {
+    let picked_file = rfd::AsyncFileDialog::new()
+        .set_title("Open a text file")
+        .pick_file()
+        .await
+        .ok_or(Error::DialogClosed)?;
+
+    load_file(picked_file, None).await
+}
+
+/// The line ending of the status row, flagged when lines end in more than one
+/// way.
+fn line_endings<'a>(counts: line_ending::Counts) -> Element<'a, Message> {
+    let dominant = counts.dominant().unwrap_or_default();
+
+    if counts.is_mixed() {
+        text(format!("Mixed line endings ({dominant})"))
+            .style(theme::text::error)
+            .into()
+    } else {
+        text(dominant.to_string()).into()
+    }
+}
+
+/// Reads the file at `path`, in `encoding` or the one detected from its
+/// bytes.
+async fn load_file(
+    path: impl Into<PathBuf>,
+    encoding: Option<Encoding>,
+) -> Result<(PathBuf, data::Buffer, Encoding), Error> {
+    let path = path.into();
+
+    let (document, encoding) = data::Buffer::load(&path, encoding).await?;
+
+    Ok((path, document, encoding))
+}
+
+/// Loads the history stored for the file of `buffer`, if it still holds the
+/// text the history was saved with.
+fn load_history(buffer: &Buffer, path: PathBuf) -> Task<Message> {
+    let id = buffer.id;
+    let hash = buffer.document.hash();
+
+    Task::perform(data::history::History::load(path, hash), move |result| {
+        Message::HistoryLoaded(id, result)
+    })
+}
+
+async fn save_file(
+    path: Option<PathBuf>,
+    snapshot: Snapshot,
+    line_ending: Option<LineEnding>,
+    encoding: Encoding,
+) -> Result<PathBuf, Error> {
+    let path = if let Some(path) = path {
+        path
+    } else {
+        rfd::AsyncFileDialog::new()
+            .save_file()
+            .await
+            .as_ref()
+            .map(rfd::FileHandle::path)
+            .map(Path::to_owned)
+            .ok_or(Error::DialogClosed)?
+    };
+
+    snapshot
+        .save(&path, line_ending.map(LineEnding::as_str), encoding)
+        .await?;
 
     Ok(path)
 }
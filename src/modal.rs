@@ -1,29 +1,61 @@
+use std::path::PathBuf;
+use std::time::SystemTime;
+
+use data::buffer::Position;
+use data::command::Command;
+use data::encoding::Encoding;
+use data::language::Language;
 use iced::Task;
 
 use crate::widget::Element;
 use crate::window;
 
 pub mod command_palette;
+pub mod undo_history;
+pub mod unsaved_changes;
 
 #[derive(Debug)]
 pub enum Modal {
     CommandPalette(command_palette::State),
+    UnsavedChanges(unsaved_changes::State),
+    UndoHistory(undo_history::State),
 }
 
 #[derive(Debug, Clone)]
 pub enum Message {
     CommandPalette(command_palette::Message),
+    UnsavedChanges(unsaved_changes::Message),
+    UndoHistory(undo_history::Message),
     Cancel,
 }
 
 pub enum Event {
     CloseModal,
+    ExecuteCommand(Command),
+    OpenFile(PathBuf),
+    /// Moves the cursor of the focused pane and closes the modal.
+    GoTo(Position),
+    /// Shows a position in the focused pane while the modal stays open.
+    Preview(Position),
+    /// Highlights the active buffer as the language, or as the detected one
+    /// if `None`.
+    SetLanguage(Option<Language>),
+    /// Goes to the state of a node of the history of the active buffer.
+    TravelHistory(usize),
+    /// Goes to the state the active buffer was in at the time.
+    TravelHistoryTo(SystemTime),
+    /// Reads the file of the active buffer again in the encoding.
+    ReopenWithEncoding(Encoding),
+    /// Saves the active buffer in the encoding, which it keeps from then on.
+    SaveWithEncoding(Encoding),
+    Save(unsaved_changes::Action),
+    Discard(unsaved_changes::Action),
 }
 
 impl Modal {
     pub fn window_id(&self) -> Option<window::Id> {
         match self {
-            Modal::CommandPalette(..) => None,
+            Modal::CommandPalette(..) | Modal::UnsavedChanges(..) | Modal::UndoHistory(..) => None,
         }
     }
 
@@ -35,12 +67,23 @@ impl Modal {
                 let (task, event) = state.update(msg);
                 (task.map(Message::CommandPalette), event)
             }
+            (Modal::UnsavedChanges(state), Message::UnsavedChanges(msg)) => {
+                let (task, event) = state.update(msg);
+                (task.map(Message::UnsavedChanges), event)
+            }
+            (Modal::UndoHistory(state), Message::UndoHistory(msg)) => {
+                let (task, event) = state.update(msg);
+                (task.map(Message::UndoHistory), event)
+            }
+            _ => (Task::none(), None),
         }
     }
 
     pub fn view(&self) -> Element<Message> {
         match self {
             Modal::CommandPalette(state) => state.view().map(Message::CommandPalette),
+            Modal::UnsavedChanges(state) => state.view().map(Message::UnsavedChanges),
+            Modal::UndoHistory(state) => state.view().map(Message::UndoHistory),
         }
     }
 }
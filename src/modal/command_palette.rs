@@ -1,32 +1,136 @@
-use iced::{
-    Length, Task,
-    widget::{Button, column, container, text, text_input},
-};
+use std::path::{Path, PathBuf};
 
-use crate::{
-    theme,
-    widget::{Element, Scrollable},
-};
+use data::buffer::Position;
+use data::command::Entry;
+use data::encoding::Encoding;
+use data::fuzzy::fuzzy_match;
+use data::language::{self, Language};
+use data::palette::{self, History, Mode};
+use data::symbol::{self, Symbol};
+use iced::advanced::widget::{self, operation};
+use iced::task;
+use iced::widget::{Button, column, container, horizontal_space, row, text, text_input};
+use iced::{Length, Task, keyboard};
 
-#[derive(Debug, Default)]
+use crate::widget::{Element, Row};
+use crate::{font, theme};
+
+/// How many entries are shown at once.
+const VISIBLE: usize = 8;
+
+#[derive(Debug)]
 pub struct State {
     pub input_value: String,
-    pub commands: Vec<String>,
-    pub filtered: Vec<String>,
+    pub commands: Vec<Entry>,
+    pub filtered: Vec<Filtered>,
+    /// The mode the palette was opened in.
+    mode: Mode,
+    /// The document of the active buffer, for symbols and line lookups.
+    document: data::Buffer,
+    /// The encoding of the active buffer, marked among the encodings.
+    encoding: Encoding,
+    symbols: Vec<Symbol>,
+    root: PathBuf,
+    files: Vec<PathBuf>,
+    selected: usize,
+    /// The first visible entry, moved along to keep the selection in view.
+    offset: usize,
+    history: History,
+    input: widget::Id,
+    /// Stops walking the project once the palette is closed.
+    _walk: task::Handle,
+}
+
+/// Something that can be picked from the palette.
+#[derive(Debug, Clone)]
+pub enum Item {
+    Command(Entry),
+    File(PathBuf),
+    Line(Position),
+    Symbol(Symbol),
+    /// A language to highlight the current file as, or `None` to detect it.
+    Language(Option<Language>),
+    Encoding(Encoding),
+}
+
+impl Item {
+    /// Where the editor should scroll to while the item is selected.
+    fn position(&self) -> Option<Position> {
+        match self {
+            Item::Line(position) => Some(*position),
+            Item::Symbol(symbol) => Some(symbol.position),
+            Item::Command(_) | Item::File(_) | Item::Language(_) | Item::Encoding(_) => None,
+        }
+    }
+}
+
+/// An item that matched the input, with the chars of its label that matched.
+#[derive(Debug, Clone)]
+pub struct Filtered {
+    pub item: Item,
+    pub label: String,
+    pub detail: String,
+    pub indices: Vec<usize>,
 }
 
 #[derive(Debug, Clone)]
 pub enum Message {
     InputChanged(String),
-    ExecuteCommand(String),
+    Up,
+    Down,
+    Submit,
+    Pick(usize),
+    FilesFound(Vec<PathBuf>),
 }
 
 impl State {
-    pub fn new(commands: Vec<String>) -> Self {
-        Self {
-            commands: commands.clone(),
-            filtered: commands,
-            ..Default::default()
+    pub fn new(
+        mode: Mode,
+        commands: Vec<Entry>,
+        document: data::Buffer,
+        language: Language,
+        encoding: Encoding,
+        root: PathBuf,
+        history: History,
+    ) -> (Self, Task<Message>) {
+        let input = widget::Id::unique();
+        let (walk, handle) =
+            Task::run(data::project::files(root.clone()), Message::FilesFound).abortable();
+
+        let mut state = Self {
+            input_value: mode.prefix().to_string(),
+            commands,
+            filtered: vec![],
+            mode,
+            symbols: symbol::scan(&document, Some(language.token())),
+            document,
+            encoding,
+            root,
+            files: vec![],
+            selected: 0,
+            offset: 0,
+            history,
+            input: input.clone(),
+            _walk: handle.abort_on_drop(),
+        };
+        state.filter();
+
+        (
+            state,
+            Task::batch([widget::operate(operation::focusable::focus(input)), walk]),
+        )
+    }
+
+    /// Maps the navigation keys the text input lets through.
+    pub fn key_pressed(key: &keyboard::Key, modifiers: keyboard::Modifiers) -> Option<Message> {
+        match key {
+            keyboard::Key::Named(keyboard::key::Named::ArrowUp) => Some(Message::Up),
+            keyboard::Key::Named(keyboard::key::Named::ArrowDown) => Some(Message::Down),
+            keyboard::Key::Named(keyboard::key::Named::Tab) if modifiers.shift() => {
+                Some(Message::Up)
+            }
+            keyboard::Key::Named(keyboard::key::Named::Tab) => Some(Message::Down),
+            _ => None,
         }
     }
 
@@ -34,21 +138,228 @@ impl State {
         match message {
             Message::InputChanged(input) => {
                 self.input_value.clone_from(input);
-                self.filtered = self
-                    .commands
-                    .iter()
-                    .filter(|cmd| cmd.to_lowercase().contains(&input.to_lowercase()))
-                    .cloned()
-                    .collect();
+                self.selected = 0;
+                self.offset = 0;
+                self.filter();
+                (Task::none(), self.preview())
+            }
+            Message::Up => {
+                self.select(
+                    self.selected
+                        .checked_sub(1)
+                        .unwrap_or(self.filtered.len().saturating_sub(1)),
+                );
+                (Task::none(), self.preview())
+            }
+            Message::Down => {
+                self.select(if self.selected + 1 < self.filtered.len() {
+                    self.selected + 1
+                } else {
+                    0
+                });
+                (Task::none(), self.preview())
+            }
+            Message::Submit => (Task::none(), self.pick(self.selected)),
+            Message::Pick(index) => (Task::none(), self.pick(*index)),
+            Message::FilesFound(files) => {
+                self.files.extend(files.iter().cloned());
+
+                if self.mode().0 == Mode::Files {
+                    self.filter();
+                }
+
                 (Task::none(), None)
             }
-            Message::ExecuteCommand(_cmd) => (Task::none(), Some(super::Event::CloseModal)),
+        }
+    }
+
+    fn pick(&self, index: usize) -> Option<super::Event> {
+        let filtered = self.filtered.get(index)?;
+
+        Some(match &filtered.item {
+            Item::Command(entry) => super::Event::ExecuteCommand(entry.command),
+            Item::File(path) => super::Event::OpenFile(path.clone()),
+            Item::Line(position) => super::Event::GoTo(*position),
+            Item::Symbol(symbol) => super::Event::GoTo(symbol.position),
+            Item::Language(language) => super::Event::SetLanguage(*language),
+            Item::Encoding(encoding) if self.mode == Mode::SaveWithEncoding => {
+                super::Event::SaveWithEncoding(*encoding)
+            }
+            Item::Encoding(encoding) => super::Event::ReopenWithEncoding(*encoding),
+        })
+    }
+
+    fn preview(&self) -> Option<super::Event> {
+        self.filtered
+            .get(self.selected)
+            .and_then(|filtered| filtered.item.position())
+            .map(super::Event::Preview)
+    }
+
+    /// The current mode and the query typed for it.
+    ///
+    /// A palette opened to pick a language or an encoding sticks to it, the
+    /// other modes follow the prefix of the input.
+    fn mode(&self) -> (Mode, &str) {
+        match self.mode {
+            Mode::Languages | Mode::ReopenWithEncoding | Mode::SaveWithEncoding => {
+                (self.mode, self.input_value.as_str())
+            }
+            _ => Mode::parse(&self.input_value),
+        }
+    }
+
+    /// Lists the items of the current mode matching the input, best and most
+    /// recently used first.
+    fn filter(&mut self) {
+        let (mode, query) = self.mode();
+
+        let rank = |item: Item, label: String, detail: String, boost: i64| {
+            let matched = fuzzy_match(query, &label)?;
+
+            Some((
+                matched.score + boost,
+                Filtered {
+                    item,
+                    label,
+                    detail,
+                    indices: matched.indices,
+                },
+            ))
+        };
+
+        let mut ranked = match mode {
+            Mode::Commands => self
+                .commands
+                .iter()
+                .filter_map(|entry| {
+                    rank(
+                        Item::Command(entry.clone()),
+                        entry.command.to_string(),
+                        entry
+                            .key_bind
+                            .as_ref()
+                            .map(ToString::to_string)
+                            .unwrap_or_default(),
+                        self.history.boost(entry.command.id()),
+                    )
+                })
+                .collect::<Vec<_>>(),
+            Mode::Files => self
+                .files
+                .iter()
+                .filter_map(|path| {
+                    rank(
+                        Item::File(path.clone()),
+                        relative(&self.root, path),
+                        String::new(),
+                        self.history.boost(&palette::file_key(path)),
+                    )
+                })
+                .collect(),
+            Mode::Symbols => self
+                .symbols
+                .iter()
+                .filter_map(|symbol| {
+                    rank(
+                        Item::Symbol(symbol.clone()),
+                        symbol.name.clone(),
+                        symbol.kind.to_string(),
+                        0,
+                    )
+                })
+                .collect(),
+            Mode::Languages => std::iter::once(None)
+                .chain(language::ALL.iter().copied().map(Some))
+                .filter_map(|language| {
+                    let (label, detail) = match language {
+                        Some(language) => (
+                            language.name.to_string(),
+                            language
+                                .extensions
+                                .iter()
+                                .map(|extension| format!(".{extension}"))
+                                .collect::<Vec<_>>()
+                                .join(" "),
+                        ),
+                        None => (String::from("Auto Detect"), String::new()),
+                    };
+
+                    rank(Item::Language(language), label, detail, 0)
+                })
+                .collect(),
+            Mode::ReopenWithEncoding | Mode::SaveWithEncoding => Encoding::all()
+                .into_iter()
+                .filter_map(|encoding| {
+                    let detail = if encoding == self.encoding {
+                        String::from("current")
+                    } else {
+                        String::new()
+                    };
+
+                    rank(Item::Encoding(encoding), encoding.to_string(), detail, 0)
+                })
+                .collect(),
+            Mode::Line => palette::parse_line(query)
+                .map(|position| {
+                    let position = self.line_position(position);
+                    let label = format!(
+                        "Go to line {}, column {}",
+                        position.line + 1,
+                        position.column + 1
+                    );
+
+                    vec![(
+                        0,
+                        Filtered {
+                            item: Item::Line(position),
+                            label,
+                            detail: String::new(),
+                            indices: vec![],
+                        },
+                    )]
+                })
+                .unwrap_or_default(),
+        };
+
+        ranked.sort_by_key(|(score, _)| std::cmp::Reverse(*score));
+
+        self.filtered = ranked.into_iter().map(|(_, filtered)| filtered).collect();
+        self.select(self.selected.min(self.filtered.len().saturating_sub(1)));
+    }
+
+    /// Clamps a line and char column to the document, converting the column
+    /// to bytes.
+    fn line_position(&self, position: Position) -> Position {
+        let line = position
+            .line
+            .min(self.document.len_lines().saturating_sub(1));
+        let text = self.document.line_text(line).unwrap_or_default();
+        let column = text
+            .char_indices()
+            .nth(position.column)
+            .map_or(text.len(), |(column, _)| column);
+
+        Position::new(line, column)
+    }
+
+    fn select(&mut self, index: usize) {
+        self.selected = index;
+
+        if index < self.offset {
+            self.offset = index;
+        } else if index >= self.offset + VISIBLE {
+            self.offset = index + 1 - VISIBLE;
         }
     }
 
     pub fn view(&self) -> Element<Message> {
-        let input = text_input("Start typing...", &self.input_value)
+        let (mode, _) = self.mode();
+
+        let input = text_input(mode.placeholder(), &self.input_value)
+            .id(self.input.clone())
             .on_input(Message::InputChanged)
+            .on_submit(Message::Submit)
             .padding(10)
             .size(20)
             .width(Length::Fill);
@@ -56,16 +367,27 @@ impl State {
         let command_buttons: Vec<Element<'_, Message>> = self
             .filtered
             .iter()
-            .map(|cmd| {
-                Button::new(text(cmd))
-                    .on_press(Message::ExecuteCommand(cmd.clone()))
-                    .padding(5)
-                    .into()
+            .enumerate()
+            .skip(self.offset)
+            .take(VISIBLE)
+            .map(|(index, filtered)| {
+                let selected = index == self.selected;
+
+                Button::new(row![
+                    highlight(&filtered.label, &filtered.indices),
+                    horizontal_space(),
+                    text(filtered.detail.as_str()).style(theme::text::secondary),
+                ])
+                .on_press(Message::Pick(index))
+                .style(move |theme, status| theme::button::secondary(theme, status, selected))
+                .width(Length::Fill)
+                .padding(5)
+                .into()
             })
             .collect();
 
-        let command_list: Scrollable<Message> =
-            Scrollable::new(column(command_buttons).spacing(5)).height(Length::Fixed(200.0));
+        let command_list = container(column(command_buttons).spacing(5))
+            .height(Length::Fixed(VISIBLE as f32 * 30.0));
 
         let content = column![input, command_list]
             .padding(20)
@@ -80,3 +402,45 @@ impl State {
             .into()
     }
 }
+
+fn relative(root: &Path, path: &Path) -> String {
+    path.strip_prefix(root)
+        .unwrap_or(path)
+        .display()
+        .to_string()
+}
+
+/// Renders `label` with the chars at `indices` emphasized.
+fn highlight<'a>(label: &str, indices: &[usize]) -> Row<'a, Message> {
+    let mut segments = Row::new();
+    let mut current = String::new();
+    let mut is_match = false;
+
+    for (i, c) in label.chars().enumerate() {
+        let matched = indices.contains(&i);
+
+        if matched != is_match && !current.is_empty() {
+            segments = segments.push(segment(std::mem::take(&mut current), is_match));
+        }
+
+        is_match = matched;
+        current.push(c);
+    }
+
+    if !current.is_empty() {
+        segments = segments.push(segment(current, is_match));
+    }
+
+    segments
+}
+
+fn segment<'a>(content: String, is_match: bool) -> Element<'a, Message> {
+    if is_match {
+        text(content)
+            .font(font::MONO_BOLD.clone())
+            .style(theme::text::primary)
+            .into()
+    } else {
+        text(content).into()
+    }
+}
@@ -0,0 +1,124 @@
+use std::time::{Duration, SystemTime};
+
+use data::history;
+use iced::widget::{Button, column, container, horizontal_space, row, scrollable, text};
+use iced::{Alignment, Length, Task};
+
+use crate::theme;
+use crate::widget::Element;
+
+/// How much of the list is shown before it scrolls.
+const HEIGHT: f32 = 400.0;
+
+/// How much a branch is indented by.
+const INDENT: f32 = 16.0;
+
+/// How far back the text can be rewound to in one step, with the label of
+/// the button doing it.
+const REWINDS: &[(Duration, &str)] = &[
+    (Duration::from_secs(60), "1 min ago"),
+    (Duration::from_secs(10 * 60), "10 min ago"),
+    (Duration::from_secs(60 * 60), "1 h ago"),
+];
+
+#[derive(Debug)]
+pub struct State {
+    entries: Vec<history::Entry>,
+    /// When the modal was opened, which the times of the entries are shown
+    /// relative to.
+    opened: SystemTime,
+}
+
+#[derive(Debug, Clone)]
+pub enum Message {
+    /// Goes to the state of a node of the history.
+    Travel(usize),
+    /// Goes to the state the text was in this long before the modal was
+    /// opened.
+    Rewind(Duration),
+}
+
+impl State {
+    pub fn new(entries: Vec<history::Entry>) -> Self {
+        Self {
+            entries,
+            opened: SystemTime::now(),
+        }
+    }
+
+    pub fn update(&mut self, message: &Message) -> (Task<Message>, Option<super::Event>) {
+        match message {
+            Message::Travel(node) => (Task::none(), Some(super::Event::TravelHistory(*node))),
+            Message::Rewind(duration) => (
+                Task::none(),
+                self.opened
+                    .checked_sub(*duration)
+                    .map(super::Event::TravelHistoryTo),
+            ),
+        }
+    }
+
+    pub fn view(&self) -> Element<Message> {
+        let entries = self.entries.iter().map(|entry| {
+            let marker = if entry.is_current {
+                "\u{25cf}"
+            } else {
+                "\u{25cb}"
+            };
+            let is_current = entry.is_current;
+
+            Button::new(row![
+                horizontal_space().width(entry.depth as f32 * INDENT),
+                text(format!("{marker} {}", entry.summary)),
+                horizontal_space(),
+                text(ago(self.opened, entry.time)).style(theme::text::secondary),
+            ])
+            .on_press(Message::Travel(entry.id))
+            .style(move |theme, status| theme::button::secondary(theme, status, is_current))
+            .width(Length::Fill)
+            .padding(5)
+            .into()
+        });
+
+        let rewinds = REWINDS.iter().map(|(duration, label)| {
+            Button::new(text(*label))
+                .on_press(Message::Rewind(*duration))
+                .style(|theme, status| theme::button::secondary(theme, status, false))
+                .padding(5)
+                .into()
+        });
+
+        let content = column![
+            text("Undo History"),
+            text("Pick a state to go back or forward to it, branches included.")
+                .style(theme::text::secondary),
+            row![text("Go back to").style(theme::text::secondary)]
+                .extend(rewinds)
+                .spacing(5)
+                .align_y(Alignment::Center),
+            container(scrollable(column(entries).spacing(5))).max_height(HEIGHT),
+        ]
+        .padding(20)
+        .spacing(10)
+        .width(Length::Fixed(400.0));
+
+        container(content)
+            .padding(20)
+            .width(Length::Shrink)
+            .height(Length::Shrink)
+            .style(theme::container::general)
+            .into()
+    }
+}
+
+/// How long before `now` the `time` was, roughly.
+fn ago(now: SystemTime, time: SystemTime) -> String {
+    let seconds = now.duration_since(time).unwrap_or_default().as_secs();
+
+    match seconds {
+        0..60 => String::from("just now"),
+        60..3600 => format!("{} min ago", seconds / 60),
+        3600..86400 => format!("{} h ago", seconds / 3600),
+        _ => format!("{} days ago", seconds / 86400),
+    }
+}
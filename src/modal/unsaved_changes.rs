@@ -0,0 +1,99 @@
+use iced::widget::{button, column, container, horizontal_space, row, text};
+use iced::{Length, Task};
+
+use data::encoding::Encoding;
+
+use crate::buffer;
+use crate::theme;
+use crate::widget::Element;
+
+/// What to do once the unsaved changes have been saved or discarded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    CloseWindow,
+    CloseTab(buffer::Id),
+    /// Reads the file of the buffer again, in the encoding.
+    Reopen(buffer::Id, Encoding),
+}
+
+#[derive(Debug)]
+pub struct State {
+    action: Action,
+    titles: Vec<String>,
+}
+
+#[derive(Debug, Clone)]
+pub enum Message {
+    Save,
+    Discard,
+    Cancel,
+}
+
+impl State {
+    pub fn new(action: Action, titles: Vec<String>) -> Self {
+        Self { action, titles }
+    }
+
+    pub fn update(&mut self, message: &Message) -> (Task<Message>, Option<super::Event>) {
+        let event = match message {
+            Message::Save => super::Event::Save(self.action),
+            Message::Discard => super::Event::Discard(self.action),
+            Message::Cancel => super::Event::CloseModal,
+        };
+
+        (Task::none(), Some(event))
+    }
+
+    pub fn view(&self) -> Element<Message> {
+        let verb = match self.action {
+            Action::CloseWindow | Action::CloseTab(_) => "closing",
+            Action::Reopen(..) => "reopening",
+        };
+        let prompt = match self.titles.as_slice() {
+            [title] => format!("Save changes to {title} before {verb}?"),
+            titles => format!("Save changes to {} files before {verb}?", titles.len()),
+        };
+
+        let files = column(self.titles.iter().map(|title| {
+            text(format!("\u{2022} {title}"))
+                .style(theme::text::secondary)
+                .into()
+        }))
+        .spacing(2);
+
+        let choice = |label, message| {
+            button(text(label))
+                .padding([4, 10])
+                .style(|theme, status| theme::button::secondary(theme, status, false))
+                .on_press(message)
+        };
+
+        let buttons = row![
+            choice("Don't save", Message::Discard),
+            horizontal_space(),
+            choice("Cancel", Message::Cancel),
+            button(text("Save"))
+                .padding([4, 10])
+                .style(|theme, status| theme::button::primary(theme, status, true))
+                .on_press(Message::Save),
+        ]
+        .spacing(10);
+
+        let content = column![
+            text(prompt),
+            text("Your changes will be lost if you don't save them.").style(theme::text::secondary),
+            files,
+            buttons,
+        ]
+        .padding(20)
+        .spacing(10)
+        .width(Length::Fixed(400.0));
+
+        container(content)
+            .padding(20)
+            .width(Length::Shrink)
+            .height(Length::Shrink)
+            .style(theme::container::general)
+            .into()
+    }
+}
@@ -0,0 +1,157 @@
+use std::ops::Range;
+
+use data::buffer::Position;
+use data::caret::{Block, Caret};
+use iced::Task;
+use iced::advanced::widget;
+use iced::widget::{pane_grid, text_editor};
+
+use crate::buffer::{self, Buffer, Replay};
+use crate::wrap::Wrap;
+
+/// A view into a [`Buffer`].
+///
+/// Every pane keeps its own `text_editor::Content`, so the same buffer can be
+/// shown in several panes with independent cursors.
+#[derive(Debug)]
+pub struct Pane {
+    pub buffer: buffer::Id,
+    pub content: text_editor::Content,
+    pub editor: widget::Id,
+    /// The scrollable around the editor and its gutter.
+    pub scrollable: widget::Id,
+    pub viewport: Viewport,
+    /// The selections that expanding the selection grew out of, the most
+    /// recent last, so shrinking it goes back through them.
+    pub expansions: Vec<Range<Position>>,
+    /// The cursors besides the one of the editor, in the document.
+    pub carets: Vec<Caret>,
+    /// The rectangular selection being made, by dragging with alt held or
+    /// selecting columns from the keyboard. It is laid out as the cursor and
+    /// the carets, a line each.
+    pub block: Option<Block>,
+}
+
+/// The part of the editor scrolled into view, in pixels.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Viewport {
+    pub offset: f32,
+    pub width: f32,
+    pub height: f32,
+}
+
+#[derive(Debug, Clone)]
+pub enum Message {
+    Clicked(pane_grid::Pane),
+    Dragged(pane_grid::DragEvent),
+    Resized(pane_grid::ResizeEvent),
+    Split(pane_grid::Axis, pane_grid::Pane),
+    Close(pane_grid::Pane),
+    Maximize(pane_grid::Pane),
+    Restore,
+    Scrolled(pane_grid::Pane, Viewport),
+    /// Selects a line of the document.
+    SelectLine(pane_grid::Pane, usize),
+    /// Folds or unfolds the region at a line of the document.
+    ToggleFold(pane_grid::Pane, usize),
+}
+
+impl Pane {
+    pub fn new(buffer: &Buffer, cursor: Position) -> Self {
+        Self {
+            buffer: buffer.id,
+            content: buffer.content(cursor),
+            editor: widget::Id::unique(),
+            scrollable: widget::Id::unique(),
+            viewport: Viewport::default(),
+            expansions: Vec::new(),
+            carets: Vec::new(),
+            block: None,
+        }
+    }
+
+    pub fn cursor(&self, buffer: &Buffer) -> Position {
+        buffer.cursor_position(&self.content)
+    }
+
+    /// Switches the pane to `buffer`, restoring its last cursor.
+    pub fn show(&mut self, buffer: &Buffer) {
+        self.buffer = buffer.id;
+        self.content = buffer.content(buffer.cursor);
+        self.expansions.clear();
+        self.carets.clear();
+        self.block = None;
+    }
+
+    /// Moves the cursor to `position`, scrolling it into view.
+    pub fn go_to(&mut self, buffer: &Buffer, position: Position) {
+        self.content = buffer.content(position);
+        self.expansions.clear();
+        self.carets.clear();
+        self.block = None;
+    }
+
+    /// Selects the columns of `block` on each of its lines.
+    pub fn select_block(&mut self, buffer: &Buffer, block: Block) {
+        let (primary, carets) = block.carets(&buffer.document);
+
+        buffer.set_caret(&mut self.content, primary);
+        self.carets = carets;
+        self.block = Some(block);
+    }
+
+    /// Catches up with the buffer after it was changed from elsewhere,
+    /// replaying the edits taken from it on the content if there are any,
+    /// rebuilding the content otherwise.
+    pub fn reload(&mut self, buffer: &Buffer, replays: Option<&[Replay]>) {
+        let cursor = self.cursor(buffer);
+        let is_mirrored = replays.is_some_and(|replays| buffer.replay(&mut self.content, replays));
+
+        if !is_mirrored {
+            self.content = buffer.content(cursor);
+        }
+
+        self.expansions.clear();
+        self.block = None;
+
+        for caret in &mut self.carets {
+            *caret = caret.clamp(&buffer.document);
+        }
+    }
+
+    pub fn focus<Message: Send + 'static>(&self) -> Task<Message> {
+        widget::operate(widget::operation::focusable::focus(self.editor.clone()))
+    }
+
+    /// Scrolls the line of the cursor into view, for editors inside of the
+    /// pane's scrollable.
+    pub fn scroll_to_cursor<Message: Send + 'static>(
+        &mut self,
+        wrap: &Wrap,
+        line_height: f32,
+        padding: f32,
+    ) -> Task<Message> {
+        let cursor = buffer::cursor(&self.content);
+        let (row, _) = wrap.row_of(cursor.line, cursor.column);
+        let top = padding + (wrap.top(cursor.line) + row) as f32 * line_height;
+        let bottom = top + line_height;
+
+        let delta = if top < self.viewport.offset {
+            top - self.viewport.offset
+        } else if self.viewport.height > 0.0 && bottom > self.viewport.offset + self.viewport.height
+        {
+            bottom - self.viewport.offset - self.viewport.height
+        } else {
+            return Task::none();
+        };
+
+        // Scrolling again before the scrollable reports its new offset must
+        // not add up.
+        self.viewport.offset += delta;
+
+        widget::operate(widget::operation::scrollable::scroll_by(
+            self.scrollable.clone(),
+            widget::operation::scrollable::AbsoluteOffset { x: 0.0, y: delta },
+        ))
+    }
+}
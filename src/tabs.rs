@@ -0,0 +1,188 @@
+use iced::widget::{button, container, row, text};
+use iced::{Length, padding};
+
+use crate::buffer::{self, Buffer};
+use crate::theme;
+use crate::widget::{Element, Scrollable};
+
+const MAX_CLOSED: usize = 20;
+
+/// The open buffers, in tab order.
+#[derive(Debug)]
+pub struct Tabs {
+    buffers: Vec<Buffer>,
+    active: usize,
+    closed: Vec<Buffer>,
+}
+
+#[derive(Debug, Clone)]
+pub enum Message {
+    Select(usize),
+    Close(usize),
+    Next,
+    Previous,
+    MoveLeft,
+    MoveRight,
+    CloseActive,
+    ReopenClosed,
+}
+
+impl Default for Tabs {
+    fn default() -> Self {
+        Self {
+            buffers: vec![Buffer::default()],
+            active: 0,
+            closed: vec![],
+        }
+    }
+}
+
+impl Tabs {
+    pub fn active(&self) -> &Buffer {
+        &self.buffers[self.active]
+    }
+
+    pub fn active_mut(&mut self) -> &mut Buffer {
+        &mut self.buffers[self.active]
+    }
+
+    pub fn get(&self, id: buffer::Id) -> Option<&Buffer> {
+        self.buffers.iter().find(|buffer| buffer.id == id)
+    }
+
+    pub fn get_mut(&mut self, id: buffer::Id) -> Option<&mut Buffer> {
+        self.buffers.iter_mut().find(|buffer| buffer.id == id)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Buffer> {
+        self.buffers.iter()
+    }
+
+    /// Makes the tab showing `id` the active one.
+    pub fn select(&mut self, id: buffer::Id) {
+        if let Some(index) = self.buffers.iter().position(|buffer| buffer.id == id) {
+            self.active = index;
+        }
+    }
+
+    /// Opens `buffer` in a new tab and focuses it.
+    ///
+    /// A buffer already showing the same file is focused instead, and an
+    /// untouched scratch tab is replaced.
+    pub fn open(&mut self, buffer: Buffer) {
+        if let Some(index) = buffer.path.as_ref().and_then(|path| {
+            self.buffers
+                .iter()
+                .position(|open| open.path.as_ref() == Some(path))
+        }) {
+            self.active = index;
+        } else if self.active().is_scratch() {
+            self.buffers[self.active] = buffer;
+        } else {
+            self.active += 1;
+            self.buffers.insert(self.active, buffer);
+        }
+    }
+
+    pub fn update(&mut self, message: Message) {
+        let len = self.buffers.len();
+
+        match message {
+            Message::Select(index) => {
+                if index < len {
+                    self.active = index;
+                }
+            }
+            Message::Close(index) => self.close(index),
+            Message::CloseActive => self.close(self.active),
+            Message::Next => {
+                self.active = (self.active + 1) % len;
+            }
+            Message::Previous => {
+                self.active = (self.active + len - 1) % len;
+            }
+            Message::MoveLeft => {
+                if self.active > 0 {
+                    self.buffers.swap(self.active, self.active - 1);
+                    self.active -= 1;
+                }
+            }
+            Message::MoveRight => {
+                if self.active + 1 < len {
+                    self.buffers.swap(self.active, self.active + 1);
+                    self.active += 1;
+                }
+            }
+            Message::ReopenClosed => {
+                if let Some(buffer) = self.closed.pop() {
+                    self.open(buffer);
+                }
+            }
+        }
+    }
+
+    pub fn close_buffer(&mut self, id: buffer::Id) {
+        if let Some(index) = self.buffers.iter().position(|buffer| buffer.id == id) {
+            self.close(index);
+        }
+    }
+
+    fn close(&mut self, index: usize) {
+        if index >= self.buffers.len() {
+            return;
+        }
+
+        let buffer = self.buffers.remove(index);
+
+        // A buffer closed with unsaved changes had them discarded, reopening
+        // it must not bring them back.
+        if !buffer.is_scratch() && !buffer.is_dirty() {
+            if self.closed.len() == MAX_CLOSED {
+                self.closed.remove(0);
+            }
+            self.closed.push(buffer);
+        }
+
+        if self.buffers.is_empty() {
+            self.buffers.push(Buffer::default());
+        }
+
+        if index < self.active || self.active == self.buffers.len() {
+            self.active -= 1;
+        }
+    }
+
+    pub fn view(&self) -> Element<'_, Message> {
+        let tabs = self.buffers.iter().enumerate().map(|(index, buffer)| {
+            let selected = index == self.active;
+            let title = if buffer.is_dirty() {
+                format!("\u{2022} {}", buffer.title())
+            } else {
+                buffer.title()
+            };
+
+            let close = button(text("\u{00d7}").style(theme::text::secondary))
+                .padding(padding::left(4).right(4))
+                .style(theme::button::bare)
+                .on_press(Message::Close(index));
+
+            button(row![text(title), close].spacing(6))
+                .padding(padding::left(10).right(4).top(4).bottom(4))
+                .style(move |theme, status| theme::button::secondary(theme, status, selected))
+                .on_press(Message::Select(index))
+                .into()
+        });
+
+        container(
+            Scrollable::new(row(tabs).spacing(2))
+                .direction(iced::widget::scrollable::Direction::Horizontal(
+                    iced::widget::scrollable::Scrollbar::new()
+                        .width(0)
+                        .scroller_width(0),
+                ))
+                .style(theme::scrollable::hidden),
+        )
+        .width(Length::Fill)
+        .into()
+    }
+}
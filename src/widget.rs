@@ -4,6 +4,7 @@ pub use self::combo_box::combo_box;
 pub use self::context_menu::context_menu;
 pub use self::decorate::decorate;
 pub use self::double_pass::double_pass;
+pub use self::editor::editor;
 pub use self::modal::modal;
 use crate::Theme;
 
@@ -11,6 +12,7 @@ pub mod combo_box;
 pub mod context_menu;
 pub mod decorate;
 pub mod double_pass;
+pub mod editor;
 pub mod modal;
 pub mod selectable_rich_text;
 pub mod selectable_text;
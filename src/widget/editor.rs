@@ -0,0 +1,359 @@
+//! The editor of a pane, iced's text editor with decorations drawn on ranges
+//! of its text.
+//!
+//! Subsystems register [`Decoration`]s, like the line of the cursor or the
+//! marker after a folded region, and the widget draws them around the text.
+//! Positions are measured on a monospace grid, wrapped lines being laid out
+//! as in [`Wrap`].
+
+use std::iter;
+use std::ops::Range;
+
+use data::buffer::Position;
+use iced::advanced::renderer::Quad;
+use iced::advanced::text::{self, Paragraph as _, Renderer as _};
+use iced::advanced::widget::Tree;
+use iced::advanced::{Layout, Renderer as _, layout, mouse, renderer};
+use iced::widget::text::{LineHeight, Shaping, Wrapping};
+use iced::widget::text_editor;
+use iced::{Color, Point, Rectangle, Size, alignment};
+
+use super::{Element, Renderer, decorate};
+use crate::Theme;
+use crate::wrap::Wrap;
+
+/// How a [`Decoration`] is drawn.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Style {
+    /// A line under the text of the range.
+    Underline(Color),
+    /// A fill behind the text of the range, up to the right edge of the
+    /// editor for the lines the range continues past.
+    Background(Color),
+    /// The text of the range drawn again in another color.
+    Foreground(Color),
+    /// Text drawn at the start of the range, like a marker after a folded
+    /// line.
+    Glyph(&'static str, Color),
+    /// A cursor at the start of the range, like the ones besides the cursor
+    /// of the editor.
+    Caret(Color),
+}
+
+/// A range of the text of the editor, between two of its positions, and how
+/// it is decorated.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Decoration {
+    pub range: Range<Position>,
+    pub style: Style,
+}
+
+/// The decorations registered for an editor.
+#[derive(Debug, Clone, Default)]
+pub struct Decorations {
+    decorations: Vec<Decoration>,
+}
+
+impl Decorations {
+    pub fn push(&mut self, range: Range<Position>, style: Style) {
+        self.decorations.push(Decoration { range, style });
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.decorations.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Decoration> {
+        self.decorations.iter()
+    }
+}
+
+/// The sizes the text of the editor is laid out with.
+#[derive(Debug, Clone, Copy)]
+pub struct Metrics {
+    pub text_size: f32,
+    pub line_height: f32,
+    pub padding: f32,
+}
+
+#[derive(Debug, Default)]
+struct State {
+    /// The width of a char of the font of the editor.
+    char_width: f32,
+}
+
+/// Draws `decorations` around `editor`, which shows `content` with its lines
+/// wrapped as in `wrap`.
+///
+/// The editor should be styled without a background, it is filled before
+/// the decorations are drawn behind the text.
+pub fn editor<'a, Message: 'a>(
+    editor: impl Into<Element<'a, Message>>,
+    content: &'a text_editor::Content,
+    decorations: Decorations,
+    wrap: Wrap,
+    metrics: Metrics,
+) -> Element<'a, Message> {
+    decorate(editor)
+        .layout(
+            move |state: &mut State,
+                  inner: &Element<'a, Message>,
+                  tree: &mut Tree,
+                  renderer: &Renderer,
+                  limits: &layout::Limits| {
+                state.char_width = <Renderer as text::Renderer>::Paragraph::with_text(text::Text {
+                    content: "0",
+                    bounds: Size::INFINITY,
+                    size: metrics.text_size.into(),
+                    line_height: LineHeight::Absolute(metrics.line_height.into()),
+                    font: renderer.default_font(),
+                    align_x: text::Alignment::Left,
+                    align_y: alignment::Vertical::Top,
+                    shaping: Shaping::Basic,
+                    wrapping: Wrapping::None,
+                })
+                .min_width();
+
+                inner.as_widget().layout(tree, renderer, limits)
+            },
+        )
+        .draw(
+            move |state: &State,
+                  inner: &Element<'a, Message>,
+                  tree: &Tree,
+                  renderer: &mut Renderer,
+                  theme: &Theme,
+                  style: &renderer::Style,
+                  layout: Layout<'_>,
+                  cursor: mouse::Cursor,
+                  viewport: &Rectangle| {
+                let bounds = layout.bounds();
+                let grid = Grid {
+                    content,
+                    wrap: &wrap,
+                    metrics,
+                    char_width: state.char_width,
+                    bounds,
+                    viewport: *viewport,
+                };
+
+                renderer.fill_quad(
+                    Quad {
+                        bounds,
+                        ..Quad::default()
+                    },
+                    theme.colors().general.background,
+                );
+
+                for decoration in decorations.iter() {
+                    if let Style::Background(color) = decoration.style {
+                        for segment in grid.segments(&decoration.range) {
+                            renderer.fill_quad(
+                                Quad {
+                                    bounds: segment.bounds,
+                                    ..Quad::default()
+                                },
+                                color,
+                            );
+                        }
+                    }
+                }
+
+                inner
+                    .as_widget()
+                    .draw(tree, renderer, theme, style, layout, cursor, viewport);
+
+                for decoration in decorations.iter() {
+                    match decoration.style {
+                        Style::Background(_) => {}
+                        Style::Underline(color) => {
+                            for segment in grid.segments(&decoration.range) {
+                                renderer.fill_quad(
+                                    Quad {
+                                        bounds: Rectangle {
+                                            y: segment.bounds.y + segment.bounds.height - 1.0,
+                                            height: 1.0,
+                                            ..segment.bounds
+                                        },
+                                        ..Quad::default()
+                                    },
+                                    color,
+                                );
+                            }
+                        }
+                        Style::Foreground(color) => {
+                            for segment in grid.segments(&decoration.range) {
+                                renderer.fill_quad(
+                                    Quad {
+                                        bounds: segment.bounds,
+                                        ..Quad::default()
+                                    },
+                                    theme.colors().general.background,
+                                );
+                                grid.fill_text(
+                                    renderer,
+                                    &segment.text,
+                                    segment.bounds.position(),
+                                    color,
+                                );
+                            }
+                        }
+                        Style::Glyph(glyph, color) => {
+                            if let Some(position) = grid.point(decoration.range.start) {
+                                grid.fill_text(renderer, glyph, position, color);
+                            }
+                        }
+                        Style::Caret(color) => {
+                            if let Some(position) = grid.point(decoration.range.start) {
+                                renderer.fill_quad(
+                                    Quad {
+                                        bounds: Rectangle::new(
+                                            position,
+                                            Size::new(1.0, metrics.line_height),
+                                        ),
+                                        ..Quad::default()
+                                    },
+                                    color,
+                                );
+                            }
+                        }
+                    }
+                }
+            },
+        )
+        .into()
+}
+
+/// The part of a decoration on one line.
+struct Segment {
+    bounds: Rectangle,
+    text: String,
+}
+
+/// Where the chars of the content are drawn.
+struct Grid<'a> {
+    content: &'a text_editor::Content,
+    wrap: &'a Wrap,
+    metrics: Metrics,
+    char_width: f32,
+    bounds: Rectangle,
+    viewport: Rectangle,
+}
+
+impl Grid<'_> {
+    fn line(&self, index: usize) -> Option<String> {
+        self.content.line(index).map(|line| line.text.into_owned())
+    }
+
+    /// The y of the `row` of `line`, counted from its first row.
+    fn y(&self, line: usize, row: usize) -> f32 {
+        self.bounds.y
+            + self.metrics.padding
+            + (self.wrap.top(line) + row) as f32 * self.metrics.line_height
+    }
+
+    /// The x of the byte `column` of the `text` of a line, on the row starting
+    /// at the byte `start`.
+    fn x(&self, text: &str, start: usize, column: usize) -> f32 {
+        let chars = text
+            .get(start.min(column)..column.min(text.len()))
+            .map_or(0, |prefix| prefix.chars().count());
+
+        self.bounds.x + self.metrics.padding + chars as f32 * self.char_width
+    }
+
+    /// The top left corner of `position`, if its row is in view.
+    fn point(&self, position: Position) -> Option<Point> {
+        let (row, start) = self.wrap.row_of(position.line, position.column);
+        let y = self.y(position.line, row);
+
+        if !self.is_visible(y) {
+            return None;
+        }
+
+        let text = self.line(position.line)?;
+
+        Some(Point::new(self.x(&text, start, position.column), y))
+    }
+
+    fn is_visible(&self, y: f32) -> bool {
+        y + self.metrics.line_height >= self.viewport.y
+            && y <= self.viewport.y + self.viewport.height
+    }
+
+    /// The parts of `range` on each of the rows in view.
+    fn segments(&self, range: &Range<Position>) -> Vec<Segment> {
+        let mut segments = Vec::new();
+
+        for line in range.start.line..=range.end.line {
+            let breaks = self.wrap.breaks(line);
+
+            if !(0..=breaks.len()).any(|row| self.is_visible(self.y(line, row))) {
+                continue;
+            }
+
+            let Some(text) = self.line(line) else {
+                continue;
+            };
+
+            let start = if line == range.start.line {
+                range.start.column
+            } else {
+                0
+            };
+            let end = (line == range.end.line).then_some(range.end.column);
+
+            let rows = iter::once(0)
+                .chain(breaks.iter().copied())
+                .zip(breaks.iter().copied().chain(iter::once(text.len())));
+
+            for (row, (row_start, row_end)) in rows.enumerate() {
+                let is_last = row == breaks.len();
+                let y = self.y(line, row);
+
+                // A range starting where a row ends starts on the next one.
+                if !self.is_visible(y) || (start >= row_end && !is_last) {
+                    continue;
+                }
+
+                let from = start.max(row_start);
+                let (to, right) = match end {
+                    Some(end) if end < row_end || is_last => (end, self.x(&text, row_start, end)),
+                    _ => (row_end, self.bounds.x + self.bounds.width),
+                };
+                let left = self.x(&text, row_start, from);
+
+                if right > left {
+                    segments.push(Segment {
+                        bounds: Rectangle::new(
+                            Point::new(left, y),
+                            Size::new(right - left, self.metrics.line_height),
+                        ),
+                        text: text.get(from..to).unwrap_or_default().to_owned(),
+                    });
+                }
+            }
+        }
+
+        segments
+    }
+
+    fn fill_text(&self, renderer: &mut Renderer, content: &str, position: Point, color: Color) {
+        renderer.fill_text(
+            text::Text {
+                content: content.to_owned(),
+                bounds: Size::new(f32::INFINITY, self.metrics.line_height),
+                size: self.metrics.text_size.into(),
+                line_height: LineHeight::Absolute(self.metrics.line_height.into()),
+                font: renderer.default_font(),
+                align_x: text::Alignment::Left,
+                align_y: alignment::Vertical::Top,
+                shaping: Shaping::Advanced,
+                wrapping: Wrapping::None,
+            },
+            position,
+            color,
+            self.viewport,
+        );
+    }
+}
@@ -0,0 +1,153 @@
+//! Where the lines of an editor wrap, so the gutter and the decorations line
+//! up with the rows the editor draws.
+//!
+//! The font of the editor is monospace, so a row holds a fixed number of
+//! columns. Lines are wrapped like the editor does, before the last word that
+//! doesn't fit in a row, a word longer than a row overflowing it.
+
+use iced::widget::text_editor;
+
+/// The rows of the lines of an editor, from the first one down to the ones
+/// asked for.
+#[derive(Debug, Clone, Default)]
+pub struct Wrap {
+    /// How many columns fit in a row, `None` when lines are not wrapped.
+    columns: Option<usize>,
+    /// The first row of every line laid out, with the byte offsets the rows
+    /// after its first one start at.
+    lines: Vec<(usize, Vec<usize>)>,
+}
+
+impl Wrap {
+    /// Lines taking a single row each.
+    pub fn none() -> Self {
+        Self::default()
+    }
+
+    /// Wraps the lines of `content` at `columns`, down to the line shown on
+    /// `row`.
+    pub fn down_to_row(content: &text_editor::Content, columns: usize, row: usize) -> Self {
+        Self::new(content, columns, |_, top| top > row)
+    }
+
+    /// Wraps the lines of `content` at `columns`, down to `line`.
+    pub fn down_to_line(content: &text_editor::Content, columns: usize, line: usize) -> Self {
+        Self::new(content, columns, |index, _| index > line)
+    }
+
+    /// Wraps the lines of `content` until `is_done` with the index and the
+    /// first row of the next one.
+    fn new(
+        content: &text_editor::Content,
+        columns: usize,
+        is_done: impl Fn(usize, usize) -> bool,
+    ) -> Self {
+        let columns = columns.max(1);
+        let mut lines = Vec::new();
+        let mut top = 0;
+
+        for index in 0..content.line_count() {
+            if is_done(index, top) {
+                break;
+            }
+
+            let breaks = content
+                .line(index)
+                .map(|line| breaks(&line.text, columns))
+                .unwrap_or_default();
+            let rows = breaks.len() + 1;
+
+            lines.push((top, breaks));
+            top += rows;
+        }
+
+        Self {
+            columns: Some(columns),
+            lines,
+        }
+    }
+
+    /// The row `line` starts on.
+    pub fn top(&self, line: usize) -> usize {
+        match self.lines.get(line) {
+            Some((top, _)) => *top,
+            None => match self.lines.last() {
+                // Lines that were not laid out are counted as a row each.
+                Some((top, breaks)) => top + breaks.len() + line - self.lines.len() + 1,
+                None => line,
+            },
+        }
+    }
+
+    /// How many rows `line` takes.
+    pub fn rows(&self, line: usize) -> usize {
+        self.breaks(line).len() + 1
+    }
+
+    /// The byte offsets the rows of `line` after its first one start at.
+    pub fn breaks(&self, line: usize) -> &[usize] {
+        self.lines
+            .get(line)
+            .map_or(&[], |(_, breaks)| breaks.as_slice())
+    }
+
+    /// The line shown on `row`.
+    pub fn line_at(&self, row: usize) -> usize {
+        if self.columns.is_none() {
+            return row;
+        }
+
+        let laid_out = self.lines.partition_point(|(top, _)| *top <= row);
+
+        match self.lines.last() {
+            Some((top, breaks)) if laid_out == self.lines.len() => {
+                (self.lines.len() - 1) + row.saturating_sub(top + breaks.len())
+            }
+            _ => laid_out.saturating_sub(1),
+        }
+    }
+
+    /// The row of `line` holding the byte `column`, counted from its first
+    /// row, and the byte offset that row starts at.
+    pub fn row_of(&self, line: usize, column: usize) -> (usize, usize) {
+        let breaks = self.breaks(line);
+        let row = breaks.partition_point(|start| *start <= column);
+
+        (row, if row == 0 { 0 } else { breaks[row - 1] })
+    }
+}
+
+/// The byte offsets the rows of `line` after its first one start at, when
+/// wrapped at `columns`.
+fn breaks(line: &str, columns: usize) -> Vec<usize> {
+    // Lines shorter in bytes are shorter in chars too.
+    if line.len() <= columns {
+        return Vec::new();
+    }
+
+    let mut breaks = Vec::new();
+    let mut width = 0;
+    let mut chars = line.char_indices().peekable();
+
+    while let Some(&(start, _)) = chars.peek() {
+        let mut word = 0;
+
+        while chars.next_if(|(_, c)| !c.is_whitespace()).is_some() {
+            word += 1;
+        }
+
+        if width > 0 && width + word > columns {
+            breaks.push(start);
+            width = 0;
+        }
+
+        width += word;
+
+        // Spaces hang past the end of the row instead of starting another.
+        while chars.next_if(|(_, c)| c.is_whitespace()).is_some() {
+            width += 1;
+        }
+    }
+
+    breaks
+}